@@ -0,0 +1,293 @@
+/* 2023 (c) | SERGAZIN SOFTWARE
+ * Derive macros for rust_mongodb_model_methods,
+ * enabled through its `derive` feature.
+*/
+
+use proc_macro::TokenStream;
+use quote::quote;
+
+// PROJECTION ======================================================================================================
+// `#[derive(Projection)] #[projection(of = User)]` on a view struct implements `Projection` for it and checks at
+// compile time that every field exists on `User` with the same type, so renaming a model field breaks the build
+// instead of silently returning defaults:
+//
+//     #[derive(Deserialize, Projection)]
+//     #[projection(of = User)]
+//     struct UserSummary { #[serde(rename = "_id")] id: IdType, name: String }
+//
+//     let rows = User::find_projected::<UserSummary>(doc! { "active": true }).await?;
+//
+// The projection document itself is built from the struct's serde field names, so serde renames apply.
+#[proc_macro_derive(Projection, attributes(projection))]
+pub fn derive_projection(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match projection(input) {
+        Ok(x) => x.into(),
+        Err(x) => x.to_compile_error().into(),
+    }
+}
+
+fn projection(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "`Projection` structs can't be generic"));
+    }
+
+    let mut of: Option<syn::Path> = None;
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("projection")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("of") {
+                of = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `of = Model`"))
+            }
+        })?;
+    }
+    let Some(of) = of else {
+        return Err(syn::Error::new_spanned(name, "missing `#[projection(of = Model)]`"));
+    };
+
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(x), .. }) => &x.named,
+        _ => return Err(syn::Error::new_spanned(name, "`Projection` needs a struct with named fields")),
+    };
+    // `let _: &Type = &model.field;` per field, a missing field or another type fails to compile
+    let checks = fields.iter().map(|field| {
+        let (ident, ty) = (&field.ident, &field.ty);
+        quote::quote_spanned! { syn::spanned::Spanned::span(ty)=> let _: &#ty = &model.#ident; }
+    });
+
+    Ok(quote! {
+        impl ::rust_mongodb_model_methods::projection::Projection for #name {
+            type Of = #of;
+        }
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn check(model: &#of) {
+                #(#checks)*
+            }
+        };
+    })
+}
+
+// FIELDS ==========================================================================================================
+// `#[derive(Fields)]` on a model generates `Model::fields()`, a struct with one `Field<Model, Type>` per field,
+// named as serde stores it (`rename` and `rename_all` applied, `skip` and `flatten` fields left out):
+//
+//     #[derive(Serialize, Deserialize, Fields)]
+//     #[serde(rename_all = "camelCase")]
+//     struct User { #[serde(rename = "_id")] id: IdType, last_login: DateTime }
+//
+//     User::set_field(&id, User::fields().last_login, DateTime::now()).await?;   // sets `lastLogin`
+#[proc_macro_derive(Fields)]
+pub fn derive_fields(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match fields(input) {
+        Ok(x) => x.into(),
+        Err(x) => x.to_compile_error().into(),
+    }
+}
+
+fn fields(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let (name, vis) = (&input.ident, &input.vis);
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "`Fields` models can't be generic"));
+    }
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(x), .. }) => &x.named,
+        _ => return Err(syn::Error::new_spanned(name, "`Fields` needs a struct with named fields")),
+    };
+
+    let rename_all = match serde_attrs(&input.attrs)?.rename_all {
+        Some(x) => Some(RenameRule::parse(&x)?),
+        None => None,
+    };
+    let mut members = Vec::new();
+    let mut values = Vec::new();
+    for field in fields {
+        let attrs = serde_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let (ident, ty) = (field.ident.as_ref().expect("named field"), &field.ty);
+        let stored = match (attrs.rename, &rename_all) {
+            (Some(x), _) => x.value(),
+            (None, Some(rule)) => rule.apply(&ident.unraw()),
+            (None, None) => ident.unraw(),
+        };
+        members.push(quote! { pub #ident: ::rust_mongodb_model_methods::field::Field<#name, #ty> });
+        values.push(quote! { #ident: ::rust_mongodb_model_methods::field::Field::new(#stored) });
+    }
+
+    let fields_name = quote::format_ident!("{}Fields", name);
+    Ok(quote! {
+        #[derive(Debug, Clone, Copy)]
+        #vis struct #fields_name {
+            #(#members,)*
+        }
+
+        impl #name {
+            #vis const fn fields() -> #fields_name {
+                #fields_name { #(#values,)* }
+            }
+        }
+    })
+}
+
+// REGISTER ========================================================================================================
+// `#[derive(Register)]` adds the model to the `registry` before `main` runs, in place of a `register::<User>()`
+// call. Models whose error type isn't `Error` name theirs:
+//
+//     #[derive(Serialize, Deserialize, Register)]
+//     struct User { #[serde(rename = "_id")] id: IdType, name: String }
+//
+//     #[derive(Serialize, Deserialize, Register)]
+//     #[register(error = AppError)]
+//     struct Invoice { #[serde(rename = "_id")] id: IdType, total: Decimal }
+#[proc_macro_derive(Register, attributes(register))]
+pub fn derive_register(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match register(input) {
+        Ok(x) => x.into(),
+        Err(x) => x.to_compile_error().into(),
+    }
+}
+
+fn register(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "`Register` models can't be generic"));
+    }
+
+    let mut error: Option<syn::Path> = None;
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("register")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("error") {
+                error = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `error = ErrorType`"))
+            }
+        })?;
+    }
+    let error = match error {
+        Some(x) => quote! { #x },
+        None => quote! { ::rust_mongodb_model_methods::Error },
+    };
+
+    Ok(quote! {
+        ::rust_mongodb_model_methods::registry::inventory::submit! {
+            ::rust_mongodb_model_methods::registry::Registration(
+                ::rust_mongodb_model_methods::registry::entry::<#name, #error>
+            )
+        }
+    })
+}
+
+trait Unraw {
+    fn unraw(&self) -> String;
+}
+
+impl Unraw for syn::Ident {
+    fn unraw(&self) -> String {
+        let name = self.to_string();
+        name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+    }
+}
+
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<syn::LitStr>,
+    rename_all: Option<syn::LitStr>,
+    skip: bool,
+}
+
+// The `#[serde(...)]` parts that change stored names, everything else is read past
+fn serde_attrs(attrs: &[syn::Attribute]) -> syn::Result<SerdeAttrs> {
+    let mut found = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|x| x.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            let is = |name: &str| meta.path.is_ident(name);
+            if (is("rename") || is("rename_all")) && meta.input.peek(syn::token::Paren) {
+                // `rename(serialize = "..", deserialize = "..")`, the serialized name is the stored one
+                let mut serialized = None;
+                meta.parse_nested_meta(|inner| {
+                    let value: syn::LitStr = inner.value()?.parse()?;
+                    if inner.path.is_ident("serialize") {
+                        serialized = Some(value);
+                    }
+                    Ok(())
+                })?;
+                match is("rename") {
+                    true => found.rename = serialized.or(found.rename.take()),
+                    false => found.rename_all = serialized.or(found.rename_all.take()),
+                }
+            } else if is("rename") {
+                found.rename = Some(meta.value()?.parse()?);
+            } else if is("rename_all") {
+                found.rename_all = Some(meta.value()?.parse()?);
+            } else {
+                found.skip |= is("skip") || is("flatten");
+                skip_meta(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(found)
+}
+
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|inner| skip_meta(&inner))?;
+    }
+    Ok(())
+}
+
+// serde's `rename_all` rules for fields, which are snake_case to begin with
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(rule: &syn::LitStr) -> syn::Result<Self> {
+        Ok(match rule.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return Err(syn::Error::new_spanned(rule, "unknown `rename_all` rule")),
+        })
+    }
+
+    fn apply(&self, field: &str) -> String {
+        let pascal = || field.split('_').map(|x| first_char(x, char::to_ascii_uppercase)).collect::<String>();
+        match self {
+            Self::Lower | Self::Snake => field.to_string(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => pascal(),
+            Self::Camel => first_char(&pascal(), char::to_ascii_lowercase),
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+fn first_char(word: &str, case: fn(&char) -> char) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|x| case(&x).to_string() + chars.as_str()).unwrap_or_default()
+}
@@ -0,0 +1,20 @@
+// ARCHIVAL ========================================================================================================
+// `archive_older_than` moves the documents whose `field` is before `cutoff` to the model's archive collection,
+// `<collection>_archive` in the same database unless `archive()` says otherwise:
+//
+//     let cutoff = bson::DateTime::from_millis(now - 90 * DAY_MS);
+//     let pause = Duration::from_millis(500);
+//     Event::archive_older_than("created_at", cutoff, 1000, pause, |x| println!("{} archived", x.archived)).await?;
+//
+// Each batch is copied, then deleted from the live collection, only in the version copied. A run stopped in
+// between leaves copies in both, running it again copies them over and deletes the rest, so an interrupted run is
+// resumed by starting it over. A document updated between its copy and the delete stays live, the next run copies
+// the newer version over the archived one (or leaves both if it's past `cutoff` now). Index `field`.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveProgress {
+    // Documents deleted from the live collection after being copied
+    pub archived: u64,
+    // Last `_id` of the batches done so far
+    pub last_id: bson::Bson,
+}
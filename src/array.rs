@@ -0,0 +1,90 @@
+// ARRAYS ==========================================================================================================
+// Updates to array fields without reading the document first, typed by the array's `Field`:
+//
+//     // newest 50 entries, most recent first
+//     let push = Push::one(activity).sort(doc! { "at": -1 }).slice(50);
+//     User::push(&id, User::fields().recent_activity, push).await?;
+//
+//     User::pull(&id, User::fields().tags, "beta".to_string()).await?;
+//     User::pull_where(&id, User::fields().sessions, doc! { "expires_at": { "$lt": now } }).await?;
+//
+//     User::add_to_set(&id, User::fields().roles, Role::Admin).await?;
+//     let admins = User::count_where(FilterExpr::array_contains("roles", "admin")).await?;
+//
+// `slice` runs after `sort`, so sorting by date and slicing to `n` keeps the first `n` by date, a negative `n`
+// keeps the last ones instead.
+
+// Field types stored as BSON arrays
+pub trait Array {
+    type Item;
+}
+
+impl<T> Array for Vec<T> {
+    type Item = T;
+}
+
+impl<T> Array for std::collections::VecDeque<T> {
+    type Item = T;
+}
+
+impl<T, S> Array for std::collections::HashSet<T, S> {
+    type Item = T;
+}
+
+impl<T> Array for std::collections::BTreeSet<T> {
+    type Item = T;
+}
+
+impl<A: Array> Array for Option<A> {
+    type Item = A::Item;
+}
+
+#[derive(Debug, Clone)]
+pub struct Push<T> {
+    each: Vec<T>,
+    position: Option<i32>,
+    sort: Option<bson::Bson>,
+    slice: Option<i32>,
+}
+
+impl<T: serde::Serialize> Push<T> {
+    pub fn one(value: T) -> Self {
+        Self::each(vec![value])
+    }
+
+    pub fn each(values: Vec<T>) -> Self {
+        Self { each: values, position: None, sort: None, slice: None }
+    }
+
+    // Inserts at `index` instead of appending, negative counts from the end
+    pub fn position(mut self, index: i32) -> Self {
+        self.position = Some(index);
+        self
+    }
+
+    // `1` / `-1` for arrays of plain values, `{ field: 1, ... }` for arrays of documents
+    pub fn sort(mut self, sort: impl Into<bson::Bson>) -> Self {
+        self.sort = Some(sort.into());
+        self
+    }
+
+    // Keeps the first `n` elements, or the last `-n` for a negative `n`
+    pub fn slice(mut self, n: i32) -> Self {
+        self.slice = Some(n);
+        self
+    }
+
+    pub fn to_bson(&self) -> Result<bson::Bson, bson::ser::Error> {
+        let mut push = bson::doc! { "$each": bson::to_bson(&self.each)? };
+        if let Some(position) = self.position {
+            push.insert("$position", position);
+        }
+        if let Some(sort) = &self.sort {
+            push.insert("$sort", sort.clone());
+        }
+        if let Some(slice) = self.slice {
+            push.insert("$slice", slice);
+        }
+        Ok(bson::Bson::Document(push))
+    }
+}
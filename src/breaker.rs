@@ -0,0 +1,162 @@
+// CIRCUIT BREAKER =================================================================================================
+// After `failure_threshold` failures in a row the breaker opens and every call fails right away with
+// `Error::CircuitOpen` for `open_for`, then `half_open_probes` calls are let through: the breaker closes again once
+// they all succeed and opens for another `open_for` as soon as one fails. Models on the same cluster share one:
+//
+//     static CLUSTER: CircuitBreaker = CircuitBreaker::new(5, Duration::from_secs(10), 2);
+//
+//     fn circuit_breaker() -> Option<&'static CircuitBreaker> {
+//         Some(&CLUSTER)
+//     }
+//
+// Only errors pointing at the cluster count as failures (network, server selection, pool cleared, step-downs and
+// `maxTimeMS` expiry). Duplicate keys, validation errors and the like say nothing about its health.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_for: Duration,
+    half_open_probes: u32,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, succeeded: u32 },
+}
+
+// One admitted call, gives its half-open slot back when dropped without an outcome (e.g. the caller cancelled)
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl CircuitBreaker {
+    pub const fn new(failure_threshold: u32, open_for: Duration, half_open_probes: u32) -> Self {
+        Self { failure_threshold, open_for, half_open_probes, state: Mutex::new(State::Closed { failures: 0 }) }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.lock() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if Instant::now() < until => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    // Runs `call` unless the breaker is open and records how it went
+    pub async fn call<T, F>(&self, call: F) -> Result<T, Error>
+    where
+        F: std::future::Future<Output = Result<T, mongodb::error::Error>>,
+    {
+        let mut permit = self.admit()?;
+        let result = call.await;
+        permit.record(result.as_ref().err().is_none_or(|x| !is_cluster_failure(x)));
+        result.map_err(Error::from)
+    }
+
+    fn admit(&self) -> Result<Permit<'_>, Error> {
+        let mut state = self.lock();
+        if let State::Open { until } = *state {
+            if Instant::now() < until {
+                return Err(Error::CircuitOpen);
+            }
+            *state = State::HalfOpen { in_flight: 0, succeeded: 0 };
+        }
+        match &mut *state {
+            State::HalfOpen { in_flight, succeeded } if *in_flight + *succeeded < self.half_open_probes.max(1) => {
+                *in_flight += 1;
+                Ok(Permit { breaker: self, probe: true })
+            }
+            State::HalfOpen { .. } => Err(Error::CircuitOpen),
+            _ => Ok(Permit { breaker: self, probe: false }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|x| x.into_inner())
+    }
+}
+
+impl Permit<'_> {
+    fn record(&mut self, success: bool) {
+        let breaker = self.breaker;
+        let mut state = breaker.lock();
+        let opened = State::Open { until: Instant::now() + breaker.open_for };
+        match &mut *state {
+            State::Closed { failures } if success => *failures = 0,
+            State::Closed { failures } => {
+                *failures += 1;
+                if *failures >= breaker.failure_threshold.max(1) {
+                    *state = opened;
+                }
+            }
+            State::HalfOpen { .. } if !success => *state = opened,
+            State::HalfOpen { in_flight, succeeded } if self.probe => {
+                *in_flight -= 1;
+                *succeeded += 1;
+                if *succeeded >= breaker.half_open_probes.max(1) {
+                    *state = State::Closed { failures: 0 };
+                }
+            }
+            // Calls admitted before the breaker opened
+            _ => {}
+        }
+        self.probe = false;
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            if let State::HalfOpen { in_flight, .. } = &mut *self.breaker.lock() {
+                *in_flight = in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+// `call` through the model's breaker, straight through without one
+pub(crate) async fn guard<T, F>(breaker: Option<&CircuitBreaker>, call: F) -> Result<T, Error>
+where
+    F: std::future::Future<Output = Result<T, mongodb::error::Error>>,
+{
+    match breaker {
+        Some(breaker) => breaker.call(call).await,
+        None => call.await.map_err(Error::from),
+    }
+}
+
+// An error that may go away when the call is made again: the breaker open or a failure pointing at the cluster
+pub(crate) fn is_transient(error: &Error) -> bool {
+    match error.root() {
+        Error::CircuitOpen => true,
+        Error::DBError(x) => is_cluster_failure(x),
+        _ => false,
+    }
+}
+
+fn is_cluster_failure(error: &mongodb::error::Error) -> bool {
+    use mongodb::error::ErrorKind;
+
+    match error.kind.as_ref() {
+        ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } | ErrorKind::ServerSelection { .. } => true,
+        ErrorKind::DnsResolve { .. } => true,
+        // MaxTimeMSExpired, ShutdownInProgress, InterruptedAtShutdown, NotWritablePrimary, PrimarySteppedDown,
+        // NotPrimaryNoSecondaryOk, NotPrimaryOrSecondary
+        ErrorKind::Command(x) => matches!(x.code, 50 | 91 | 11600 | 10107 | 189 | 13435 | 13436),
+        _ => false,
+    }
+}
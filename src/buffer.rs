@@ -0,0 +1,276 @@
+// BUFFERED WRITES =================================================================================================
+// `BufferedWriter` queues single-document writes and sends them as a few commands instead of one round-trip each,
+// for import code writing row by row:
+//
+//     let mut writer = BufferedWriter::<Product>::new().max_ops(500).window(Duration::from_millis(50));
+//     for row in rows {
+//         writer.insert(&row.into()).await?;
+//         writer.update_one(doc! { "sku": &row.parent }, doc! { "$inc": { "variants": 1 } }).await?;
+//     }
+//     let summary = writer.finish().await?;
+//
+// or for a scope, flushed when it ends:
+//
+//     let (_, summary) = buffer::scope::<Product, Error, _, _>(|writer| Box::pin(async move {
+//         writer.delete_one(doc! { "sku": "old" }).await
+//     }))
+//     .await?;
+//
+// The queue is flushed once it holds `max_ops` writes, or when a write comes in after its oldest one has waited
+// `window`; nothing flushes an idle writer, end with `flush()` or `finish()`, queued writes are lost on drop.
+// Writes keep their order: each run of the same kind goes out as one ordered `insert`, `update` or `delete`
+// command, several when it would take a command over the 16MB BSON limit. The first rejected write fails the
+// flush, the ones before it are written, it and the rest stay queued for another `flush()` or a `discard()`.
+//
+// Each write is checked like its single-document method (`read_only`, `immutable_fields`, `shard_key`) when it's
+// queued, unique fields and references aren't looked up first, the server's unique indexes still apply.
+
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use crate::{breaker, id_type, immutable, shard, write, Error, ErrorContext, RustMongoDBModelMethods};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FlushSummary {
+    pub inserted: u64,
+    pub matched: u64,
+    pub modified: u64,
+    pub deleted: u64,
+    // Commands sent
+    pub commands: u64,
+}
+
+impl FlushSummary {
+    fn add(&mut self, other: &FlushSummary) {
+        self.inserted += other.inserted;
+        self.matched += other.matched;
+        self.modified += other.modified;
+        self.deleted += other.deleted;
+        self.commands += other.commands;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Kind {
+    // Command name and the field holding its statements
+    fn command(self) -> (&'static str, &'static str) {
+        match self {
+            Kind::Insert => ("insert", "documents"),
+            Kind::Update => ("update", "updates"),
+            Kind::Delete => ("delete", "deletes"),
+        }
+    }
+}
+
+#[must_use = "queued writes are lost when it's dropped, end with `finish()`"]
+pub struct BufferedWriter<M, E = Error> {
+    queue: Vec<(Kind, bson::Document)>,
+    oldest: Option<Instant>,
+    max_ops: usize,
+    window: Duration,
+    // Of the flushes so far
+    totals: FlushSummary,
+    model: PhantomData<fn() -> (M, E)>,
+}
+
+// Runs `f` with a writer and flushes it when `f` succeeds, returning its result and the totals
+pub async fn scope<M, E, T, F>(f: F) -> Result<(T, FlushSummary), E>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+    F: for<'a> FnOnce(&'a mut BufferedWriter<M, E>) -> futures::future::BoxFuture<'a, Result<T, E>>,
+{
+    let mut writer = BufferedWriter::new();
+    let result = f(&mut writer).await?;
+    Ok((result, writer.finish().await?))
+}
+
+impl<M, E> Default for BufferedWriter<M, E>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, E> BufferedWriter<M, E>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    // 1000 writes or 100ms
+    pub fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+            oldest: None,
+            max_ops: 1000,
+            window: Duration::from_millis(100),
+            totals: FlushSummary::default(),
+            model: PhantomData,
+        }
+    }
+
+    pub fn max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = max_ops.max(1);
+        self
+    }
+
+    // Only checked when a write is queued: an idle writer doesn't flush on its own, and what's still queued when it's
+    // dropped is lost, end with `finish()`
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    // Writes queued and not sent yet
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    // An unset `_id` gets `generate_id()`, like in `create_one`
+    pub async fn insert(&mut self, data: &M) -> Result<(), E> {
+        let context = self.context();
+        let mut document = bson::to_document(data).map_err(context.wrapper(M::map_error))?;
+        if document.get("_id").is_none_or(id_type::is_unset) {
+            document.insert("_id", M::generate_id());
+        }
+        shard::check_document(M::shard_key(), &document).map_err(context.wrapper(M::map_error))?;
+        self.push(Kind::Insert, document).await
+    }
+
+    // `update` with operators, like `update_one_with`
+    pub async fn update_one(&mut self, filter: bson::Document, update: bson::Document) -> Result<(), E> {
+        let context = self.context().filter(&filter);
+        shard::check_filter(M::shard_key(), &filter, true).map_err(context.wrapper(M::map_error))?;
+        let modifications = update.clone().into();
+        immutable::check(M::immutable_fields(), &modifications).map_err(context.wrapper(M::map_error))?;
+        let mut statement = bson::doc! { "q": filter, "u": update };
+        self.collate(&mut statement)?;
+        self.push(Kind::Update, statement).await
+    }
+
+    pub async fn delete_one(&mut self, filter: bson::Document) -> Result<(), E> {
+        let context = self.context().filter(&filter);
+        shard::check_filter(M::shard_key(), &filter, true).map_err(context.wrapper(M::map_error))?;
+        let mut statement = bson::doc! { "q": filter, "limit": 1 };
+        self.collate(&mut statement)?;
+        self.push(Kind::Delete, statement).await
+    }
+
+    // Sends the queue now, returns what this flush wrote. On an error the writes not written stay queued, a rejected
+    // one first: `flush()` again or `discard()` them.
+    pub async fn flush(&mut self) -> Result<FlushSummary, E> {
+        let context = self.context();
+        crate::writable::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let mut flushed = FlushSummary::default();
+        let result = self.send(&mut flushed).await;
+        self.totals.add(&flushed);
+        if self.queue.is_empty() {
+            self.oldest = None;
+        }
+        result.map_err(|x| M::map_error(context.wrap(x)))?;
+        Ok(flushed)
+    }
+
+    // Drops the queued writes without sending them, returns how many there were
+    pub fn discard(&mut self) -> usize {
+        self.oldest = None;
+        std::mem::take(&mut self.queue).len()
+    }
+
+    async fn send(&mut self, flushed: &mut FlushSummary) -> Result<(), Error> {
+        let collection = crate::writes::<M, E>();
+        let database = collection.client().database(&collection.namespace().db);
+        while let Some(&(kind, _)) = self.queue.first() {
+            let run = self.queue.iter().take_while(|(x, _)| *x == kind).map(|(_, x)| x);
+            let run = write::fitting(run)?;
+            let statements: Vec<bson::Document> = self.queue[..run].iter().map(|(_, x)| x.clone()).collect();
+
+            let (name, field) = kind.command();
+            let mut command = bson::doc! { name: collection.name(), field: statements, "ordered": true };
+            if let Some(write_concern) = collection.write_concern() {
+                command.insert("writeConcern", bson::to_bson(write_concern)?);
+            }
+            let response = breaker::guard(M::circuit_breaker(), database.run_command(command, None)).await?;
+            flushed.commands += 1;
+
+            let written = response.get("n").and_then(crate::as_u64).unwrap_or_default();
+            match kind {
+                Kind::Insert => flushed.inserted += written,
+                Kind::Update => {
+                    flushed.matched += written;
+                    flushed.modified += response.get("nModified").and_then(crate::as_u64).unwrap_or_default();
+                }
+                Kind::Delete => flushed.deleted += written,
+            }
+            match rejected(kind, &response) {
+                Ok(()) => drop(self.queue.drain(..run)),
+                Err((sent, error)) => {
+                    self.queue.drain(..sent.min(run));
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Flushes the rest, returns what every flush wrote
+    pub async fn finish(mut self) -> Result<FlushSummary, E> {
+        self.flush().await?;
+        Ok(self.totals)
+    }
+
+    async fn push(&mut self, kind: Kind, statement: bson::Document) -> Result<(), E> {
+        crate::writable::<M, E>().map_err(self.context().wrapper(M::map_error))?;
+        self.queue.push((kind, statement));
+        let oldest = *self.oldest.get_or_insert_with(Instant::now);
+        if self.queue.len() >= self.max_ops || oldest.elapsed() >= self.window {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    fn collate(&self, statement: &mut bson::Document) -> Result<(), E> {
+        if let Some(collation) = M::collation() {
+            let collation = bson::to_document(&collation).map_err(self.context().wrapper(M::map_error))?;
+            statement.insert("collation", collation);
+        }
+        Ok(())
+    }
+
+    fn context(&self) -> ErrorContext {
+        ErrorContext::new(M::collection().name(), "buffered_write")
+    }
+}
+
+// The first rejected statement or write concern failure of a command response, with how many statements of the
+// command went through before it
+fn rejected(kind: Kind, response: &bson::Document) -> Result<(), (usize, Error)> {
+    let failed = |message: String| match kind {
+        Kind::Insert => Error::CreateFailed(message),
+        Kind::Update => Error::UpdateFailed(message),
+        Kind::Delete => Error::DeleteFailed(message),
+    };
+    let errors = response.get_array("writeErrors").map(|x| x.as_slice()).unwrap_or_default();
+    if let Some(error) = errors.first().and_then(|x| x.as_document()) {
+        let index = error.get("index").and_then(crate::as_u64).unwrap_or_default();
+        let message = error.get_str("errmsg").unwrap_or_default();
+        let error = failed(format!("Buffered write {} of the batch failed: {}", index, message));
+        return Err((index as usize, error));
+    }
+    if let Ok(error) = response.get_document("writeConcernError") {
+        let message = error.get_str("errmsg").unwrap_or_default();
+        return Err((usize::MAX, failed(format!("Write concern not satisfied: {}", message))));
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+// CANCELLATION ====================================================================================================
+// Long operations stop as soon as their `CancellationToken` is cancelled, closing their cursor on the way out:
+//
+//     let token = CancellationToken::new();
+//     let mut orders = cancel::until_cancelled(Order::tail(doc! {}), token.clone());
+//     // elsewhere, on shutdown
+//     token.cancel();
+//
+// Dropping a stream or future before it's done works the same way: the driver kills a cursor that wasn't
+// exhausted when it's dropped, so nothing stays open on the server. The token is for stopping work from another
+// task without holding on to it.
+
+use futures::StreamExt;
+pub use tokio_util::sync::CancellationToken;
+
+use crate::Error;
+
+// Ends `stream` once `token` is cancelled
+pub fn until_cancelled<T: Send + 'static>(
+    stream: futures::stream::BoxStream<'static, T>,
+    token: CancellationToken,
+) -> futures::stream::BoxStream<'static, T> {
+    stream.take_until(token.cancelled_owned()).boxed()
+}
+
+// `future`'s result, or `Error::Cancelled` when `token` is cancelled first (`future` is dropped then)
+pub(crate) async fn or_cancelled<T, X, F>(token: &CancellationToken, future: F) -> Result<T, Error>
+where
+    X: Into<Error>,
+    F: std::future::Future<Output = Result<T, X>>,
+{
+    let cancelled = std::pin::pin!(token.cancelled());
+    let future = std::pin::pin!(future);
+    match futures::future::select(cancelled, future).await {
+        futures::future::Either::Left(_) => Err(Error::Cancelled),
+        futures::future::Either::Right((x, _)) => x.map_err(Into::into),
+    }
+}
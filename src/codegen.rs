@@ -0,0 +1,277 @@
+// CODE GENERATION =================================================================================================
+// `generate_model` samples an existing collection and writes a model for it, a starting point to edit rather than
+// a finished model:
+//
+//     let collection = rms::database()?.collection::<Document>("legacy_orders");
+//     std::fs::write("src/models/order.rs", rms::generate_model(&collection, "Order", 1000).await?)?;
+//
+//     #[derive(Debug, Clone, Serialize, Deserialize)]
+//     pub struct Order {
+//         #[serde(rename = "_id")]
+//         pub id: IdType,
+//         #[serde(rename = "customerId")]
+//         pub customer_id: ObjectId,
+//         #[serde(default, skip_serializing_if = "Option::is_none")]
+//         pub note: Option<String>,
+//         pub lines: Vec<OrderLines>,
+//     }
+//
+// plus a struct per embedded document and the `RustMongoDBModelMethods` impl. A field missing from or `null` in
+// some sampled documents becomes an `Option`; ints and longs widen to `i64`, numbers mixed with doubles to `f64`;
+// anything else holding more than one type stays a `Bson` with a comment listing the types seen. `_id` is always
+// `IdType`, check the type it's stored with against the ID feature.
+
+use futures::TryStreamExt;
+
+use crate::{error, Error, ErrorContext};
+
+#[derive(Debug, Default)]
+struct Shape {
+    // Documents of this shape seen
+    count: u64,
+    // In the order first seen
+    fields: Vec<(String, Field)>,
+}
+
+#[derive(Debug, Default)]
+struct Field {
+    present: u64,
+    // BSON type names, null included
+    types: Vec<&'static str>,
+    document: Option<Box<Shape>>,
+    elements: Option<Box<Field>>,
+}
+
+impl Shape {
+    fn add(&mut self, document: &bson::Document) {
+        self.count += 1;
+        for (name, value) in document {
+            let index = match self.fields.iter().position(|(x, _)| x == name) {
+                Some(x) => x,
+                None => {
+                    self.fields.push((name.clone(), Field::default()));
+                    self.fields.len() - 1
+                }
+            };
+            self.fields[index].1.add(value);
+        }
+    }
+}
+
+impl Field {
+    fn add(&mut self, value: &bson::Bson) {
+        self.present += 1;
+        let name = error::type_name(value);
+        if !self.types.contains(&name) {
+            self.types.push(name);
+        }
+        match value {
+            bson::Bson::Document(x) => self.document.get_or_insert_with(Default::default).add(x),
+            bson::Bson::Array(x) => {
+                let elements = self.elements.get_or_insert_with(Default::default);
+                x.iter().for_each(|x| elements.add(x));
+            }
+            _ => {}
+        }
+    }
+
+    fn nullable(&self) -> bool {
+        self.types.contains(&"null")
+    }
+
+    // Rust type of the non-null values, or the types seen when it has to stay a `Bson`. Embedded documents are
+    // added to `structs` as `path`.
+    fn rust_type<'a>(&'a self, path: &str, structs: &mut Vec<(String, &'a Shape)>) -> Result<String, String> {
+        let mut types: Vec<&str> = self.types.iter().copied().filter(|x| *x != "null").collect();
+        let numbers = ["int", "long", "double"];
+        if types.len() > 1 && types.iter().all(|x| numbers.contains(x)) {
+            types = vec![if types.contains(&"double") { "double" } else { "long" }];
+        }
+        let scalar = match types.as_slice() {
+            [] => return Err("null only".to_string()),
+            [x] => *x,
+            x => return Err(x.join(", ")),
+        };
+        Ok(match scalar {
+            "string" => "String".to_string(),
+            "int" => "i32".to_string(),
+            "long" => "i64".to_string(),
+            "double" => "f64".to_string(),
+            "bool" => "bool".to_string(),
+            "objectId" => "ObjectId".to_string(),
+            "date" => "bson::DateTime".to_string(),
+            "binData" => "bson::Binary".to_string(),
+            "decimal" => "bson::Decimal128".to_string(),
+            "timestamp" => "bson::Timestamp".to_string(),
+            "regex" => "bson::Regex".to_string(),
+            "object" => match self.document.as_deref() {
+                Some(shape) if !shape.fields.is_empty() => {
+                    structs.push((path.to_string(), shape));
+                    path.to_string()
+                }
+                _ => "bson::Document".to_string(),
+            },
+            "array" => match self.elements.as_deref().map(|x| (x, x.rust_type(path, structs))) {
+                Some((elements, Ok(x))) if elements.nullable() => format!("Vec<Option<{}>>", x),
+                Some((_, Ok(x))) => format!("Vec<{}>", x),
+                _ => "Vec<Bson>".to_string(),
+            },
+            x => return Err(x.to_string()),
+        })
+    }
+}
+
+// Source of a model for the documents of `collection`, inferred from `sample` random ones
+pub async fn generate_model(
+    collection: &mongodb::Collection<bson::Document>,
+    name: &str,
+    sample: u32,
+) -> Result<String, Error> {
+    let context = ErrorContext::new(collection.name(), "generate_model");
+    let pipeline = vec![bson::doc! { "$sample": { "size": i64::from(sample.max(1)) } }];
+    let mut cursor = collection.aggregate(pipeline, None).await.map_err(|x| context.wrap(x))?;
+    let mut shape = Shape::default();
+    while let Some(document) = cursor.try_next().await.map_err(|x| context.wrap(x))? {
+        shape.add(&document);
+    }
+    if shape.count == 0 {
+        let message = format!("`{}` has no documents to infer a model from", collection.name());
+        return Err(context.wrap(Error::InvalidParams(message)));
+    }
+    Ok(render(name, collection.name(), shape))
+}
+
+fn render(name: &str, collection: &str, shape: Shape) -> String {
+    let mut source = String::new();
+    // Embedded documents are rendered after the struct holding them
+    let mut structs = vec![(name.to_string(), &shape)];
+    let mut next = 0;
+    while let Some((struct_name, struct_shape)) = structs.get(next).cloned() {
+        source.push_str(&render_struct(&struct_name, struct_shape, next == 0, &mut structs));
+        next += 1;
+    }
+
+    source.push_str(&format!(
+        "\n#[async_trait::async_trait]\nimpl RustMongoDBModelMethods for {name} {{\n    \
+         fn collection() -> mongodb::Collection<Self> {{\n        \
+         rms::database().expect(\"rms::init\").collection({collection:?})\n    }}\n\n    \
+         fn id_value(&self) -> &IdType {{\n        &self.id\n    }}\n}}\n",
+    ));
+
+    // Only what the structs use, unused imports would warn
+    let mut bson = Vec::new();
+    for (used, import) in [("bson::", "self"), ("ObjectId", "oid::ObjectId"), (": Bson", "Bson"), ("<Bson>", "Bson")] {
+        if source.contains(used) && !bson.contains(&import) {
+            bson.push(import);
+        }
+    }
+    let mut imports = String::new();
+    if !bson.is_empty() {
+        imports.push_str(&format!("use rms::bson::{{{}}};\n", bson.join(", ")));
+    }
+    imports.push_str("use rms::{IdType, RustMongoDBModelMethods};\nuse serde::{Deserialize, Serialize};\n");
+    imports + &source
+}
+
+fn render_struct<'a>(name: &str, shape: &'a Shape, root: bool, structs: &mut Vec<(String, &'a Shape)>) -> String {
+    let mut source = format!("\n#[derive(Debug, Clone, Serialize, Deserialize)]\npub struct {} {{\n", name);
+    let mut used: Vec<String> = Vec::new();
+    for (stored, field) in &shape.fields {
+        let mut attributes = Vec::new();
+        let (ident, rust_type, optional) = if root && stored == "_id" {
+            ("id".to_string(), Ok("IdType".to_string()), false)
+        } else {
+            let mut ident = identifier(stored);
+            while used.contains(&ident) {
+                ident.push('_');
+            }
+            let rust_type = field.rust_type(&format!("{}{}", name, pascal_case(&ident)), structs);
+            (ident, rust_type, field.present < shape.count || field.nullable())
+        };
+        used.push(ident.clone());
+
+        if ident.trim_start_matches("r#") != stored {
+            attributes.push(format!("rename = {:?}", stored));
+        }
+        if optional {
+            attributes.push("default, skip_serializing_if = \"Option::is_none\"".to_string());
+        }
+        if let Err(types) = &rust_type {
+            source.push_str(&format!("    // Stored as {}\n", types));
+        }
+        if !attributes.is_empty() {
+            source.push_str(&format!("    #[serde({})]\n", attributes.join(", ")));
+        }
+        let rust_type = rust_type.unwrap_or_else(|_| "Bson".to_string());
+        let rust_type = if optional { format!("Option<{}>", rust_type) } else { rust_type };
+        source.push_str(&format!("    pub {}: {},\n", ident, rust_type));
+    }
+    source.push_str("}\n");
+    source
+}
+
+// Strict and reserved keywords of every edition, `gen` included
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+    "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+    "mut", "override", "priv", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+// `createdAt` -> `created_at`, keywords raw, anything else not allowed in an identifier replaced by `_`
+fn identifier(stored: &str) -> String {
+    let mut ident = String::new();
+    for (i, x) in stored.chars().enumerate() {
+        match x {
+            x if x.is_ascii_uppercase() => {
+                if i > 0 && !ident.ends_with('_') {
+                    ident.push('_');
+                }
+                ident.push(x.to_ascii_lowercase());
+            }
+            x if x.is_ascii_alphanumeric() || x == '_' => ident.push(x),
+            _ => ident.push('_'),
+        }
+    }
+    if ident.is_empty() || ident.starts_with(|x: char| x.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    match ident.as_str() {
+        // Can't be raw identifiers
+        "self" | "Self" | "super" | "crate" | "_" => format!("{}_", ident),
+        x if KEYWORDS.contains(&x) => format!("r#{}", ident),
+        _ => ident,
+    }
+}
+
+fn pascal_case(ident: &str) -> String {
+    ident
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let mut chars = x.chars();
+            chars.next().map(|x| x.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_and_odd_names_compile() {
+        let mut document = bson::doc! { "_id": 1, "self": 1, "2fa": true, "first name": "a", "firstName": "b" };
+        for keyword in KEYWORDS {
+            document.insert(*keyword, "value");
+        }
+        let mut shape = Shape::default();
+        shape.add(&document);
+        let source = render("Legacy", "legacy", shape);
+
+        syn::parse_file(&source).unwrap_or_else(|x| panic!("{}\n{}", x, source));
+        assert!(source.contains("pub r#try: String,"));
+        assert!(source.contains("#[serde(rename = \"self\")]\n    pub self_: i32,"));
+    }
+}
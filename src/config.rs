@@ -0,0 +1,203 @@
+// CONFIG ==========================================================================================================
+// `Config::from_env()` reads the connection settings every service needs from the environment and reports every
+// missing or malformed variable at once, not just the first:
+//
+//     let config = Config::from_env()?;
+//     config.connect().await?;
+//
+//     MONGODB_URI                              required
+//     MONGODB_DATABASE                         required
+//     MONGODB_TLS                              `true`/`false`, defaults to what the URI says
+//     MONGODB_TLS_CA_FILE                      path, turns TLS on
+//     MONGODB_TLS_CERT_KEY_FILE                path, turns TLS on
+//     MONGODB_TLS_ALLOW_INVALID_CERTIFICATES   `true`/`false`
+//     MONGODB_WRITE_CONCERN                    `majority`, a number of nodes or a custom tag
+//     MONGODB_WRITE_CONCERN_JOURNAL            `true`/`false`
+//     MONGODB_WRITE_CONCERN_TIMEOUT_MS         milliseconds
+//     MONGODB_RETRY_WRITES                     `true`/`false`
+//     MONGODB_READ_ONLY                        `true`/`false`, see `read_only`
+//
+// Settings left out keep the URI's value. `pool` isn't read from the environment, set it before `connect`.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mongodb::options::{Acknowledgment, WriteConcern};
+
+use crate::connection::{Connection, PoolConfig};
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub uri: String,
+    pub database: String,
+    pub tls: Option<TlsConfig>,
+    pub write_concern: Option<WriteConcern>,
+    pub retry_writes: Option<bool>,
+    pub pool: PoolConfig,
+    // Turns on `read_only` for the process on `connect`
+    pub read_only: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    pub ca_file: Option<PathBuf>,
+    pub cert_key_file: Option<PathBuf>,
+    pub allow_invalid_certificates: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigProblem {
+    Missing(&'static str),
+    Invalid { name: &'static str, reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    // `from_env` with another source of variables, e.g. a map in tests
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut vars = Vars { var: &var, problems: Vec::new() };
+
+        let uri = vars.required("MONGODB_URI");
+        let database = vars.required("MONGODB_DATABASE");
+
+        let tls_enabled = vars.parsed::<bool>("MONGODB_TLS");
+        let ca_file = vars.optional("MONGODB_TLS_CA_FILE").map(PathBuf::from);
+        let cert_key_file = vars.optional("MONGODB_TLS_CERT_KEY_FILE").map(PathBuf::from);
+        let allow_invalid_certificates = vars.parsed::<bool>("MONGODB_TLS_ALLOW_INVALID_CERTIFICATES");
+        let tls_configured = ca_file.is_some() || cert_key_file.is_some() || allow_invalid_certificates.is_some();
+        let tls = match tls_enabled {
+            Some(false) if tls_configured => {
+                vars.invalid("MONGODB_TLS", "TLS files or options are set but TLS is turned off");
+                None
+            }
+            None if !tls_configured => None,
+            enabled => Some(TlsConfig {
+                enabled: enabled.unwrap_or(true),
+                ca_file,
+                cert_key_file,
+                allow_invalid_certificates: allow_invalid_certificates.unwrap_or(false),
+            }),
+        };
+
+        let w = vars.optional("MONGODB_WRITE_CONCERN").map(|x| match x.parse::<u32>() {
+            Ok(nodes) => Acknowledgment::Nodes(nodes),
+            Err(_) if x == "majority" => Acknowledgment::Majority,
+            Err(_) => Acknowledgment::Custom(x),
+        });
+        let journal = vars.parsed::<bool>("MONGODB_WRITE_CONCERN_JOURNAL");
+        let w_timeout = vars.parsed::<u64>("MONGODB_WRITE_CONCERN_TIMEOUT_MS").map(Duration::from_millis);
+        let write_concern = if w.is_some() || journal.is_some() || w_timeout.is_some() {
+            Some(WriteConcern::builder().w(w).journal(journal).w_timeout(w_timeout).build())
+        } else {
+            None
+        };
+
+        let retry_writes = vars.parsed::<bool>("MONGODB_RETRY_WRITES");
+        let read_only = vars.parsed::<bool>("MONGODB_READ_ONLY").unwrap_or(false);
+
+        match (uri, database) {
+            (Some(uri), Some(database)) if vars.problems.is_empty() => {
+                let pool = PoolConfig::default();
+                Ok(Self { uri, database, tls, write_concern, retry_writes, pool, read_only })
+            }
+            _ => Err(ConfigError { problems: vars.problems }),
+        }
+    }
+
+    pub async fn options(&self) -> Result<mongodb::options::ClientOptions, Error> {
+        let mut options = mongodb::options::ClientOptions::parse(&self.uri).await?;
+        if let Some(tls) = &self.tls {
+            options.tls = Some(match tls.enabled {
+                true => mongodb::options::Tls::Enabled(
+                    mongodb::options::TlsOptions::builder()
+                        .ca_file_path(tls.ca_file.clone())
+                        .cert_key_file_path(tls.cert_key_file.clone())
+                        .allow_invalid_certificates(tls.allow_invalid_certificates)
+                        .build(),
+                ),
+                false => mongodb::options::Tls::Disabled,
+            });
+        }
+        if let Some(write_concern) = &self.write_concern {
+            options.write_concern = Some(write_concern.clone());
+        }
+        options.retry_writes = self.retry_writes.or(options.retry_writes);
+        self.pool.apply(&mut options);
+        Ok(options)
+    }
+
+    // `init` with these settings
+    pub async fn connect(&self) -> Result<&'static Connection, Error> {
+        let connection = crate::connection::init(self.options().await?, &self.database)?;
+        if self.read_only {
+            crate::read_only::set(true);
+        }
+        Ok(connection)
+    }
+}
+
+struct Vars<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Vars<'_> {
+    // Empty values count as unset
+    fn optional(&self, name: &'static str) -> Option<String> {
+        (self.var)(name).map(|x| x.trim().to_string()).filter(|x| !x.is_empty())
+    }
+
+    fn required(&mut self, name: &'static str) -> Option<String> {
+        let value = self.optional(name);
+        if value.is_none() {
+            self.problems.push(ConfigProblem::Missing(name));
+        }
+        value
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, name: &'static str) -> Option<T>
+    where
+        T::Err: std::fmt::Display,
+    {
+        let value = self.optional(name)?;
+        match value.parse() {
+            Ok(x) => Some(x),
+            Err(x) => {
+                self.invalid(name, &format!("`{}`: {}", value, x));
+                None
+            }
+        }
+    }
+
+    fn invalid(&mut self, name: &'static str, reason: &str) {
+        self.problems.push(ConfigProblem::Invalid { name, reason: reason.to_string() });
+    }
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigProblem::Missing(name) => write!(f, "{} is not set", name),
+            ConfigProblem::Invalid { name, reason } => write!(f, "{} is invalid: {}", name, reason),
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let problems: Vec<String> = self.problems.iter().map(ToString::to_string).collect();
+        write!(f, "{}", problems.join(", "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,345 @@
+// CONNECTION ======================================================================================================
+// `init` connects once at startup and keeps the client around for the crate-level helpers like `health()`:
+//
+//     let options = ClientOptions::parse(&uri).await?;
+//     rms::init(options, "shop")?;
+//
+//     fn collection() -> Collection<User> {
+//         rms::database().expect("rms::init").collection("users")
+//     }
+//
+// `connect` parses the URI and applies a `PoolConfig` on top of it, for services that don't need anything else
+// from `ClientOptions`:
+//
+//     let pool = PoolConfig::new().app_name("billing").max_pool_size(50).connect_timeout(Duration::from_secs(5));
+//     rms::connect(&uri, "shop", pool).await?;
+//
+// The client counts its pool's connections as they're opened, checked out and closed, the counters are read
+// through `pool_stats()`. A `cmap_event_handler` already set on the options still gets every event.
+//
+// Binaries spanning clusters name their databases instead, once at startup, and models say which one they live
+// in. `init` stays the `"default"` one, for the models that don't:
+//
+//     rms::init_router(Router::new()
+//         .route("transactional", ClientOptions::parse(&primary_uri).await?, "shop")
+//         .route("reporting", ClientOptions::parse(&warehouse_uri).await?, "analytics"))?;
+//
+//     fn logical_database() -> &'static str {
+//         "reporting"
+//     }
+//     fn collection() -> Collection<DailySales> {
+//         rms::database_of(Self::logical_database()).expect("rms::init_router").collection("daily_sales")
+//     }
+//
+// Each route gets its own client and pool, two routes to one cluster too. A `Session` only takes models of the
+// logical database it was first used with, `Error::InvalidParams` otherwise: its client can't reach the others.
+// `check_database()` tells whether a model's `collection()` really is in its logical database.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use mongodb::event::cmap::{self, CmapEventHandler};
+
+use crate::Error;
+
+// The logical database of `init`, and of models not naming one
+pub const DEFAULT_DATABASE: &str = "default";
+
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+static ROUTES: OnceLock<HashMap<String, Connection>> = OnceLock::new();
+
+pub struct Connection {
+    pub client: mongodb::Client,
+    pub database: mongodb::Database,
+    pool: Arc<PoolMonitor>,
+}
+
+// Unset fields keep the value from the URI, or the driver's default. Compressors need the matching driver
+// feature (`zstd-compression`, `zlib-compression` or `snappy-compression`) enabled on `mongodb`.
+#[derive(Debug, Clone, Default)]
+pub struct PoolConfig {
+    pub app_name: Option<String>,
+    pub max_pool_size: Option<u32>,
+    pub min_pool_size: Option<u32>,
+    // Connections a pool may be establishing at the same time
+    pub max_connecting: Option<u32>,
+    pub max_idle_time: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub server_selection_timeout: Option<Duration>,
+    pub compressors: Option<Vec<mongodb::options::Compressor>>,
+}
+
+// Logical database names to the client options and database they're reached with
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+    routes: HashMap<String, (mongodb::options::ClientOptions, String)>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Routing `logical` a second time replaces the first route
+    pub fn route(mut self, logical: &str, options: mongodb::options::ClientOptions, database: &str) -> Self {
+        self.routes.insert(logical.to_string(), (options, database.to_string()));
+        self
+    }
+}
+
+// Totals across the pools of every server the client talks to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct PoolStats {
+    pub open: u64,
+    pub in_use: u64,
+    pub created: u64,
+    pub closed: u64,
+    // Operations waiting for a connection
+    pub waiting: u64,
+    pub checkout_failures: u64,
+    pub cleared: u64,
+}
+
+impl PoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn app_name(mut self, app_name: &str) -> Self {
+        self.app_name = Some(app_name.to_string());
+        self
+    }
+
+    pub fn max_pool_size(mut self, size: u32) -> Self {
+        self.max_pool_size = Some(size);
+        self
+    }
+
+    pub fn min_pool_size(mut self, size: u32) -> Self {
+        self.min_pool_size = Some(size);
+        self
+    }
+
+    pub fn max_connecting(mut self, connecting: u32) -> Self {
+        self.max_connecting = Some(connecting);
+        self
+    }
+
+    pub fn max_idle_time(mut self, idle: Duration) -> Self {
+        self.max_idle_time = Some(idle);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn server_selection_timeout(mut self, timeout: Duration) -> Self {
+        self.server_selection_timeout = Some(timeout);
+        self
+    }
+
+    pub fn compressors(mut self, compressors: Vec<mongodb::options::Compressor>) -> Self {
+        self.compressors = Some(compressors);
+        self
+    }
+
+    pub fn apply(&self, options: &mut mongodb::options::ClientOptions) {
+        if let Some(x) = &self.app_name {
+            options.app_name = Some(x.clone());
+        }
+        options.max_pool_size = self.max_pool_size.or(options.max_pool_size);
+        options.min_pool_size = self.min_pool_size.or(options.min_pool_size);
+        options.max_connecting = self.max_connecting.or(options.max_connecting);
+        options.max_idle_time = self.max_idle_time.or(options.max_idle_time);
+        options.connect_timeout = self.connect_timeout.or(options.connect_timeout);
+        options.server_selection_timeout = self.server_selection_timeout.or(options.server_selection_timeout);
+        if let Some(x) = &self.compressors {
+            options.compressors = Some(x.clone());
+        }
+    }
+}
+
+pub async fn connect(uri: &str, database: &str, pool: PoolConfig) -> Result<&'static Connection, Error> {
+    let mut options = mongodb::options::ClientOptions::parse(uri).await?;
+    pool.apply(&mut options);
+    init(options, database)
+}
+
+// Fails with `Error::InvalidParams` when called a second time
+pub fn init(options: mongodb::options::ClientOptions, database: &str) -> Result<&'static Connection, Error> {
+    let connection = Connection::open(options, database)?;
+    CONNECTION.set(connection).map_err(|_| Error::InvalidParams("`init` was already called".to_string()))?;
+    self::connection()
+}
+
+// Fails with `Error::InvalidParams` when called a second time, or for a `"default"` route, that one is `init`'s
+pub fn init_router(router: Router) -> Result<(), Error> {
+    if router.routes.contains_key(DEFAULT_DATABASE) {
+        let message = format!("`{}` is the database of `init`, not a route", DEFAULT_DATABASE);
+        return Err(Error::InvalidParams(message));
+    }
+    let mut routes = HashMap::with_capacity(router.routes.len());
+    for (logical, (options, database)) in router.routes {
+        routes.insert(logical, Connection::open(options, &database)?);
+    }
+    ROUTES.set(routes).map_err(|_| Error::InvalidParams("`init_router` was already called".to_string()))
+}
+
+// The connection of a logical database, `init`'s for `"default"`
+pub fn connection_of(logical: &str) -> Result<&'static Connection, Error> {
+    if logical == DEFAULT_DATABASE {
+        return connection();
+    }
+    let routes = ROUTES.get().ok_or(Error::NotInitialized)?;
+    let unknown = || Error::InvalidParams(format!("no route to the `{}` database", logical));
+    routes.get(logical).ok_or_else(unknown)
+}
+
+pub fn client_of(logical: &str) -> Result<mongodb::Client, Error> {
+    Ok(connection_of(logical)?.client.clone())
+}
+
+pub fn database_of(logical: &str) -> Result<mongodb::Database, Error> {
+    Ok(connection_of(logical)?.database.clone())
+}
+
+pub fn connection() -> Result<&'static Connection, Error> {
+    CONNECTION.get().ok_or(Error::NotInitialized)
+}
+
+pub fn client() -> Result<mongodb::Client, Error> {
+    Ok(connection()?.client.clone())
+}
+
+pub fn database() -> Result<mongodb::Database, Error> {
+    Ok(connection()?.database.clone())
+}
+
+pub fn pool_stats() -> Result<PoolStats, Error> {
+    Ok(connection()?.pool_stats())
+}
+
+impl Connection {
+    fn open(mut options: mongodb::options::ClientOptions, database: &str) -> Result<Self, Error> {
+        let pool = Arc::new(PoolMonitor { next: options.cmap_event_handler.take(), ..Default::default() });
+        options.cmap_event_handler = Some(pool.clone());
+
+        let client = mongodb::Client::with_options(options)?;
+        Ok(Connection { database: client.database(database), client, pool })
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+}
+
+#[derive(Default)]
+struct PoolMonitor {
+    next: Option<Arc<dyn CmapEventHandler>>,
+    created: AtomicU64,
+    closed: AtomicU64,
+    checkouts_started: AtomicU64,
+    checked_out: AtomicU64,
+    checked_in: AtomicU64,
+    checkout_failures: AtomicU64,
+    cleared: AtomicU64,
+}
+
+impl PoolMonitor {
+    fn stats(&self) -> PoolStats {
+        let (created, closed) = (self.created.load(Ordering::Relaxed), self.closed.load(Ordering::Relaxed));
+        let checked_out = self.checked_out.load(Ordering::Relaxed);
+        let checked_in = self.checked_in.load(Ordering::Relaxed);
+        let started = self.checkouts_started.load(Ordering::Relaxed);
+        let checkout_failures = self.checkout_failures.load(Ordering::Relaxed);
+        PoolStats {
+            open: created.saturating_sub(closed),
+            in_use: checked_out.saturating_sub(checked_in),
+            created,
+            closed,
+            waiting: started.saturating_sub(checked_out + checkout_failures),
+            checkout_failures,
+            cleared: self.cleared.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl CmapEventHandler for PoolMonitor {
+    fn handle_pool_created_event(&self, event: cmap::PoolCreatedEvent) {
+        if let Some(next) = &self.next {
+            next.handle_pool_created_event(event);
+        }
+    }
+
+    fn handle_pool_ready_event(&self, event: cmap::PoolReadyEvent) {
+        if let Some(next) = &self.next {
+            next.handle_pool_ready_event(event);
+        }
+    }
+
+    fn handle_pool_cleared_event(&self, event: cmap::PoolClearedEvent) {
+        self.cleared.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_pool_cleared_event(event);
+        }
+    }
+
+    fn handle_pool_closed_event(&self, event: cmap::PoolClosedEvent) {
+        if let Some(next) = &self.next {
+            next.handle_pool_closed_event(event);
+        }
+    }
+
+    fn handle_connection_created_event(&self, event: cmap::ConnectionCreatedEvent) {
+        self.created.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_created_event(event);
+        }
+    }
+
+    fn handle_connection_ready_event(&self, event: cmap::ConnectionReadyEvent) {
+        if let Some(next) = &self.next {
+            next.handle_connection_ready_event(event);
+        }
+    }
+
+    fn handle_connection_closed_event(&self, event: cmap::ConnectionClosedEvent) {
+        self.closed.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_closed_event(event);
+        }
+    }
+
+    fn handle_connection_checkout_started_event(&self, event: cmap::ConnectionCheckoutStartedEvent) {
+        self.checkouts_started.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_checkout_started_event(event);
+        }
+    }
+
+    fn handle_connection_checkout_failed_event(&self, event: cmap::ConnectionCheckoutFailedEvent) {
+        self.checkout_failures.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_checkout_failed_event(event);
+        }
+    }
+
+    fn handle_connection_checked_out_event(&self, event: cmap::ConnectionCheckedOutEvent) {
+        self.checked_out.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_checked_out_event(event);
+        }
+    }
+
+    fn handle_connection_checked_in_event(&self, event: cmap::ConnectionCheckedInEvent) {
+        self.checked_in.fetch_add(1, Ordering::Relaxed);
+        if let Some(next) = &self.next {
+            next.handle_connection_checked_in_event(event);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+// CURSORS =========================================================================================================
+// `prefetch` reads up to `ahead` items of a stream on a separate task while the caller is still busy with the
+// previous ones, so the next batch is already on its way when it's needed:
+//
+//     let rows = User::find_raw(doc! {}).await?;
+//     let mut rows = cursor::prefetch(rows, 1000);
+//     while let Some(row) = rows.next().await {
+//         process(row?);
+//     }
+//
+// Pair it with a `batch_size()` close to `ahead`. Must run inside a Tokio runtime. Dropping the returned stream
+// stops the task and drops the cursor with it.
+
+use futures::{SinkExt, StreamExt};
+
+pub fn prefetch<T: Send + 'static>(
+    stream: futures::stream::BoxStream<'static, T>,
+    ahead: usize,
+) -> futures::stream::BoxStream<'static, T> {
+    // The channel holds `ahead` items plus one per sender
+    let (mut sender, receiver) = futures::channel::mpsc::channel(ahead.saturating_sub(1));
+    tokio::spawn(async move {
+        let mut stream = stream.map(Ok);
+        // Fails once the receiver is gone
+        let _ = sender.send_all(&mut stream).await;
+    });
+    receiver.boxed()
+}
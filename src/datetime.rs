@@ -0,0 +1,64 @@
+// DATETIME INTEROP ================================================================================================
+// `chrono::DateTime<Utc>` (feature "chrono") and `time::OffsetDateTime` (feature "time") fields are stored as
+// BSON dates instead of strings when annotated with the serde helpers below:
+//
+//     #[serde(with = "rust_mongodb_model_methods::datetime::chrono_as_bson")]
+//     created_at: chrono::DateTime<chrono::Utc>,
+//     #[serde(with = "rust_mongodb_model_methods::datetime::chrono_option_as_bson", default)]
+//     deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+//
+// The filter builders accept anything convertible into `bson::DateTime`, so ranges match what was stored.
+
+#[cfg(feature = "chrono")]
+pub use bson::serde_helpers::chrono_datetime_as_bson_datetime as chrono_as_bson;
+#[cfg(feature = "time")]
+pub use bson::serde_helpers::time_0_3_offsetdatetime_as_bson_datetime as time_as_bson;
+
+#[cfg(feature = "chrono")]
+pub mod chrono_option_as_bson {
+    pub fn serialize<S: serde::Serializer>(
+        value: &Option<chrono::DateTime<chrono::Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let value = value.map(bson::DateTime::from_chrono);
+        serde::Serialize::serialize(&value, serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, D::Error> {
+        let value: Option<bson::DateTime> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(value.map(bson::DateTime::to_chrono))
+    }
+}
+
+#[cfg(feature = "time")]
+pub mod time_option_as_bson {
+    pub fn serialize<S: serde::Serializer>(value: &Option<time::OffsetDateTime>, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = value.map(bson::DateTime::from_time_0_3);
+        serde::Serialize::serialize(&value, serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<time::OffsetDateTime>, D::Error> {
+        let value: Option<bson::DateTime> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(value.map(bson::DateTime::to_time_0_3))
+    }
+}
+
+// FILTER BUILDERS =================================================================================================
+// `between` is half-open: `from` is included, `to` is not, so consecutive ranges never overlap.
+pub fn between(field: &str, from: impl Into<bson::DateTime>, to: impl Into<bson::DateTime>) -> bson::Document {
+    bson::doc! { field: { "$gte": from.into(), "$lt": to.into() } }
+}
+
+pub fn before(field: &str, to: impl Into<bson::DateTime>) -> bson::Document {
+    bson::doc! { field: { "$lt": to.into() } }
+}
+
+pub fn after(field: &str, from: impl Into<bson::DateTime>) -> bson::Document {
+    bson::doc! { field: { "$gt": from.into() } }
+}
+
+pub fn since(field: &str, from: impl Into<bson::DateTime>) -> bson::Document {
+    bson::doc! { field: { "$gte": from.into() } }
+}
@@ -0,0 +1,83 @@
+// DECIMAL128 ======================================================================================================
+// `rust_decimal::Decimal` fields are stored as BSON Decimal128 so money never goes through a float:
+//
+//     #[serde(with = "rust_mongodb_model_methods::decimal::as_decimal128")]
+//     balance: rust_decimal::Decimal,
+//
+// Filters and updates must use the same encoding, hence the helpers below.
+
+use rust_decimal::Decimal;
+
+pub fn to_decimal128(value: &Decimal) -> bson::Decimal128 {
+    // Decimal's string form is always a plain, finite number that Decimal128 can represent exactly
+    value.to_string().parse().expect("Decimal always fits into Decimal128")
+}
+
+pub fn from_decimal128(value: &bson::Decimal128) -> Result<Decimal, String> {
+    let repr = value.to_string();
+    repr.parse::<Decimal>()
+        .or_else(|_| Decimal::from_scientific(&repr))
+        .map_err(|x| format!("Decimal128 {} can't be represented as Decimal: {}", repr, x))
+}
+
+pub fn to_bson(value: &Decimal) -> bson::Bson {
+    bson::Bson::Decimal128(to_decimal128(value))
+}
+
+// SERDE HELPERS ===================================================================================================
+pub mod as_decimal128 {
+    use rust_decimal::Decimal;
+
+    pub fn serialize<S: serde::Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&super::to_decimal128(value), serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+        let value: bson::Decimal128 = serde::Deserialize::deserialize(deserializer)?;
+        super::from_decimal128(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+pub mod option_as_decimal128 {
+    use rust_decimal::Decimal;
+
+    pub fn serialize<S: serde::Serializer>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = value.as_ref().map(super::to_decimal128);
+        serde::Serialize::serialize(&value, serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+        let value: Option<bson::Decimal128> = serde::Deserialize::deserialize(deserializer)?;
+        value.as_ref().map(super::from_decimal128).transpose().map_err(serde::de::Error::custom)
+    }
+}
+
+// FILTER / UPDATE HELPERS =========================================================================================
+pub fn eq(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { field: to_bson(value) }
+}
+
+pub fn gt(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { field: { "$gt": to_bson(value) } }
+}
+
+pub fn gte(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { field: { "$gte": to_bson(value) } }
+}
+
+pub fn lt(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { field: { "$lt": to_bson(value) } }
+}
+
+pub fn lte(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { field: { "$lte": to_bson(value) } }
+}
+
+pub fn set(field: &str, value: &Decimal) -> bson::Document {
+    bson::doc! { "$set": { field: to_bson(value) } }
+}
+
+// The server adds Decimal128 values exactly, so concurrent `$inc`s on a balance never lose cents
+pub fn inc(field: &str, amount: &Decimal) -> bson::Document {
+    bson::doc! { "$inc": { field: to_bson(amount) } }
+}
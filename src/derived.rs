@@ -0,0 +1,32 @@
+// DERIVED FIELDS ==================================================================================================
+// Fields computed from the rest of the document (totals, search keys) are recomputed in Rust and written back:
+//
+//     impl DerivedFields for Order {
+//         const FIELDS: &'static [&'static str] = &["total", "search_key"];
+//
+//         fn recompute(&mut self) {
+//             self.total = self.lines.iter().map(|x| x.price * x.quantity).sum();
+//             self.search_key = format!("{} {}", self.number, self.customer_name).to_lowercase();
+//         }
+//     }
+//
+//     let changed = Order::refresh_derived(doc! { "status": "open" }).await?;
+//
+// Only `FIELDS` are written, and only for documents where `recompute` changed them, so a concurrent update of
+// another field isn't overwritten. Run it after changing how a field is derived.
+
+pub trait DerivedFields {
+    // Stored names of the fields `recompute` sets
+    const FIELDS: &'static [&'static str];
+
+    fn recompute(&mut self);
+}
+
+// `$set` of the derived fields that differ between the stored `before` and the recomputed `after`
+pub(crate) fn changes(fields: &[&str], before: &bson::Document, after: &bson::Document) -> bson::Document {
+    fields
+        .iter()
+        .map(|x| (x.to_string(), after.get(*x).cloned().unwrap_or(bson::Bson::Null)))
+        .filter(|(field, value)| before.get(field).unwrap_or(&bson::Bson::Null) != value)
+        .collect()
+}
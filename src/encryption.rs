@@ -0,0 +1,294 @@
+// FIELD ENCRYPTION ================================================================================================
+// AES-256-GCM for single fields, done by the application instead of the driver (no libmongocrypt). Fields opt in
+// through serde, the database only ever sees a binary:
+//
+//     #[derive(Serialize, Deserialize)]
+//     struct Patient {
+//         #[serde(with = "rms::encryption::randomized")]
+//         diagnosis: String,
+//         #[serde(with = "rms::encryption::deterministic")]
+//         ssn: String,
+//     }
+//
+//     encryption::set_keyring(Keyring::new(2, &key_2).retired(1, &key_1));
+//
+// Randomized fields encrypt to a new ciphertext every time. Deterministic ones always encrypt a value to the same
+// ciphertext under a given key, so they can be looked up by equality, against every key of the keyring:
+//
+//     Patient::find_one(doc! { "ssn": encryption::deterministic::matching(&ssn)? }).await?;
+//
+// Each ciphertext names its key. Rotating means adding the new key as current and keeping the old ones as
+// `retired` until `reencrypt()` has rewritten the collection with the new one. Deterministic mode reveals which
+// documents hold equal values, use it only where lookups need it. Ciphertexts are binaries of subtype 0x80:
+// `[1][mode][key id, u32 BE][nonce, 12 bytes][AES-GCM ciphertext and tag]`, the first 6 bytes authenticated too.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+const VERSION: u8 = 1;
+const SUBTYPE: bson::spec::BinarySubtype = bson::spec::BinarySubtype::UserDefined(0x80);
+const HEADER_LEN: usize = 6;
+
+static KEYRING: RwLock<Option<Arc<Keyring>>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Randomized = 0,
+    Deterministic = 1,
+}
+
+pub struct Keyring {
+    current: u32,
+    keys: HashMap<u32, Key>,
+}
+
+struct Key {
+    cipher: LessSafeKey,
+    // Derives deterministic nonces from the plaintext
+    nonces: hmac::Key,
+}
+
+impl Key {
+    fn new(key: &[u8; 32]) -> Self {
+        let master = hmac::Key::new(hmac::HMAC_SHA256, key);
+        let cipher = hmac::sign(&master, b"rms field encryption key");
+        let cipher = UnboundKey::new(&aead::AES_256_GCM, cipher.as_ref()).expect("32-byte key");
+        let nonces = hmac::Key::new(hmac::HMAC_SHA256, hmac::sign(&master, b"rms deterministic nonce").as_ref());
+        Self { cipher: LessSafeKey::new(cipher), nonces }
+    }
+}
+
+impl Keyring {
+    // `key` encrypts from now on, `id` is stored with each ciphertext
+    pub fn new(id: u32, key: &[u8; 32]) -> Self {
+        Self { current: id, keys: HashMap::from([(id, Key::new(key))]) }
+    }
+
+    // A key of an earlier rotation, still decrypting what it encrypted
+    pub fn retired(mut self, id: u32, key: &[u8; 32]) -> Self {
+        self.keys.entry(id).or_insert_with(|| Key::new(key));
+        self
+    }
+}
+
+// Key material stays out of logs
+impl std::fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut ids: Vec<&u32> = self.keys.keys().collect();
+        ids.sort();
+        f.debug_struct("Keyring").field("current", &self.current).field("ids", &ids).finish()
+    }
+}
+
+// Replaces the keyring, e.g. on rotation. Encrypted fields fail to (de)serialize until one is set.
+pub fn set_keyring(keyring: Keyring) {
+    *KEYRING.write().unwrap_or_else(|x| x.into_inner()) = Some(Arc::new(keyring));
+}
+
+fn keyring() -> Result<Arc<Keyring>, String> {
+    let keyring = KEYRING.read().unwrap_or_else(|x| x.into_inner());
+    keyring.clone().ok_or_else(|| "no keyring, call `encryption::set_keyring` first".to_string())
+}
+
+fn encrypt<T>(value: &T, mode: Mode, keyring: &Keyring, id: u32) -> Result<bson::Binary, String>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let key = keyring.keys.get(&id).ok_or_else(|| format!("no key {} in the keyring", id))?;
+    let mut plaintext = Vec::new();
+    bson::doc! { "v": bson::to_bson(value).map_err(|x| x.to_string())? }
+        .to_writer(&mut plaintext)
+        .map_err(|x| x.to_string())?;
+
+    let mut header = vec![VERSION, mode as u8];
+    header.extend(id.to_be_bytes());
+    let mut nonce = [0; aead::NONCE_LEN];
+    match mode {
+        Mode::Randomized => SystemRandom::new().fill(&mut nonce).map_err(|_| "no randomness available")?,
+        Mode::Deterministic => {
+            let tag = hmac::sign(&key.nonces, &[header.as_slice(), &plaintext].concat());
+            nonce.copy_from_slice(&tag.as_ref()[..aead::NONCE_LEN]);
+        }
+    }
+
+    let aad = Aad::from(header.clone());
+    key.cipher
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), aad, &mut plaintext)
+        .map_err(|_| "encryption failed")?;
+    let mut bytes = header;
+    bytes.extend(nonce);
+    bytes.extend(plaintext);
+    Ok(bson::Binary { subtype: SUBTYPE, bytes })
+}
+
+fn decrypt<T: serde::de::DeserializeOwned>(binary: &bson::Binary, keyring: &Keyring) -> Result<T, String> {
+    let bytes = &binary.bytes;
+    if binary.subtype != SUBTYPE || bytes.len() < HEADER_LEN + aead::NONCE_LEN || bytes[0] != VERSION {
+        return Err("not an encrypted field".to_string());
+    }
+    let (header, rest) = bytes.split_at(HEADER_LEN);
+    let (nonce, ciphertext) = rest.split_at(aead::NONCE_LEN);
+    let id = u32::from_be_bytes([header[2], header[3], header[4], header[5]]);
+
+    let key = keyring.keys.get(&id).ok_or_else(|| format!("no key {} in the keyring", id))?;
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "invalid nonce")?;
+    let mut ciphertext = ciphertext.to_vec();
+    let plaintext = key
+        .cipher
+        .open_in_place(nonce, Aad::from(header), &mut ciphertext)
+        .map_err(|_| format!("decryption with key {} failed", id))?;
+
+    let mut document = bson::Document::from_reader(&plaintext[..]).map_err(|x| x.to_string())?;
+    bson::from_bson(document.remove("v").unwrap_or_default()).map_err(|x| x.to_string())
+}
+
+fn serialize<T, S>(value: &T, mode: Mode, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: serde::Serialize + ?Sized,
+    S: serde::Serializer,
+{
+    use serde::ser::Error;
+    let keyring = keyring().map_err(S::Error::custom)?;
+    let binary = encrypt(value, mode, &keyring, keyring.current).map_err(S::Error::custom)?;
+    serde::Serialize::serialize(&binary, serializer)
+}
+
+fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: serde::de::DeserializeOwned,
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    let binary: bson::Binary = serde::Deserialize::deserialize(deserializer)?;
+    let keyring = keyring().map_err(D::Error::custom)?;
+    decrypt(&binary, &keyring).map_err(D::Error::custom)
+}
+
+// `#[serde(with = "rms::encryption::randomized")]`
+pub mod randomized {
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: serde::Serialize + ?Sized,
+        S: serde::Serializer,
+    {
+        super::serialize(value, super::Mode::Randomized, serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: serde::de::DeserializeOwned,
+        D: serde::Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+}
+
+// `#[serde(with = "rms::encryption::deterministic")]`
+pub mod deterministic {
+    use crate::Error;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: serde::Serialize + ?Sized,
+        S: serde::Serializer,
+    {
+        super::serialize(value, super::Mode::Deterministic, serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: serde::de::DeserializeOwned,
+        D: serde::Deserializer<'de>,
+    {
+        super::deserialize(deserializer)
+    }
+
+    // `{ $in: [...] }` of `value` encrypted with each key of the keyring, to filter a deterministic field on
+    pub fn matching<T: serde::Serialize + ?Sized>(value: &T) -> Result<bson::Bson, Error> {
+        let keyring = super::keyring().map_err(Error::Encryption)?;
+        let mut ids: Vec<u32> = keyring.keys.keys().copied().collect();
+        ids.sort();
+        let encrypted = ids
+            .into_iter()
+            .map(|id| super::encrypt(value, super::Mode::Deterministic, &keyring, id).map(bson::Bson::Binary))
+            .collect::<Result<Vec<bson::Bson>, String>>()
+            .map_err(Error::Encryption)?;
+        Ok(bson::Bson::Document(bson::doc! { "$in": encrypted }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: [u8; 32] = [1; 32];
+    const NEW: [u8; 32] = [2; 32];
+
+    #[test]
+    fn round_trips_in_both_modes() {
+        let keyring = Keyring::new(1, &OLD);
+        for mode in [Mode::Randomized, Mode::Deterministic] {
+            let binary = encrypt("123-45-6789", mode, &keyring, 1).unwrap();
+            assert_eq!(binary.subtype, SUBTYPE);
+            assert_eq!(&binary.bytes[..HEADER_LEN], &[VERSION, mode as u8, 0, 0, 0, 1]);
+            assert_eq!(decrypt::<String>(&binary, &keyring).unwrap(), "123-45-6789");
+        }
+        let first = encrypt("123-45-6789", Mode::Randomized, &keyring, 1).unwrap();
+        assert_ne!(first, encrypt("123-45-6789", Mode::Randomized, &keyring, 1).unwrap());
+    }
+
+    #[test]
+    fn deterministic_is_equal_under_one_key_only() {
+        let keyring = Keyring::new(2, &NEW).retired(1, &OLD);
+        let encrypted = |value: &str, id| encrypt(value, Mode::Deterministic, &keyring, id).unwrap();
+        assert_eq!(encrypted("alice", 2), encrypted("alice", 2));
+        assert_ne!(encrypted("alice", 2), encrypted("bob", 2));
+        assert_ne!(encrypted("alice", 2).bytes[HEADER_LEN..], encrypted("alice", 1).bytes[HEADER_LEN..]);
+    }
+
+    #[test]
+    fn matching_covers_every_key_of_the_keyring() {
+        let before_rotation = encrypt("alice", Mode::Deterministic, &Keyring::new(1, &OLD), 1).unwrap();
+        set_keyring(Keyring::new(2, &NEW).retired(1, &OLD));
+        let after_rotation = encrypt("alice", Mode::Deterministic, &keyring().unwrap(), 2).unwrap();
+
+        let filter = deterministic::matching("alice").unwrap();
+        let values = filter.as_document().unwrap().get_array("$in").unwrap();
+        assert_eq!(values, &vec![bson::Bson::Binary(before_rotation), bson::Bson::Binary(after_rotation)]);
+    }
+
+    #[test]
+    fn rejects_tampered_header_or_ciphertext() {
+        let keyring = Keyring::new(1, &OLD);
+        let binary = encrypt("alice", Mode::Randomized, &keyring, 1).unwrap();
+        let tampered = |at: usize| {
+            let mut bytes = binary.bytes.clone();
+            bytes[at] ^= 1;
+            decrypt::<String>(&bson::Binary { subtype: SUBTYPE, bytes }, &keyring).unwrap_err()
+        };
+
+        assert_eq!(tampered(1), "decryption with key 1 failed");
+        assert_eq!(tampered(HEADER_LEN), "decryption with key 1 failed");
+        assert_eq!(tampered(binary.bytes.len() - 1), "decryption with key 1 failed");
+        assert_eq!(tampered(0), "not an encrypted field");
+
+        let generic = bson::Binary { subtype: bson::spec::BinarySubtype::Generic, bytes: binary.bytes.clone() };
+        assert_eq!(decrypt::<String>(&generic, &keyring).unwrap_err(), "not an encrypted field");
+        let truncated = bson::Binary { subtype: SUBTYPE, bytes: binary.bytes[..HEADER_LEN + 4].to_vec() };
+        assert_eq!(decrypt::<String>(&truncated, &keyring).unwrap_err(), "not an encrypted field");
+    }
+
+    #[test]
+    fn rejects_an_unknown_key_id() {
+        let binary = encrypt("alice", Mode::Deterministic, &Keyring::new(1, &OLD), 1).unwrap();
+        assert_eq!(decrypt::<String>(&binary, &Keyring::new(2, &NEW)).unwrap_err(), "no key 1 in the keyring");
+        assert_eq!(
+            encrypt("alice", Mode::Randomized, &Keyring::new(2, &NEW), 1).unwrap_err(),
+            "no key 1 in the keyring"
+        );
+    }
+}
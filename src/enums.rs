@@ -0,0 +1,137 @@
+// ENUM STORAGE ====================================================================================================
+// Enums are stored in one of three shapes: a string, an integer or a tagged document.
+// Declare the shape once with `stored_enum!`, mark the model field with
+// `#[serde(with = "rust_mongodb_model_methods::enums::stored")]` and build filters with the helpers
+// below, so the value written to the database and the value used in queries never drift apart.
+//
+//     stored_enum!(Status as int { Active = 1, Disabled = 2 });
+//     stored_enum!(Role as string { Admin = "admin", Member = "member" });
+//     stored_enum!(Shape as tagged); // uses the enum's own serde impl, e.g. #[serde(tag = "type")]
+//
+//     User::find(enums::eq("status", &Status::Active)).await?;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumRepr {
+    String,
+    Int,
+    Tagged,
+}
+
+pub trait StoredEnum: Sized {
+    const REPR: EnumRepr;
+
+    fn to_stored(&self) -> bson::Bson;
+    fn from_stored(value: &bson::Bson) -> Option<Self>;
+}
+
+#[macro_export]
+macro_rules! stored_enum {
+    ($name:ty as string { $($variant:ident = $value:literal),+ $(,)? }) => {
+        impl $crate::enums::StoredEnum for $name {
+            const REPR: $crate::enums::EnumRepr = $crate::enums::EnumRepr::String;
+
+            fn to_stored(&self) -> $crate::bson::Bson {
+                match self {
+                    $(Self::$variant => $crate::bson::Bson::String($value.to_string()),)+
+                }
+            }
+
+            fn from_stored(value: &$crate::bson::Bson) -> Option<Self> {
+                match value.as_str()? {
+                    $($value => Some(Self::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+    ($name:ty as int { $($variant:ident = $value:literal),+ $(,)? }) => {
+        impl $crate::enums::StoredEnum for $name {
+            const REPR: $crate::enums::EnumRepr = $crate::enums::EnumRepr::Int;
+
+            fn to_stored(&self) -> $crate::bson::Bson {
+                match self {
+                    $(Self::$variant => $crate::bson::Bson::Int32($value),)+
+                }
+            }
+
+            fn from_stored(value: &$crate::bson::Bson) -> Option<Self> {
+                let value: i64 = match value {
+                    $crate::bson::Bson::Int32(x) => *x as i64,
+                    $crate::bson::Bson::Int64(x) => *x,
+                    _ => return None,
+                };
+                $(if value == $value as i64 { return Some(Self::$variant); })+
+                None
+            }
+        }
+    };
+    ($name:ty as tagged) => {
+        impl $crate::enums::StoredEnum for $name {
+            const REPR: $crate::enums::EnumRepr = $crate::enums::EnumRepr::Tagged;
+
+            fn to_stored(&self) -> $crate::bson::Bson {
+                $crate::bson::to_bson(self).unwrap_or($crate::bson::Bson::Null)
+            }
+
+            fn from_stored(value: &$crate::bson::Bson) -> Option<Self> {
+                $crate::bson::from_bson(value.clone()).ok()
+            }
+        }
+    };
+}
+
+// SERDE HELPERS ===================================================================================================
+pub mod stored {
+    use super::StoredEnum;
+
+    pub fn serialize<T: StoredEnum, S: serde::Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&value.to_stored(), serializer)
+    }
+
+    pub fn deserialize<'de, T: StoredEnum, D: serde::Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        let value: bson::Bson = serde::Deserialize::deserialize(deserializer)?;
+        T::from_stored(&value).ok_or_else(|| {
+            serde::de::Error::custom(format!("invalid {} value: {}", std::any::type_name::<T>(), value))
+        })
+    }
+}
+
+pub mod stored_option {
+    use super::StoredEnum;
+
+    pub fn serialize<T: StoredEnum, S: serde::Serializer>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::stored::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T: StoredEnum, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<T>, D::Error> {
+        let value: Option<bson::Bson> = serde::Deserialize::deserialize(deserializer)?;
+        match value {
+            None | Some(bson::Bson::Null) => Ok(None),
+            Some(value) => T::from_stored(&value).map(Some).ok_or_else(|| {
+                serde::de::Error::custom(format!("invalid {} value: {}", std::any::type_name::<T>(), value))
+            }),
+        }
+    }
+}
+
+// FILTER HELPERS ==================================================================================================
+pub fn eq<T: StoredEnum>(field: &str, value: &T) -> bson::Document {
+    bson::doc! { field: value.to_stored() }
+}
+
+pub fn ne<T: StoredEnum>(field: &str, value: &T) -> bson::Document {
+    bson::doc! { field: { "$ne": value.to_stored() } }
+}
+
+pub fn one_of<T: StoredEnum>(field: &str, values: &[T]) -> bson::Document {
+    let values: Vec<bson::Bson> = values.iter().map(StoredEnum::to_stored).collect();
+    bson::doc! { field: { "$in": values } }
+}
+
+pub fn none_of<T: StoredEnum>(field: &str, values: &[T]) -> bson::Document {
+    let values: Vec<bson::Bson> = values.iter().map(StoredEnum::to_stored).collect();
+    bson::doc! { field: { "$nin": values } }
+}
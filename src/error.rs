@@ -0,0 +1,295 @@
+// ERRORS ==========================================================================================================
+// Every error returned by the trait methods is wrapped in `Error::WithContext`, naming the collection, the
+// method and a redacted summary of the filter or ID it ran with:
+//
+//     find_one_strict on `users` ({email: <string>}): User matching {email: <string>} not found
+//
+// Filter values are replaced by their BSON type so logs never carry user data, `_id` values are shortened to
+// their first characters. Match on `error.root()` to get at the underlying variant.
+
+use crate::config::ConfigError;
+use crate::startup::StartupReport;
+use crate::unique::ValidationErrors;
+use crate::IndexDrift;
+
+#[derive(Debug)]
+pub enum Error {
+    // `model` is the type name without its path, `id` is shortened and `filter` redacted like the context
+    NotFound { model: &'static str, id: Option<String>, filter: Option<String> },
+    InvalidId(String),
+    InvalidParams(String),
+    DBError(mongodb::error::Error),
+    BSONSerError(bson::ser::Error),
+    BSONDeError(bson::de::Error),
+    IOError(std::io::Error),
+    CreateFailed(String),
+    UpdateFailed(String),
+    DeleteFailed(String),
+    ImportFailed(String),
+    ExportFailed(String),
+    RestoreFailed(String),
+    IndexDrift(IndexDrift),
+    Timeout(std::time::Duration),
+    // A crate-level helper ran before `init`
+    NotInitialized,
+    InvalidConfig(ConfigError),
+    // The model's circuit breaker is open, the call never reached the database
+    CircuitOpen,
+    // The operation's `CancellationToken` was cancelled
+    Cancelled,
+    // `field` of the written document points to `_id`s not in `collection`, shortened like `NotFound`'s
+    BrokenReference { field: String, collection: String, missing: Vec<String> },
+    // A write while the model is read-only, see `read_only`
+    ReadOnly,
+    // An update writing one of the model's `immutable_fields`
+    ImmutableField(String),
+    // No keyring, or a key missing from it, see `encryption`
+    Encryption(String),
+    // Field-level problems of the written document, see `unique`
+    Validation(ValidationErrors),
+    // A write of a sharded model missing `field` of its `shard_key` from its "document" or "filter", see `shard`
+    MissingShardKey { field: String, from: &'static str },
+    // Problems found by `verify_startup`, the report lists them
+    StartupFailed(StartupReport),
+    WithContext(ErrorContext, Box<Error>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub collection: String,
+    pub operation: &'static str,
+    // Redacted filter or shortened ID
+    pub target: Option<String>,
+}
+
+impl Error {
+    pub fn not_found<M>() -> Self {
+        Error::NotFound { model: model_name::<M>(), id: None, filter: None }
+    }
+
+    pub fn not_found_id<M>(id: impl Into<bson::Bson>) -> Self {
+        Error::NotFound { model: model_name::<M>(), id: Some(shorten_id(&id.into())), filter: None }
+    }
+
+    pub fn not_found_filter<M>(filter: &bson::Document) -> Self {
+        Error::NotFound { model: model_name::<M>(), id: None, filter: Some(redact(filter)) }
+    }
+
+    pub fn broken_reference(field: &str, collection: &str, missing: &[bson::Bson]) -> Self {
+        let missing = missing.iter().map(shorten_id).collect();
+        Error::BrokenReference { field: field.to_string(), collection: collection.to_string(), missing }
+    }
+
+    // The variant without its context
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::WithContext(_, x) => x.root(),
+            x => x,
+        }
+    }
+
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext(context, _) => Some(context),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorContext {
+    pub fn new(collection: &str, operation: &'static str) -> Self {
+        Self { collection: collection.to_string(), operation, target: None }
+    }
+
+    pub fn filter(mut self, filter: &bson::Document) -> Self {
+        self.target = Some(redact(filter));
+        self
+    }
+
+    pub fn id(mut self, id: impl Into<bson::Bson>) -> Self {
+        self.target = Some(redact_value(&id.into(), true));
+        self
+    }
+
+    // Attaches this context unless the error already carries one from a nested call
+    pub fn wrap(&self, error: impl Into<Error>) -> Error {
+        match error.into() {
+            x @ Error::WithContext(..) => x,
+            x => Error::WithContext(self.clone(), Box::new(x)),
+        }
+    }
+
+    // `.map_err(context.wrapper(Self::map_error))?`
+    pub fn wrapper<'a, X: Into<Error>, E: 'a>(&'a self, map: fn(Error) -> E) -> impl Fn(X) -> E + Send + Sync + 'a {
+        move |x| map(self.wrap(x))
+    }
+}
+
+// `app::models::User` -> `User`
+pub(crate) fn model_name<M>() -> &'static str {
+    let name = std::any::type_name::<M>();
+    let path = name.split('<').next().unwrap_or(name);
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+pub fn redact(filter: &bson::Document) -> String {
+    redact_document(filter, false)
+}
+
+fn redact_document(document: &bson::Document, reveal: bool) -> String {
+    let fields: Vec<String> = document
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key, redact_value(value, reveal || key == "_id")))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn redact_value(value: &bson::Bson, reveal: bool) -> String {
+    match value {
+        bson::Bson::Document(x) => redact_document(x, reveal),
+        bson::Bson::Array(x) => {
+            let items: Vec<String> = x.iter().map(|x| redact_value(x, reveal)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        x if reveal => shorten_id(x),
+        x => format!("<{}>", type_name(x)),
+    }
+}
+
+pub(crate) fn shorten_id(value: &bson::Bson) -> String {
+    let full = match value {
+        bson::Bson::ObjectId(x) => x.to_hex(),
+        bson::Bson::String(x) => x.clone(),
+        bson::Bson::Binary(x) => x.bytes.iter().map(|x| format!("{:02x}", x)).collect(),
+        x => return format!("<{}>", type_name(x)),
+    };
+    match full.char_indices().nth(4) {
+        Some((end, _)) => format!("{}…", &full[..end]),
+        None => full,
+    }
+}
+
+pub(crate) fn type_name(value: &bson::Bson) -> &'static str {
+    match value {
+        bson::Bson::Double(_) => "double",
+        bson::Bson::String(_) => "string",
+        bson::Bson::Array(_) => "array",
+        bson::Bson::Document(_) => "object",
+        bson::Bson::Boolean(_) => "bool",
+        bson::Bson::Null => "null",
+        bson::Bson::RegularExpression(_) => "regex",
+        bson::Bson::JavaScriptCode(_) | bson::Bson::JavaScriptCodeWithScope(_) => "javascript",
+        bson::Bson::Int32(_) => "int",
+        bson::Bson::Int64(_) => "long",
+        bson::Bson::Timestamp(_) => "timestamp",
+        bson::Bson::Binary(_) => "binData",
+        bson::Bson::ObjectId(_) => "objectId",
+        bson::Bson::DateTime(_) => "date",
+        bson::Bson::Symbol(_) => "symbol",
+        bson::Bson::Decimal128(_) => "decimal",
+        bson::Bson::Undefined => "undefined",
+        bson::Bson::MaxKey => "maxKey",
+        bson::Bson::MinKey => "minKey",
+        bson::Bson::DbPointer(_) => "dbPointer",
+    }
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} on `{}`", self.operation, self.collection)?;
+        if let Some(target) = &self.target {
+            write!(f, " ({})", target)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound { model, id: Some(id), .. } => write!(f, "{} {} not found", model, id),
+            Error::NotFound { model, filter: Some(filter), .. } => write!(f, "{} matching {} not found", model, filter),
+            Error::NotFound { model, .. } => write!(f, "{} not found", model),
+            Error::InvalidId(x) => write!(f, "invalid id: {}", x),
+            Error::InvalidParams(x) => write!(f, "invalid parameters: {}", x),
+            Error::DBError(x) => write!(f, "database error: {}", x),
+            Error::BSONSerError(x) => write!(f, "BSON serialization failed: {}", x),
+            Error::BSONDeError(x) => write!(f, "BSON deserialization failed: {}", x),
+            Error::IOError(x) => write!(f, "I/O error: {}", x),
+            Error::CreateFailed(x) => write!(f, "create failed: {}", x),
+            Error::UpdateFailed(x) => write!(f, "update failed: {}", x),
+            Error::DeleteFailed(x) => write!(f, "delete failed: {}", x),
+            Error::ImportFailed(x) => write!(f, "import failed: {}", x),
+            Error::ExportFailed(x) => write!(f, "export failed: {}", x),
+            Error::RestoreFailed(x) => write!(f, "restore failed: {}", x),
+            Error::IndexDrift(x) => write!(
+                f,
+                "index drift: {} missing, {} extra, {} mismatched",
+                x.missing.len(),
+                x.extra.len(),
+                x.mismatched.len()
+            ),
+            Error::Timeout(x) => write!(f, "timed out after {:?}", x),
+            Error::NotInitialized => write!(f, "not initialized, call `init` first"),
+            Error::InvalidConfig(x) => write!(f, "invalid configuration: {}", x),
+            Error::CircuitOpen => write!(f, "circuit breaker open"),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::BrokenReference { field, collection, missing } => {
+                write!(f, "`{}` points to no document in `{}`: {}", field, collection, missing.join(", "))
+            }
+            Error::ReadOnly => write!(f, "read-only mode, writes are disabled"),
+            Error::ImmutableField(x) => write!(f, "`{}` can't be changed once created", x),
+            Error::Encryption(x) => write!(f, "field encryption failed: {}", x),
+            Error::Validation(x) => write!(f, "validation failed: {}", x),
+            Error::StartupFailed(x) => write!(f, "{}", x),
+            Error::MissingShardKey { field, from } => {
+                write!(f, "`{}` of the shard key is missing from the {}, the write can't target a shard", field, from)
+            }
+            Error::WithContext(context, x) => write!(f, "{}: {}", context, x),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::DBError(x) => Some(x),
+            Error::BSONSerError(x) => Some(x),
+            Error::BSONDeError(x) => Some(x),
+            Error::IOError(x) => Some(x),
+            Error::InvalidConfig(x) => Some(x),
+            Error::WithContext(_, x) => x.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<mongodb::error::Error> for Error {
+    fn from(error: mongodb::error::Error) -> Self {
+        Error::DBError(error)
+    }
+}
+
+impl From<bson::ser::Error> for Error {
+    fn from(error: bson::ser::Error) -> Self {
+        Error::BSONSerError(error)
+    }
+}
+
+impl From<bson::de::Error> for Error {
+    fn from(error: bson::de::Error) -> Self {
+        Error::BSONDeError(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::IOError(error)
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(error: ConfigError) -> Self {
+        Error::InvalidConfig(error)
+    }
+}
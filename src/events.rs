@@ -0,0 +1,171 @@
+// EVENT SOURCING ==================================================================================================
+// Aggregates stored as an append-only event log, `{ aggregate_id, version, event, recorded_at }` per event, with
+// an optional snapshot of the state every `snapshot_every()` events so rehydrating doesn't replay everything:
+//
+//     impl EventSourced for Account {
+//         type Event = AccountEvent;
+//         fn events() -> Collection<Document> { db().collection("account_events") }
+//         fn snapshots() -> Option<Collection<Document>> { Some(db().collection("account_snapshots")) }
+//         fn apply(&mut self, event: &AccountEvent) { ... }
+//     }
+//
+//     Account::append_event(&id, &AccountEvent::Deposited { amount: 10 }).await?;
+//     let (account, version) = Account::rehydrate(&id).await?;
+//
+// Versions start at 1 and are unique per aggregate (`ensure_event_indexes`), so concurrent appends can't both
+// take the same version: the loser retries with the next one. Appends, snapshots and `ensure_event_indexes` fail
+// with `Error::ReadOnly` while `read_only::enabled()`.
+
+use futures::TryStreamExt;
+
+use crate::{Error, ErrorContext, IdType};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Recorded<T> {
+    pub aggregate_id: IdType,
+    pub version: u64,
+    pub event: T,
+    pub recorded_at: bson::DateTime,
+}
+
+const APPEND_ATTEMPTS: usize = 10;
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(x)) => x.code == 11000,
+        _ => false,
+    }
+}
+
+#[async_trait::async_trait]
+pub trait EventSourced<E = Error>
+where
+    Self: Default + serde::Serialize + serde::de::DeserializeOwned + Send + Sync + 'static,
+    E: From<Error>,
+{
+    type Event: serde::Serialize + serde::de::DeserializeOwned + Send + Sync;
+
+    fn events() -> mongodb::Collection<bson::Document>;
+    fn apply(&mut self, event: &Self::Event);
+
+    // Optional: where snapshots go, `{ _id: aggregate_id, version, state }`. No snapshots by default.
+    fn snapshots() -> Option<mongodb::Collection<bson::Document>> {
+        None
+    }
+
+    fn snapshot_every() -> u64 {
+        100
+    }
+
+    async fn ensure_event_indexes() -> Result<(), E> {
+        let events = Self::events();
+        let context = ErrorContext::new(events.name(), "ensure_event_indexes");
+        if crate::read_only::enabled() {
+            return Err(context.wrap(Error::ReadOnly).into());
+        }
+        let index = crate::Index::asc("aggregate_id").then_asc("version").unique();
+        events.create_index(index.to_model(), None).await.map_err(context.wrapper(E::from))?;
+        Ok(())
+    }
+
+    // Returns the version of the appended event
+    async fn append_event(aggregate_id: &IdType, event: &Self::Event) -> Result<u64, E> {
+        let events = Self::events();
+        let context = ErrorContext::new(events.name(), "append_event").id(aggregate_id.to_owned());
+        if crate::read_only::enabled() {
+            return Err(context.wrap(Error::ReadOnly).into());
+        }
+        let event = bson::to_bson(event).map_err(context.wrapper(E::from))?;
+
+        let mut attempts = 0;
+        let version = loop {
+            let options = mongodb::options::FindOneOptions::builder().sort(bson::doc! { "version": -1 }).build();
+            let last = events
+                .find_one(bson::doc! { "aggregate_id": aggregate_id }, options)
+                .await
+                .map_err(context.wrapper(E::from))?;
+            let version = last.and_then(|x| crate::as_u64(x.get("version")?)).unwrap_or(0) + 1;
+
+            let document = bson::doc! {
+                "aggregate_id": aggregate_id,
+                "version": version as i64,
+                "event": event.clone(),
+                "recorded_at": bson::DateTime::now(),
+            };
+            match events.insert_one(document, None).await {
+                Ok(_) => break version,
+                Err(x) if is_duplicate_key(&x) && attempts + 1 < APPEND_ATTEMPTS => attempts += 1,
+                Err(x) => return Err(E::from(context.wrap(x))),
+            }
+        };
+
+        if Self::snapshots().is_some() && version % Self::snapshot_every().max(1) == 0 {
+            Self::save_snapshot(aggregate_id).await?;
+        }
+
+        Ok(version)
+    }
+
+    // Events with a version above `after`, oldest first
+    async fn load_events(aggregate_id: &IdType, after: u64) -> Result<Vec<Recorded<Self::Event>>, E> {
+        let events = Self::events();
+        let context = ErrorContext::new(events.name(), "load_events").id(aggregate_id.to_owned());
+
+        let filter = bson::doc! { "aggregate_id": aggregate_id, "version": { "$gt": after as i64 } };
+        let options = mongodb::options::FindOptions::builder().sort(bson::doc! { "version": 1 }).build();
+        let events = events.clone_with_type::<Recorded<Self::Event>>();
+        let cursor = events.find(filter, options).await.map_err(context.wrapper(E::from))?;
+        let items = cursor.try_collect().await.map_err(context.wrapper(E::from))?;
+
+        Ok(items)
+    }
+
+    // Latest snapshot plus the events recorded after it. Unknown aggregates come back as `Default` at version 0.
+    async fn rehydrate(aggregate_id: &IdType) -> Result<(Self, u64), E> {
+        let (mut state, mut version) = (Self::default(), 0);
+
+        if let Some(snapshots) = Self::snapshots() {
+            let context = ErrorContext::new(snapshots.name(), "rehydrate").id(aggregate_id.to_owned());
+            let snapshot = snapshots
+                .find_one(bson::doc! { "_id": aggregate_id }, None)
+                .await
+                .map_err(context.wrapper(E::from))?;
+            if let Some(mut snapshot) = snapshot {
+                version = snapshot.get("version").and_then(crate::as_u64).unwrap_or(0);
+                let saved = snapshot.remove("state").unwrap_or_default();
+                state = bson::from_bson(saved).map_err(context.wrapper(E::from))?;
+            }
+        }
+
+        for recorded in Self::load_events(aggregate_id, version).await? {
+            state.apply(&recorded.event);
+            version = recorded.version;
+        }
+
+        Ok((state, version))
+    }
+
+    // Never replaces a snapshot with an older one
+    async fn save_snapshot(aggregate_id: &IdType) -> Result<(), E> {
+        let Some(snapshots) = Self::snapshots() else {
+            return Ok(());
+        };
+        let context = ErrorContext::new(snapshots.name(), "save_snapshot").id(aggregate_id.to_owned());
+        if crate::read_only::enabled() {
+            return Err(context.wrap(Error::ReadOnly).into());
+        }
+
+        let (state, version) = Self::rehydrate(aggregate_id).await?;
+        let state = bson::to_bson(&state).map_err(context.wrapper(E::from))?;
+
+        let filter = bson::doc! { "_id": aggregate_id, "version": { "$lt": version as i64 } };
+        let update = bson::doc! { "$set": { "version": version as i64, "state": state } };
+        let options = mongodb::options::UpdateOptions::builder().upsert(true).build();
+        match snapshots.update_one(filter, update, options).await {
+            // The upsert collides with a newer snapshot that already exists
+            Err(x) if is_duplicate_key(&x) => Ok(()),
+            Err(x) => Err(E::from(context.wrap(x))),
+            Ok(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// EXPORT ==========================================================================================================
+// Formats and progress of `stream_to_writer`, which writes documents as they come off the cursor instead of
+// collecting them first, so a dump endpoint holds one batch in memory however big the collection is:
+//
+//     let mut body = Vec::new();
+//     let report = |x: Progress| println!("{} documents, {} bytes", x.documents, x.bytes);
+//     User::stream_to_writer(doc! {}, Format::Ndjson, &mut body, report).await?;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // One relaxed Extended JSON document per line, plain JSON for most types
+    Ndjson,
+    // One canonical Extended JSON document per line, keeps every BSON type (what `export_extjson` writes)
+    ExtJson,
+    // Documents as stored, back to back, like a `mongodump` `.bson` file
+    Bson,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Progress {
+    pub documents: u64,
+    pub bytes: u64,
+}
@@ -0,0 +1,53 @@
+// FIELDS ==========================================================================================================
+// `Field<M, T>` is the stored name of a field of model `M` holding a `T`, so single-field updates can't write a
+// value of the wrong type or to a misspelled field:
+//
+//     #[derive(Serialize, Deserialize, Fields)]
+//     struct User { #[serde(rename = "_id")] id: IdType, status: Status, nickname: Option<String> }
+//
+//     User::set_field(&id, User::fields().status, Status::Banned).await?;
+//     User::unset_field(&id, User::fields().nickname).await?;
+//
+// `#[derive(Fields)]` (`derive` feature) generates `User::fields()`, applying serde's `rename` and `rename_all`.
+// Without it the constants are written by hand: `const STATUS: Field<User, Status> = Field::new("status");`
+
+use std::marker::PhantomData;
+
+#[cfg(feature = "derive")]
+pub use rust_mongodb_model_methods_derive::Fields;
+
+pub struct Field<M, T> {
+    name: &'static str,
+    _model: PhantomData<fn() -> (M, T)>,
+}
+
+impl<M, T> Field<M, T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, _model: PhantomData }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+// Not derived, that would require `M: Clone` and `T: Clone`
+impl<M, T> Clone for Field<M, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M, T> Copy for Field<M, T> {}
+
+impl<M, T> std::fmt::Debug for Field<M, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Field({})", self.name)
+    }
+}
+
+impl<M, T> std::fmt::Display for Field<M, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -0,0 +1,127 @@
+// FILTERS =========================================================================================================
+// `FilterExpr` builds query filters out of typed values instead of `doc!` literals, so a misspelled operator
+// doesn't compile:
+//
+//     let active_adults = FilterExpr::eq("status", "active").and(FilterExpr::gte("age", 18));
+//     let count = User::count_where(active_adults).await?;
+//
+// Values are anything that converts into BSON: numbers, strings, dates, ObjectIds, UUIDs, `IdType`s. Decimals go
+// through `decimal::to_bson`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterExpr {
+    // Matches every document
+    All,
+    Field { field: String, operator: &'static str, value: bson::Bson },
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+    Nor(Vec<FilterExpr>),
+}
+
+impl FilterExpr {
+    fn field(field: &str, operator: &'static str, value: impl Into<bson::Bson>) -> Self {
+        Self::Field { field: field.to_string(), operator, value: value.into() }
+    }
+
+    pub fn eq(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$eq", value)
+    }
+
+    pub fn ne(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$ne", value)
+    }
+
+    pub fn gt(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$gt", value)
+    }
+
+    pub fn gte(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$gte", value)
+    }
+
+    pub fn lt(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$lt", value)
+    }
+
+    pub fn lte(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$lte", value)
+    }
+
+    pub fn is_in<V: Into<bson::Bson>>(field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Self::field(field, "$in", values.into_iter().map(Into::into).collect::<Vec<bson::Bson>>())
+    }
+
+    pub fn not_in<V: Into<bson::Bson>>(field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Self::field(field, "$nin", values.into_iter().map(Into::into).collect::<Vec<bson::Bson>>())
+    }
+
+    // Array `field` has `value` among its elements
+    pub fn array_contains(field: &str, value: impl Into<bson::Bson>) -> Self {
+        Self::field(field, "$all", vec![value.into()])
+    }
+
+    // Array `field` has every one of `values`, in any order
+    pub fn array_contains_all<V: Into<bson::Bson>>(field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Self::field(field, "$all", values.into_iter().map(Into::into).collect::<Vec<bson::Bson>>())
+    }
+
+    pub fn exists(field: &str, exists: bool) -> Self {
+        Self::field(field, "$exists", exists)
+    }
+
+    pub fn and(self, other: FilterExpr) -> Self {
+        match (self, other) {
+            (Self::All, x) | (x, Self::All) => x,
+            (Self::And(mut x), Self::And(y)) => {
+                x.extend(y);
+                Self::And(x)
+            }
+            (Self::And(mut x), y) => {
+                x.push(y);
+                Self::And(x)
+            }
+            (x, y) => Self::And(vec![x, y]),
+        }
+    }
+
+    pub fn or(self, other: FilterExpr) -> Self {
+        match (self, other) {
+            (Self::All, _) | (_, Self::All) => Self::All,
+            (Self::Or(mut x), Self::Or(y)) => {
+                x.extend(y);
+                Self::Or(x)
+            }
+            (Self::Or(mut x), y) => {
+                x.push(y);
+                Self::Or(x)
+            }
+            (x, y) => Self::Or(vec![x, y]),
+        }
+    }
+
+    pub fn to_document(&self) -> bson::Document {
+        let list = |x: &[FilterExpr]| x.iter().map(|x| bson::Bson::Document(x.to_document())).collect::<Vec<_>>();
+        match self {
+            Self::All => bson::Document::new(),
+            Self::Field { field, operator, value } => bson::doc! { field: { *operator: value.clone() } },
+            Self::And(x) => bson::doc! { "$and": list(x) },
+            Self::Or(x) => bson::doc! { "$or": list(x) },
+            Self::Nor(x) => bson::doc! { "$nor": list(x) },
+        }
+    }
+}
+
+// `!filter` matches the documents `filter` doesn't
+impl std::ops::Not for FilterExpr {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Nor(vec![self])
+    }
+}
+
+impl From<FilterExpr> for bson::Document {
+    fn from(filter: FilterExpr) -> Self {
+        filter.to_document()
+    }
+}
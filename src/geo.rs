@@ -0,0 +1,113 @@
+// GEO =============================================================================================================
+// `$geoNear` queries returning each document with its distance from the query point:
+//
+//     let nearby: Vec<(Shop, f64)> = Shop::geo_near(
+//         GeoNear::new(Point::new(76.9286, 43.2567))
+//             .key("location")
+//             .max_distance(2_000.0)
+//             .query(doc! { "open": true }),
+//     )
+//     .await?;
+//
+// With a 2dsphere index distances are in meters, with a legacy 2d index in coordinate units.
+
+pub(crate) const DISTANCE_FIELD: &str = "_geo_distance";
+
+// GeoJSON point, longitude first as the spec requires
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename = "Point")]
+pub struct Point {
+    pub coordinates: [f64; 2],
+}
+
+impl Point {
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Self { coordinates: [longitude, latitude] }
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.coordinates[0]
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.coordinates[1]
+    }
+
+    pub fn to_bson(&self) -> bson::Bson {
+        bson::Bson::Document(bson::doc! { "type": "Point", "coordinates": [self.coordinates[0], self.coordinates[1]] })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoNear {
+    near: Point,
+    key: Option<String>,
+    max_distance: Option<f64>,
+    min_distance: Option<f64>,
+    query: Option<bson::Document>,
+    spherical: bool,
+    limit: Option<u64>,
+}
+
+impl GeoNear {
+    pub fn new(near: Point) -> Self {
+        Self { near, key: None, max_distance: None, min_distance: None, query: None, spherical: true, limit: None }
+    }
+
+    // Required when the collection has more than one geo index
+    pub fn key(mut self, field: &str) -> Self {
+        self.key = Some(field.to_string());
+        self
+    }
+
+    pub fn max_distance(mut self, distance: f64) -> Self {
+        self.max_distance = Some(distance);
+        self
+    }
+
+    pub fn min_distance(mut self, distance: f64) -> Self {
+        self.min_distance = Some(distance);
+        self
+    }
+
+    pub fn query(mut self, filter: bson::Document) -> Self {
+        self.query = Some(filter);
+        self
+    }
+
+    pub fn spherical(mut self, spherical: bool) -> Self {
+        self.spherical = spherical;
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn pipeline(&self) -> Vec<bson::Document> {
+        let mut stage = bson::doc! {
+            "near": self.near.to_bson(),
+            "distanceField": DISTANCE_FIELD,
+            "spherical": self.spherical,
+        };
+        if let Some(key) = &self.key {
+            stage.insert("key", key);
+        }
+        if let Some(distance) = self.max_distance {
+            stage.insert("maxDistance", distance);
+        }
+        if let Some(distance) = self.min_distance {
+            stage.insert("minDistance", distance);
+        }
+        if let Some(query) = &self.query {
+            stage.insert("query", query.clone());
+        }
+
+        let mut pipeline = vec![bson::doc! { "$geoNear": stage }];
+        if let Some(limit) = self.limit {
+            pipeline.push(bson::doc! { "$limit": limit as i64 });
+        }
+        pipeline
+    }
+}
@@ -0,0 +1,128 @@
+// ASYNC-GRAPHQL ===================================================================================================
+// Relay connections and dataloaders for models that are also GraphQL objects (feature "graphql"):
+//
+//     #[Object]
+//     impl Query {
+//         async fn users(&self, after: Option<String>, before: Option<String>, first: Option<i32>, last: Option<i32>)
+//             -> async_graphql::Result<Connection<String, User>> {
+//             graphql::connection::<User, Error>(doc! {}, after, before, first, last).await
+//         }
+//     }
+//
+//     // One `find_by_ids` per request instead of one query per order
+//     let loader = DataLoader::new(ModelLoader::<User>::new(), tokio::spawn);
+//     let user = ctx.data_unchecked::<DataLoader<ModelLoader<User>>>().load_one(order.user_id).await?;
+//
+// Cursors are `_id` values, so pages are stable under concurrent inserts and follow `_id` order. Errors reach
+// clients with a `code` extension and, for internal ones, a generic message; models with a custom error type need
+// `MyError: ErrorExtensions`.
+
+use std::collections::HashMap;
+
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::dataloader::Loader;
+use async_graphql::ErrorExtensions;
+
+use crate::{id_type, Error, IdType, RustMongoDBModelMethods};
+
+// Like the axum `IntoResponse`, server-side details stay in the logs, not in what clients see
+impl ErrorExtensions for Error {
+    fn extend(&self) -> async_graphql::Error {
+        let (code, message) = match self.root() {
+            x @ Error::NotFound { .. } => ("NOT_FOUND", x.to_string()),
+            Error::InvalidId(reason) => ("BAD_REQUEST", format!("Invalid id: {}", reason)),
+            Error::InvalidParams(reason) => ("BAD_REQUEST", reason.clone()),
+            x @ Error::BrokenReference { .. } => ("UNPROCESSABLE_ENTITY", x.to_string()),
+            x @ Error::ImmutableField(_) => ("UNPROCESSABLE_ENTITY", x.to_string()),
+            Error::Validation(x) => ("UNPROCESSABLE_ENTITY", x.to_string()),
+            x @ Error::MissingShardKey { .. } => ("BAD_REQUEST", x.to_string()),
+            Error::Timeout(_) => ("TIMEOUT", "Timed out".to_string()),
+            Error::CircuitOpen => ("SERVICE_UNAVAILABLE", "Service unavailable".to_string()),
+            Error::ReadOnly => ("SERVICE_UNAVAILABLE", "Read-only mode".to_string()),
+            _ => ("INTERNAL_SERVER_ERROR", "Internal server error".to_string()),
+        };
+        async_graphql::Error::new(message).extend_with(|_, x| x.set("code", code))
+    }
+}
+
+pub async fn connection<M, E>(
+    filter: bson::Document,
+    after: Option<String>,
+    before: Option<String>,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> async_graphql::Result<Connection<String, M>>
+where
+    M: RustMongoDBModelMethods<E> + async_graphql::OutputType,
+    E: From<Error> + ErrorExtensions,
+{
+    let load = |after: Option<String>, before: Option<String>, first: Option<usize>, last: Option<usize>| async move {
+        let mut range = bson::Document::new();
+        if let Some(after) = &after {
+            range.insert("$gt", id_type::parse(after).map_err(|x| Error::InvalidId(x).extend())?);
+        }
+        if let Some(before) = &before {
+            range.insert("$lt", id_type::parse(before).map_err(|x| Error::InvalidId(x).extend())?);
+        }
+        let filter = match range.is_empty() {
+            true => filter,
+            false => bson::doc! { "$and": [filter, { "_id": range }] },
+        };
+
+        // `last` without `first` pages backwards from `before`
+        let backwards = last.is_some() && first.is_none();
+        let limit = first.or(last);
+        let options = mongodb::options::FindOptions::builder()
+            .sort(bson::doc! { "_id": if backwards { -1 } else { 1 } })
+            .limit(limit.map(|x| x as i64 + 1))
+            .build();
+
+        let mut items = M::find_with_options(filter, options).await.map_err(|x| x.extend())?;
+        let has_more = limit.is_some_and(|x| items.len() > x);
+        if let Some(limit) = limit {
+            items.truncate(limit);
+        }
+        if backwards {
+            items.reverse();
+        }
+
+        let (has_previous, has_next) = match backwards {
+            true => (has_more, before.is_some()),
+            false => (after.is_some(), has_more),
+        };
+        let mut connection = Connection::new(has_previous, has_next);
+        connection.edges.extend(items.into_iter().map(|x| Edge::new(x.id_value().to_string(), x)));
+        Ok::<_, async_graphql::Error>(connection)
+    };
+    async_graphql::connection::query(after, before, first, last, load).await
+}
+
+pub struct ModelLoader<M, E = Error> {
+    _model: std::marker::PhantomData<fn() -> (M, E)>,
+}
+
+impl<M, E> ModelLoader<M, E> {
+    pub fn new() -> Self {
+        Self { _model: std::marker::PhantomData }
+    }
+}
+
+impl<M, E> Default for ModelLoader<M, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M, E> Loader<IdType> for ModelLoader<M, E>
+where
+    M: RustMongoDBModelMethods<E> + Clone,
+    E: From<Error> + ErrorExtensions + Send + 'static,
+{
+    type Value = M;
+    type Error = async_graphql::Error;
+
+    async fn load(&self, keys: &[IdType]) -> Result<HashMap<IdType, M>, Self::Error> {
+        let items = M::find_by_ids(keys).await.map_err(|x| x.extend())?;
+        Ok(items.into_iter().map(|x| (x.id_value().to_owned(), x)).collect())
+    }
+}
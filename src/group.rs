@@ -0,0 +1,156 @@
+// GROUP BY ========================================================================================================
+// Typed `$group` rollups:
+//
+//     let per_status: Vec<(String, u64)> = Order::count_by("status").await?;
+//     let revenue: Vec<(String, f64)> = Order::sum_by("country", "total").await?;
+//     let stats: Vec<(String, Stats)> = Order::group_by("country")
+//         .filter(doc! { "paid": true })
+//         .agg("orders", Accumulator::Count)
+//         .agg("revenue", Accumulator::sum("total"))
+//         .run()
+//         .await?;
+//
+// With a single accumulator the value is deserialized from that accumulator alone, with several from a
+// document holding one field per accumulator name.
+//
+// `find_duplicates` lists the values a unique index on `fields` would reject, run it before adding one to old data:
+//
+//     for x in User::find_duplicates(&["email"], doc! {}).await? {
+//         println!("{} x{}: {:?}", x.key, x.ids.len(), x.ids);
+//     }
+
+use crate::{Error, ErrorContext, IdType, RustMongoDBModelMethods};
+
+#[derive(Debug, Clone)]
+pub enum Accumulator {
+    Count,
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+    First(String),
+    Last(String),
+    Push(String),
+    AddToSet(String),
+}
+
+impl Accumulator {
+    pub fn sum(field: &str) -> Self {
+        Self::Sum(field.to_string())
+    }
+    pub fn avg(field: &str) -> Self {
+        Self::Avg(field.to_string())
+    }
+    pub fn min(field: &str) -> Self {
+        Self::Min(field.to_string())
+    }
+    pub fn max(field: &str) -> Self {
+        Self::Max(field.to_string())
+    }
+
+    pub fn to_bson(&self) -> bson::Bson {
+        let (operator, field) = match self {
+            Self::Count => return bson::Bson::Document(bson::doc! { "$sum": 1 }),
+            Self::Sum(field) => ("$sum", field),
+            Self::Avg(field) => ("$avg", field),
+            Self::Min(field) => ("$min", field),
+            Self::Max(field) => ("$max", field),
+            Self::First(field) => ("$first", field),
+            Self::Last(field) => ("$last", field),
+            Self::Push(field) => ("$push", field),
+            Self::AddToSet(field) => ("$addToSet", field),
+        };
+        bson::Bson::Document(bson::doc! { operator: format!("${}", field) })
+    }
+}
+
+pub struct GroupBy<M, E = Error> {
+    field: String,
+    filter: bson::Document,
+    accumulators: bson::Document,
+    _model: std::marker::PhantomData<fn() -> (M, E)>,
+}
+
+impl<M, E> GroupBy<M, E>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    pub fn new(field: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            filter: bson::Document::new(),
+            accumulators: bson::Document::new(),
+            _model: std::marker::PhantomData,
+        }
+    }
+
+    pub fn filter(mut self, filter: bson::Document) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn agg(mut self, name: &str, accumulator: Accumulator) -> Self {
+        self.accumulators.insert(name, accumulator.to_bson());
+        self
+    }
+
+    pub fn pipeline(&self) -> Vec<bson::Document> {
+        let mut group = bson::doc! { "_id": format!("${}", self.field) };
+        group.extend(self.accumulators.clone());
+
+        vec![
+            bson::doc! { "$match": self.filter.clone() },
+            bson::doc! { "$group": group },
+            bson::doc! { "$sort": { "_id": 1 } },
+        ]
+    }
+
+    pub async fn run<K, V>(self) -> Result<Vec<(K, V)>, E>
+    where
+        K: serde::de::DeserializeOwned,
+        V: serde::de::DeserializeOwned,
+    {
+        let context = ErrorContext::new(M::collection().name(), "group_by").filter(&self.filter);
+        let rows = M::aggregate::<bson::Document>(self.pipeline()).await?;
+        let single = self.accumulators.len() == 1;
+
+        let items = rows
+            .into_iter()
+            .map(|mut row| {
+                let key = row.remove("_id").unwrap_or(bson::Bson::Null);
+                let value = match single {
+                    true => row.into_iter().next().map(|(_, x)| x).unwrap_or(bson::Bson::Null),
+                    false => bson::Bson::Document(row),
+                };
+                Ok((bson::from_bson(key)?, bson::from_bson(value)?))
+            })
+            .collect::<Result<Vec<(K, V)>, bson::de::Error>>()
+            .map_err(context.wrapper(M::map_error))?;
+
+        Ok(items)
+    }
+}
+
+// Documents sharing the same values of the grouped fields. Missing fields group as `null`, as in a unique index.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Duplicate {
+    // The shared values, by field name
+    pub key: bson::Document,
+    pub ids: Vec<IdType>,
+}
+
+// Group keys can't contain dots, so fields are grouped under `f0`, `f1`... and renamed back before returning
+pub(crate) fn duplicates_pipeline(fields: &[&str], filter: bson::Document) -> Vec<bson::Document> {
+    let group: bson::Document =
+        fields.iter().enumerate().map(|(i, x)| (format!("f{i}"), format!("${x}").into())).collect();
+    let key: bson::Document =
+        fields.iter().enumerate().map(|(i, x)| (x.to_string(), format!("$_id.f{i}").into())).collect();
+    vec![
+        bson::doc! { "$match": filter },
+        bson::doc! { "$group": { "_id": group, "ids": { "$push": "$_id" }, "count": { "$sum": 1 } } },
+        bson::doc! { "$match": { "count": { "$gt": 1 } } },
+        bson::doc! { "$sort": { "count": -1 } },
+        bson::doc! { "$project": { "_id": 0, "key": key, "ids": 1 } },
+    ]
+}
@@ -0,0 +1,237 @@
+// HASHED FIELDS ===================================================================================================
+// `Hashed` holds a password hash, never the password: it hashes when built and only the hash is serialized.
+//
+//     struct User {
+//         email: String,
+//         password: Hashed<String>,
+//     }
+//
+//     let user = User { email, password: Hashed::new_async(&form.password).await };
+//     if !user.password.verify_async(&form.password).await { ... }
+//     if user.password.needs_rehash() {
+//         let password = Hashed::<String>::new_async(&form.password).await;
+//         User::update_by_id(&user.id, doc! { "password": password.as_str() }).await?;
+//     }
+//
+// Hashing is slow on purpose, in async code use `new_async` and `verify_async`, which run on tokio's blocking pool;
+// `new` and `verify` block the thread they're called on.
+//
+// New hashes use argon2id with the `argon2` feature, bcrypt with `bcrypt` (cost 12, only the first 72 bytes of the
+// password count) and PBKDF2-HMAC-SHA256 otherwise, stored as `$pbkdf2-sha256$<iterations>$<salt>$<hash>` (hex,
+// random 16-byte salt). Hashes of the other schemes still verify as long as their feature is on, so switching
+// schemes doesn't lock anyone out: `needs_rehash` tells which to replace at the next login, also the ones hashed
+// with weaker parameters than the current ones, the cost is part of the hash. `Debug` and `Display` don't show the
+// hash either.
+
+use std::marker::PhantomData;
+use std::num::NonZeroU32;
+
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+
+pub const ITERATIONS: u32 = 600_000;
+
+const PBKDF2: &str = "pbkdf2-sha256";
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Pbkdf2,
+    #[cfg(feature = "argon2")]
+    Argon2,
+    #[cfg(feature = "bcrypt")]
+    Bcrypt,
+}
+
+// What new hashes use
+#[cfg(feature = "argon2")]
+const CURRENT: Scheme = Scheme::Argon2;
+#[cfg(all(feature = "bcrypt", not(feature = "argon2")))]
+const CURRENT: Scheme = Scheme::Bcrypt;
+#[cfg(not(any(feature = "argon2", feature = "bcrypt")))]
+const CURRENT: Scheme = Scheme::Pbkdf2;
+
+fn salt() -> [u8; SALT_LEN] {
+    let mut salt = [0; SALT_LEN];
+    SystemRandom::new().fill(&mut salt).expect("system randomness");
+    salt
+}
+
+fn hash(scheme: Scheme, plaintext: &[u8]) -> String {
+    match scheme {
+        Scheme::Pbkdf2 => {
+            let salt = salt();
+            let iterations = NonZeroU32::new(ITERATIONS).expect("non-zero iterations");
+            let mut hash = [0; HASH_LEN];
+            pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, plaintext, &mut hash);
+            format!("${}${}${}${}", PBKDF2, ITERATIONS, hex(&salt), hex(&hash))
+        }
+        #[cfg(feature = "argon2")]
+        Scheme::Argon2 => {
+            use argon2::PasswordHasher;
+            let salt = argon2::password_hash::SaltString::encode_b64(&salt()).expect("16-byte salt");
+            let hash = argon2::Argon2::default().hash_password(plaintext, &salt).expect("default argon2 parameters");
+            hash.to_string()
+        }
+        #[cfg(feature = "bcrypt")]
+        Scheme::Bcrypt => bcrypt::hash(plaintext, bcrypt::DEFAULT_COST).expect("default bcrypt cost"),
+    }
+}
+
+// `T` is the type of the plaintext, it only keeps `Hashed<String>` apart from other hashed fields
+pub struct Hashed<T = String> {
+    hash: String,
+    plaintext: PhantomData<fn(T)>,
+}
+
+impl<T> Hashed<T> {
+    pub fn new(plaintext: impl AsRef<[u8]>) -> Self {
+        Self::from_hash(hash(CURRENT, plaintext.as_ref()))
+    }
+
+    // `new` on the blocking pool, doesn't hold up other tasks for the hashing time
+    pub async fn new_async(plaintext: impl AsRef<[u8]>) -> Self {
+        let plaintext = plaintext.as_ref().to_vec();
+        let hash = tokio::task::spawn_blocking(move || hash(CURRENT, &plaintext)).await.expect("hashing panicked");
+        Self::from_hash(hash)
+    }
+
+    // Constant time, a malformed stored hash or one of a scheme whose feature is off verifies nothing
+    pub fn verify(&self, candidate: impl AsRef<[u8]>) -> bool {
+        let candidate = candidate.as_ref();
+        match self.scheme() {
+            Some(Scheme::Pbkdf2) => self.pbkdf2_parts().is_some_and(|(iterations, salt, hash)| {
+                pbkdf2::verify(pbkdf2::PBKDF2_HMAC_SHA256, iterations, &salt, candidate, &hash).is_ok()
+            }),
+            #[cfg(feature = "argon2")]
+            Some(Scheme::Argon2) => {
+                use argon2::PasswordVerifier;
+                let hash = argon2::PasswordHash::new(&self.hash);
+                hash.is_ok_and(|x| argon2::Argon2::default().verify_password(candidate, &x).is_ok())
+            }
+            #[cfg(feature = "bcrypt")]
+            Some(Scheme::Bcrypt) => bcrypt::verify(candidate, &self.hash).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    // `verify` on the blocking pool
+    pub async fn verify_async(&self, candidate: impl AsRef<[u8]>) -> bool {
+        let (hashed, candidate) = (Hashed::<()>::from_hash(self.hash.clone()), candidate.as_ref().to_vec());
+        tokio::task::spawn_blocking(move || hashed.verify(candidate)).await.expect("verifying panicked")
+    }
+
+    fn from_hash(hash: String) -> Self {
+        Self { hash, plaintext: PhantomData }
+    }
+
+    // The stored form, e.g. for a `$set`
+    pub fn as_str(&self) -> &str {
+        &self.hash
+    }
+
+    // Hashed with another scheme than new hashes, or with weaker parameters
+    pub fn needs_rehash(&self) -> bool {
+        if self.scheme() != Some(CURRENT) {
+            return true;
+        }
+        match CURRENT {
+            Scheme::Pbkdf2 => self.pbkdf2_parts().is_none_or(|(iterations, _, _)| iterations.get() < ITERATIONS),
+            #[cfg(feature = "argon2")]
+            Scheme::Argon2 => {
+                let hash = argon2::PasswordHash::new(&self.hash).ok();
+                let params = hash.as_ref().and_then(|x| argon2::Params::try_from(x).ok());
+                let current = argon2::Params::DEFAULT;
+                params.is_none_or(|x| {
+                    x.m_cost() < current.m_cost() || x.t_cost() < current.t_cost() || x.p_cost() < current.p_cost()
+                })
+            }
+            #[cfg(feature = "bcrypt")]
+            Scheme::Bcrypt => {
+                let cost = self.hash.get(4..6).and_then(|x| x.parse::<u32>().ok());
+                cost.is_none_or(|x| x < bcrypt::DEFAULT_COST)
+            }
+        }
+    }
+
+    // The scheme of a well-formed stored hash, among the enabled ones
+    fn scheme(&self) -> Option<Scheme> {
+        if self.pbkdf2_parts().is_some() {
+            return Some(Scheme::Pbkdf2);
+        }
+        #[cfg(feature = "argon2")]
+        if argon2::PasswordHash::new(&self.hash).is_ok_and(|x| x.algorithm.as_str().starts_with("argon2")) {
+            return Some(Scheme::Argon2);
+        }
+        #[cfg(feature = "bcrypt")]
+        if ["$2a$", "$2b$", "$2y$"].iter().any(|x| self.hash.starts_with(x)) && self.hash.len() == 60 {
+            return Some(Scheme::Bcrypt);
+        }
+        None
+    }
+
+    fn pbkdf2_parts(&self) -> Option<(NonZeroU32, Vec<u8>, Vec<u8>)> {
+        let mut parts = self.hash.strip_prefix('$')?.split('$');
+        if parts.next()? != PBKDF2 {
+            return None;
+        }
+        let iterations = NonZeroU32::new(parts.next()?.parse().ok()?)?;
+        let (salt, hash) = (unhex(parts.next()?)?, unhex(parts.next()?)?);
+        parts.next().is_none().then_some((iterations, salt, hash))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+impl<T> Clone for Hashed<T> {
+    fn clone(&self) -> Self {
+        Self::from_hash(self.hash.clone())
+    }
+}
+
+impl<T> PartialEq for Hashed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
+impl<T> Eq for Hashed<T> {}
+
+impl<T> std::fmt::Debug for Hashed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Hashed(..)")
+    }
+}
+
+impl<T> std::fmt::Display for Hashed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<hashed>")
+    }
+}
+
+impl<T> serde::Serialize for Hashed<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.hash)
+    }
+}
+
+// Only hashes in the stored form, a plaintext password in the database fails to deserialize
+impl<'de, T> serde::Deserialize<'de> for Hashed<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hash = Self::from_hash(String::deserialize(deserializer)?);
+        match hash.scheme() {
+            Some(_) => Ok(hash),
+            None => Err(serde::de::Error::custom("not a password hash of an enabled scheme")),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// HEALTH ==========================================================================================================
+// `health()` pings the database set up by `init` and reports what a `/healthz` endpoint needs to show:
+//
+//     async fn healthz() -> Result<Json<Health>, Error> {
+//         Ok(Json(rms::health().await?))
+//     }
+//
+// The role is the one of the server the driver picked for the check, the primary unless the client's read
+// preference says otherwise.
+//
+// `warmup()` is for before the service takes traffic: it opens pool connections up front, so the first requests
+// don't pay for the TCP, TLS and auth handshakes, and fails right away on wrong credentials:
+//
+//     rms::init(options, "shop")?;
+//     rms::warmup(10).await?;
+//     axum::serve(listener, app).await?;
+//
+// It sends rounds of `min_connections` concurrent commands until the pools hold that many connections, up to 10
+// rounds. Connections count across all servers of the deployment and the pool closes them again after the
+// client's `max_idle_time`, set `min_pool_size` too to keep them open. `warmup_of` warms a `Router` route.
+
+use crate::connection::{self, PoolStats};
+use crate::{Error, ErrorContext};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Health {
+    // Round trip of the `ping` command
+    pub latency_ms: f64,
+    pub server_version: String,
+    pub role: Role,
+    pub replica_set: Option<String>,
+    pub pool: PoolStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Primary,
+    Secondary,
+    Arbiter,
+    Standalone,
+    Mongos,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Warmup {
+    // Open connections afterwards, short of `min_connections` when the pool's `max_pool_size` is smaller
+    pub connections: u64,
+    // Users the connections authenticated as, `user@db`, empty without credentials
+    pub authenticated_users: Vec<String>,
+    pub elapsed_ms: f64,
+}
+
+pub async fn warmup(min_connections: u32) -> Result<Warmup, Error> {
+    warm(connection::connection()?, min_connections).await
+}
+
+pub async fn warmup_of(logical: &str, min_connections: u32) -> Result<Warmup, Error> {
+    warm(connection::connection_of(logical)?, min_connections).await
+}
+
+async fn warm(connection: &connection::Connection, min_connections: u32) -> Result<Warmup, Error> {
+    const ROUNDS: usize = 10;
+
+    let admin = connection.client.database("admin");
+    let context = ErrorContext::new(admin.name(), "warmup");
+    let started = std::time::Instant::now();
+
+    // Authenticates the first connection, failing on bad credentials before opening more
+    let status = admin.run_command(bson::doc! { "connectionStatus": 1 }, None).await.map_err(|x| context.wrap(x))?;
+    let users = status.get_document("authInfo").and_then(|x| x.get_array("authenticatedUsers"));
+    let authenticated_users = users
+        .map(|x| x.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|x| x.as_document())
+        .map(|x| format!("{}@{}", x.get_str("user").unwrap_or_default(), x.get_str("db").unwrap_or_default()))
+        .collect();
+
+    for _ in 0..ROUNDS {
+        if connection.pool_stats().open >= u64::from(min_connections) {
+            break;
+        }
+        let pings = (0..min_connections).map(|_| admin.run_command(bson::doc! { "ping": 1 }, None));
+        for result in futures::future::join_all(pings).await {
+            result.map_err(|x| context.wrap(x))?;
+        }
+    }
+
+    Ok(Warmup {
+        connections: connection.pool_stats().open,
+        authenticated_users,
+        elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+pub async fn health() -> Result<Health, Error> {
+    let connection = connection::connection()?;
+    let admin = connection.client.database("admin");
+    let context = ErrorContext::new(admin.name(), "health");
+
+    let started = std::time::Instant::now();
+    admin.run_command(bson::doc! { "ping": 1 }, None).await.map_err(|x| context.wrap(x))?;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let build_info = admin.run_command(bson::doc! { "buildInfo": 1 }, None).await.map_err(|x| context.wrap(x))?;
+    let server_version = build_info.get_str("version").unwrap_or_default().to_string();
+
+    let hello = admin.run_command(bson::doc! { "hello": 1 }, None).await.map_err(|x| context.wrap(x))?;
+    let replica_set = hello.get_str("setName").ok().map(str::to_string);
+    let flag = |key| hello.get_bool(key).unwrap_or(false);
+    let role = if hello.get_str("msg") == Ok("isdbgrid") {
+        Role::Mongos
+    } else if replica_set.is_none() {
+        Role::Standalone
+    } else if flag("isWritablePrimary") {
+        Role::Primary
+    } else if flag("secondary") {
+        Role::Secondary
+    } else if flag("arbiterOnly") {
+        Role::Arbiter
+    } else {
+        Role::Other
+    };
+
+    Ok(Health { latency_ms, server_version, role, replica_set, pool: connection.pool_stats() })
+}
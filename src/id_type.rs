@@ -0,0 +1,106 @@
+// ID TYPE =========================================================================================================
+// `uuid_as_id` is a default feature, so it's routinely enabled next to an explicit `oid_as_id` or
+// `string_as_id` through transitive dependencies. The explicitly chosen kind wins over the default, only
+// `oid_as_id` + `string_as_id` together is a real conflict.
+
+#[cfg(all(feature = "oid_as_id", feature = "string_as_id"))]
+compile_error!("features `oid_as_id` and `string_as_id` are mutually exclusive, enable only one of them");
+
+#[cfg(not(any(feature = "oid_as_id", feature = "uuid_as_id", feature = "string_as_id")))]
+compile_error!("enable one of the `oid_as_id`, `uuid_as_id` (default) or `string_as_id` features");
+
+#[cfg(all(feature = "oid_as_id", not(feature = "string_as_id")))]
+mod selected {
+    pub type IdType = bson::oid::ObjectId;
+
+    pub(crate) fn from_bson(value: &bson::Bson) -> Option<IdType> {
+        value.as_object_id()
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<IdType, String> {
+        bson::oid::ObjectId::parse_str(value).map_err(|x| x.to_string())
+    }
+
+    pub(crate) fn generate() -> IdType {
+        bson::oid::ObjectId::new()
+    }
+
+    pub(crate) fn is_unset(value: &bson::Bson) -> bool {
+        match value {
+            bson::Bson::Null => true,
+            bson::Bson::ObjectId(x) => x.bytes() == [0; 12],
+            _ => false,
+        }
+    }
+}
+
+#[cfg(all(feature = "uuid_as_id", not(feature = "oid_as_id"), not(feature = "string_as_id")))]
+mod selected {
+    pub type IdType = bson::Uuid;
+
+    // Accepts generic Binary too: `uuid::Uuid`'s own serde impl writes subtype 0 unless the field uses
+    // `uuid_as_binary`, and such documents should still be readable
+    pub(crate) fn from_bson(value: &bson::Bson) -> Option<IdType> {
+        match value {
+            bson::Bson::Binary(bson::Binary {
+                subtype: bson::spec::BinarySubtype::Uuid | bson::spec::BinarySubtype::Generic,
+                bytes,
+            }) => <[u8; 16]>::try_from(bytes.as_slice()).ok().map(bson::Uuid::from_bytes),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<IdType, String> {
+        bson::Uuid::parse_str(value).map_err(|x| x.to_string())
+    }
+
+    pub(crate) fn generate() -> IdType {
+        bson::Uuid::new()
+    }
+
+    // The nil UUID counts as unset
+    pub(crate) fn is_unset(value: &bson::Bson) -> bool {
+        match value {
+            bson::Bson::Null => true,
+            bson::Bson::Binary(x) => x.bytes.iter().all(|x| *x == 0),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(all(feature = "string_as_id", not(feature = "oid_as_id")))]
+mod selected {
+    pub type IdType = String;
+
+    pub(crate) fn from_bson(value: &bson::Bson) -> Option<IdType> {
+        value.as_str().map(str::to_string)
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<IdType, String> {
+        Ok(value.to_string())
+    }
+
+    // ULIDs keep string IDs sortable by creation time
+    pub(crate) fn generate() -> IdType {
+        use crate::ids::IdGenerator;
+        crate::ids::Ulid::generate()
+    }
+
+    pub(crate) fn is_unset(value: &bson::Bson) -> bool {
+        match value {
+            bson::Bson::Null => true,
+            bson::Bson::String(x) => x.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+pub use selected::IdType;
+pub(crate) use selected::{from_bson, generate, is_unset, parse};
+
+// Stores a `uuid::Uuid` field as the standard UUID binary subtype 4 instead of generic binary:
+//
+//     #[serde(rename = "_id", with = "rust_mongodb_model_methods::id_type::uuid_as_binary")]
+//     id: uuid::Uuid,
+#[cfg(feature = "uuid_as_id")]
+pub use bson::serde_helpers::uuid_1_as_binary as uuid_as_binary;
@@ -0,0 +1,91 @@
+// STRING IDS ======================================================================================================
+// Generators for `string_as_id` models. ULID and KSUID start with a timestamp, so their string order follows
+// creation time and they work as keyset pagination cursors; nanoid is purely random.
+//
+//     let id = ids::Ulid::generate();      // 01J5ZK3V8Q4T6X9B2C7D1E0F3G
+//     let id = ids::Ksuid::generate();     // 2jAq8YhVvN1cV5JtQ2k4mR7sZpX
+//     let id = ids::NanoId::generate();    // V1StGXR8_Z5jdHi6B-myT
+
+use rand::Rng;
+
+pub trait IdGenerator {
+    fn generate() -> String;
+}
+
+// ULID ============================================================================================================
+// 48-bit millisecond timestamp + 80 random bits, Crockford base32. Monotonic within a process: IDs generated in
+// the same millisecond increment the random part instead of drawing a new one.
+pub struct Ulid;
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+static LAST_ULID: std::sync::Mutex<(u64, u128)> = std::sync::Mutex::new((0, 0));
+
+impl IdGenerator for Ulid {
+    fn generate() -> String {
+        let now = unix_time().as_millis() as u64 & 0xFFFF_FFFF_FFFF;
+
+        let random = {
+            let mut last = LAST_ULID.lock().unwrap_or_else(|x| x.into_inner());
+            let random = match last.0 == now {
+                true => (last.1 + 1) & ((1 << 80) - 1),
+                false => rand::thread_rng().gen::<u128>() & ((1 << 80) - 1),
+            };
+            *last = (now, random);
+            random
+        };
+
+        let value = ((now as u128) << 80) | random;
+        (0..26).rev().map(|i| CROCKFORD[((value >> (i * 5)) & 0x1F) as usize] as char).collect()
+    }
+}
+
+// KSUID ===========================================================================================================
+// 32-bit seconds since the KSUID epoch (2014-05-13) + 128 random bits, base62, always 27 characters
+pub struct Ksuid;
+
+const KSUID_EPOCH: u64 = 1_400_000_000;
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+impl IdGenerator for Ksuid {
+    fn generate() -> String {
+        let timestamp = (unix_time().as_secs().saturating_sub(KSUID_EPOCH) as u32).to_be_bytes();
+        let payload: [u8; 16] = rand::thread_rng().gen();
+
+        let mut bytes = [0u8; 20];
+        bytes[..4].copy_from_slice(&timestamp);
+        bytes[4..].copy_from_slice(&payload);
+
+        // Big-endian base conversion of the 160-bit number, left-padded with zeros
+        let mut digits = Vec::with_capacity(27);
+        let mut number = bytes.to_vec();
+        while number.iter().any(|x| *x != 0) {
+            let mut remainder = 0u32;
+            for byte in number.iter_mut() {
+                let value = (remainder << 8) | *byte as u32;
+                *byte = (value / 62) as u8;
+                remainder = value % 62;
+            }
+            digits.push(BASE62[remainder as usize]);
+        }
+        digits.resize(27, b'0');
+        digits.iter().rev().map(|x| *x as char).collect()
+    }
+}
+
+// NANOID ==========================================================================================================
+// 21 URL-safe characters, ~126 random bits
+pub struct NanoId;
+
+const NANOID_ALPHABET: &[u8; 64] = b"_-0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+impl IdGenerator for NanoId {
+    fn generate() -> String {
+        let mut rng = rand::thread_rng();
+        (0..21).map(|_| NANOID_ALPHABET[rng.gen_range(0..64)] as char).collect()
+    }
+}
+
+fn unix_time() -> std::time::Duration {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default()
+}
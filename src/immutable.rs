@@ -0,0 +1,160 @@
+// IMMUTABLE FIELDS ================================================================================================
+// Fields set once at creation and never changed afterwards:
+//
+//     fn immutable_fields() -> &'static [&'static str] {
+//         &["created_at", "owner_id"]
+//     }
+//
+// Updates writing one of them fail with `Error::ImmutableField` before reaching the database, whatever the
+// operator (`$set`, `$unset`, `$inc`, `$rename` from or to it, ...), also through a parent (`owner` when
+// `owner.id` is immutable) or a child path. Pipelines are checked on their `$set`, `$addFields` and `$unset`
+// stages, one replacing the whole document (`$replaceWith`, `$replaceRoot`, `$project`) is rejected. `upsert_many`
+// writes them with `$setOnInsert` instead, so they apply to the created documents only.
+//
+// `find_one_and_replace` compares the replacement with the document it matches first and only replaces that one,
+// leaving an immutable field out of the replacement counts as changing it.
+
+use crate::Error;
+
+// The first immutable field `update` would write
+pub(crate) fn check(immutable: &[&str], update: &mongodb::options::UpdateModifications) -> Result<(), Error> {
+    let Some(first) = immutable.first() else {
+        return Ok(());
+    };
+    let paths = match update {
+        mongodb::options::UpdateModifications::Document(x) => operator_paths(x),
+        mongodb::options::UpdateModifications::Pipeline(x) => match pipeline_paths(x) {
+            Some(x) => x,
+            None => return Err(Error::ImmutableField(first.to_string())),
+        },
+        _ => Vec::new(),
+    };
+    for path in paths {
+        if let Some(field) = immutable.iter().find(|x| overlaps(x, &path)) {
+            return Err(Error::ImmutableField(field.to_string()));
+        }
+    }
+    Ok(())
+}
+
+// The first immutable field `replacement` doesn't carry over unchanged from `current`
+pub(crate) fn check_replacement(
+    immutable: &[&str],
+    current: &bson::Document,
+    replacement: &bson::Document,
+) -> Result<(), Error> {
+    match immutable.iter().find(|x| crate::path_value(current, x) != crate::path_value(replacement, x)) {
+        Some(field) => Err(Error::ImmutableField(field.to_string())),
+        None => Ok(()),
+    }
+}
+
+// Writing one of `a` and `b` writes the other
+pub(crate) fn overlaps(a: &str, b: &str) -> bool {
+    let nested = |parent: &str, child: &str| child.strip_prefix(parent).is_some_and(|x| x.starts_with('.'));
+    a == b || nested(a, b) || nested(b, a)
+}
+
+fn operator_paths(update: &bson::Document) -> Vec<String> {
+    let mut paths = Vec::new();
+    for (operator, fields) in update {
+        let Some(fields) = fields.as_document() else {
+            continue;
+        };
+        for (field, value) in fields {
+            paths.push(field.clone());
+            if operator == "$rename" {
+                paths.extend(value.as_str().map(str::to_string));
+            }
+        }
+    }
+    paths
+}
+
+// `None` when a stage may replace the whole document
+fn pipeline_paths(pipeline: &[bson::Document]) -> Option<Vec<String>> {
+    let mut paths = Vec::new();
+    for stage in pipeline {
+        for (name, value) in stage {
+            match (name.as_str(), value) {
+                ("$set" | "$addFields", bson::Bson::Document(x)) => paths.extend(x.keys().cloned()),
+                ("$unset", bson::Bson::String(x)) => paths.push(x.clone()),
+                ("$unset", bson::Bson::Array(x)) => {
+                    paths.extend(x.iter().filter_map(|x| x.as_str()).map(str::to_string));
+                }
+                _ => return None,
+            }
+        }
+    }
+    Some(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+    use mongodb::options::UpdateModifications;
+
+    const IMMUTABLE: &[&str] = &["created_at", "owner.id"];
+
+    fn rejected(update: UpdateModifications) -> Option<String> {
+        match check(IMMUTABLE, &update) {
+            Err(Error::ImmutableField(x)) => Some(x),
+            Err(x) => panic!("unexpected error {}", x),
+            Ok(()) => None,
+        }
+    }
+
+    #[test]
+    fn overlaps_parents_and_children_only() {
+        assert!(overlaps("owner.id", "owner.id"));
+        assert!(overlaps("owner.id", "owner"));
+        assert!(overlaps("owner", "owner.id"));
+        assert!(overlaps("owner.id", "owner.id.kind"));
+        assert!(!overlaps("owner.id", "owner.identity"));
+        assert!(!overlaps("owner.id", "owner.name"));
+        assert!(!overlaps("owner", "owners"));
+    }
+
+    #[test]
+    fn rejects_operators_writing_an_immutable_path() {
+        let update = |x| rejected(UpdateModifications::Document(x));
+        assert_eq!(update(doc! { "$set": { "name": "x", "owner": { "id": 2 } } }), Some("owner.id".to_string()));
+        assert_eq!(update(doc! { "$unset": { "owner.id.kind": "" } }), Some("owner.id".to_string()));
+        assert_eq!(update(doc! { "$currentDate": { "created_at": true } }), Some("created_at".to_string()));
+        assert_eq!(update(doc! { "$set": { "owner.name": "x" }, "$inc": { "visits": 1 } }), None);
+    }
+
+    #[test]
+    fn rejects_a_rename_from_or_to_an_immutable_path() {
+        let rename = |from: &str, to: &str| rejected(UpdateModifications::Document(doc! { "$rename": { from: to } }));
+        assert_eq!(rename("created_at", "created"), Some("created_at".to_string()));
+        assert_eq!(rename("legacy_created", "created_at"), Some("created_at".to_string()));
+        assert_eq!(rename("old_owner", "owner"), Some("owner.id".to_string()));
+        assert_eq!(rename("nick", "name"), None);
+    }
+
+    #[test]
+    fn checks_pipeline_stages_and_rejects_replacing_ones() {
+        let pipeline = |x: Vec<bson::Document>| rejected(UpdateModifications::Pipeline(x));
+        assert_eq!(pipeline(vec![doc! { "$set": { "name": "x" } }, doc! { "$unset": ["tmp", "flag"] }]), None);
+        assert_eq!(pipeline(vec![doc! { "$addFields": { "owner": "$$REMOVE" } }]), Some("owner.id".to_string()));
+        assert_eq!(pipeline(vec![doc! { "$unset": "created_at" }]), Some("created_at".to_string()));
+        let replace = doc! { "$replaceWith": { "$mergeObjects": ["$$ROOT", { "name": "x" }] } };
+        assert_eq!(pipeline(vec![doc! { "$set": { "name": "x" } }, replace]), Some("created_at".to_string()));
+        assert_eq!(pipeline(vec![doc! { "$project": { "name": 1 } }]), Some("created_at".to_string()));
+        assert!(check(&[], &UpdateModifications::Pipeline(vec![doc! { "$replaceRoot": {} }])).is_ok());
+    }
+
+    #[test]
+    fn replacement_has_to_carry_immutable_fields_over() {
+        let current = doc! { "created_at": 1, "owner": { "id": 7, "name": "a" }, "name": "x" };
+        let replaced = |x| match check_replacement(IMMUTABLE, &current, &x) {
+            Err(Error::ImmutableField(x)) => Some(x),
+            _ => None,
+        };
+        assert_eq!(replaced(doc! { "created_at": 1, "owner": { "id": 7, "name": "b" }, "name": "y" }), None);
+        assert_eq!(replaced(doc! { "owner": { "id": 7 } }), Some("created_at".to_string()));
+        assert_eq!(replaced(doc! { "created_at": 1, "owner": { "id": 8 } }), Some("owner.id".to_string()));
+    }
+}
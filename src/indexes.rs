@@ -0,0 +1,318 @@
+// INDEXES =========================================================================================================
+// Models declare their indexes by overriding `indexes()`, `ensure_indexes()` creates them:
+//
+//     fn indexes() -> Vec<Index> {
+//         vec![
+//             Index::asc("email").unique().where_null("deleted_at"),
+//             Index::desc("created_at").then_asc("status"),
+//             Index::geo_2dsphere("location"),
+//             Index::hashed("tenant_id"),
+//             Index::wildcard("metadata"),
+//         ]
+//     }
+
+// Strength 2 compares base letters and accents but ignores case, so "Bob@x.io" and "bob@x.io" are equal
+pub fn case_insensitive() -> mongodb::options::Collation {
+    mongodb::options::Collation::builder()
+        .locale("en")
+        .strength(mongodb::options::CollationStrength::Secondary)
+        .build()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexKind {
+    Asc,
+    Desc,
+    Text,
+    // GeoJSON geometries on an earth-like sphere, required by `geo_near` with `Point`s
+    Geo2dSphere,
+    // Legacy coordinate pairs on a flat plane
+    Geo2d,
+    // Hash of the value, for hashed shard keys; equality lookups only
+    Hashed,
+    // Every field below a path, the path itself ends with `$**`
+    Wildcard,
+}
+
+impl IndexKind {
+    pub fn to_bson(&self) -> bson::Bson {
+        match self {
+            Self::Asc => bson::Bson::Int32(1),
+            Self::Desc => bson::Bson::Int32(-1),
+            Self::Text => bson::Bson::String("text".to_string()),
+            Self::Geo2dSphere => bson::Bson::String("2dsphere".to_string()),
+            Self::Geo2d => bson::Bson::String("2d".to_string()),
+            Self::Hashed => bson::Bson::String("hashed".to_string()),
+            Self::Wildcard => bson::Bson::Int32(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    pub keys: Vec<(String, IndexKind)>,
+    pub options: mongodb::options::IndexOptions,
+}
+
+impl Index {
+    pub fn new(field: &str, kind: IndexKind) -> Self {
+        Self { keys: vec![(field.to_string(), kind)], options: Default::default() }
+    }
+
+    pub fn asc(field: &str) -> Self {
+        Self::new(field, IndexKind::Asc)
+    }
+    pub fn desc(field: &str) -> Self {
+        Self::new(field, IndexKind::Desc)
+    }
+    pub fn text(field: &str) -> Self {
+        Self::new(field, IndexKind::Text)
+    }
+    pub fn geo_2dsphere(field: &str) -> Self {
+        Self::new(field, IndexKind::Geo2dSphere)
+    }
+    pub fn geo_2d(field: &str) -> Self {
+        Self::new(field, IndexKind::Geo2d)
+    }
+    pub fn hashed(field: &str) -> Self {
+        Self::new(field, IndexKind::Hashed)
+    }
+
+    // Indexes every field of a subdocument, e.g. `wildcard("metadata")` covers `metadata.$**`
+    pub fn wildcard(field: &str) -> Self {
+        Self::new(&format!("{}.$**", field), IndexKind::Wildcard)
+    }
+
+    // Indexes every field of the document, narrow it down with `wildcard_projection`
+    pub fn wildcard_all() -> Self {
+        Self::new("$**", IndexKind::Wildcard)
+    }
+
+    // Compound indexes: keys are kept in declaration order
+    pub fn then(mut self, field: &str, kind: IndexKind) -> Self {
+        self.keys.push((field.to_string(), kind));
+        self
+    }
+    pub fn then_asc(self, field: &str) -> Self {
+        self.then(field, IndexKind::Asc)
+    }
+    pub fn then_desc(self, field: &str) -> Self {
+        self.then(field, IndexKind::Desc)
+    }
+
+    // Unique regardless of case. Queries only use this index when they run with the same collation, so
+    // return `case_insensitive()` from the model's `collation()` as well.
+    pub fn unique_case_insensitive(field: &str) -> Self {
+        Self::asc(field).unique().collation(case_insensitive())
+    }
+
+    pub fn collation(mut self, collation: mongodb::options::Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.options.name = Some(name.to_string());
+        self
+    }
+    pub fn unique(mut self) -> Self {
+        self.options.unique = Some(true);
+        self
+    }
+    pub fn sparse(mut self) -> Self {
+        self.options.sparse = Some(true);
+        self
+    }
+    pub fn expire_after(mut self, duration: std::time::Duration) -> Self {
+        self.options.expire_after = Some(duration);
+        self
+    }
+
+    // Only documents matching `filter` are indexed, e.g. unique emails among documents that aren't soft
+    // deleted. Queries must include the same condition for the planner to pick the index.
+    pub fn partial(mut self, filter: bson::Document) -> Self {
+        self.options.partial_filter_expression = Some(filter);
+        self
+    }
+
+    // Partial expressions can't use `$exists: false` or `null` equality, `$type: "null"` is the supported form.
+    // It only matches fields stored as an explicit null, so don't skip serializing `None` for `field`.
+    pub fn where_null(self, field: &str) -> Self {
+        self.partial(bson::doc! { field: { "$type": "null" } })
+    }
+
+    // Only valid with `wildcard_all()`: `{ "field": 1 }` to include or `{ "field": 0 }` to exclude paths
+    pub fn wildcard_projection(mut self, projection: bson::Document) -> Self {
+        self.options.wildcard_projection = Some(projection);
+        self
+    }
+
+    // Coordinate range of a 2d index, the server defaults to [-180, 180)
+    pub fn bounds(mut self, min: f64, max: f64) -> Self {
+        self.options.min = Some(min);
+        self.options.max = Some(max);
+        self
+    }
+
+    pub fn keys_document(&self) -> bson::Document {
+        self.keys.iter().map(|(field, kind)| (field.clone(), kind.to_bson())).collect()
+    }
+
+    pub fn to_model(&self) -> mongodb::IndexModel {
+        mongodb::IndexModel::builder().keys(self.keys_document()).options(self.options.clone()).build()
+    }
+}
+
+// DRIFT ===========================================================================================================
+// Declared and server indexes are matched by their key document, options are compared only where they matter
+// for query results or constraints. An index without a declared name may carry any name on the server.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionMismatch {
+    pub option: String,
+    pub declared: bson::Bson,
+    pub actual: bson::Bson,
+}
+
+#[derive(Debug, Clone)]
+pub struct MismatchedIndex {
+    pub name: String,
+    pub keys: bson::Document,
+    pub differences: Vec<OptionMismatch>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexDrift {
+    // Declared but not present on the server
+    pub missing: Vec<Index>,
+    // Present on the server but not declared (the `_id` index is never reported)
+    pub extra: Vec<String>,
+    pub mismatched: Vec<MismatchedIndex>,
+}
+
+impl IndexDrift {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+
+    pub fn compare(declared: &[Index], existing: &[mongodb::IndexModel]) -> Self {
+        let mut drift = Self::default();
+        let mut matched = vec![false; existing.len()];
+
+        for index in declared {
+            let keys = index.keys_document();
+            let found = existing.iter().position(|x| same_keys(&x.keys, &keys));
+            let Some(position) = found else {
+                drift.missing.push(index.clone());
+                continue;
+            };
+            matched[position] = true;
+
+            let actual = &existing[position];
+            let differences = compare_options(index, actual);
+            if !differences.is_empty() {
+                drift.mismatched.push(MismatchedIndex { name: index_name(actual), keys, differences });
+            }
+        }
+
+        for (position, index) in existing.iter().enumerate() {
+            let name = index_name(index);
+            if !matched[position] && name != "_id_" {
+                drift.extra.push(name);
+            }
+        }
+
+        drift
+    }
+}
+
+const COMPARED_OPTIONS: [&str; 7] =
+    ["name", "unique", "sparse", "expireAfterSeconds", "partialFilterExpression", "collation", "wildcardProjection"];
+
+fn index_name(index: &mongodb::IndexModel) -> String {
+    index.options.as_ref().and_then(|x| x.name.clone()).unwrap_or_default()
+}
+
+fn compare_options(declared: &Index, actual: &mongodb::IndexModel) -> Vec<OptionMismatch> {
+    let declared_options = bson::to_document(&declared.options).unwrap_or_default();
+    let actual_options = actual.options.as_ref().and_then(|x| bson::to_document(x).ok()).unwrap_or_default();
+
+    let mut differences = Vec::new();
+    for option in COMPARED_OPTIONS {
+        let declared_value = declared_options.get(option).cloned().unwrap_or(bson::Bson::Null);
+        let actual_value = actual_options.get(option).cloned().unwrap_or(bson::Bson::Null);
+
+        let same = match option {
+            // Only a declared name is binding, the server always assigns one
+            "name" => declared_value == bson::Bson::Null || declared_value == actual_value,
+            "unique" | "sparse" => declared_value.as_bool().unwrap_or(false) == actual_value.as_bool().unwrap_or(false),
+            // The server fills in every collation default, only the declared fields are compared
+            "collation" => match (&declared_value, &actual_value) {
+                (bson::Bson::Document(declared), bson::Bson::Document(actual)) => {
+                    declared.iter().all(|(key, value)| actual.get(key).is_some_and(|x| same_value(value, x)))
+                }
+                _ => same_value(&declared_value, &actual_value),
+            },
+            _ => same_value(&declared_value, &actual_value),
+        };
+
+        if !same {
+            differences.push(OptionMismatch { option: option.to_string(), declared: declared_value, actual: actual_value });
+        }
+    }
+    differences
+}
+
+// Key documents in order, `{ a: 1.0 }` is `{ a: 1 }`
+fn same_keys(a: &bson::Document, b: &bson::Document) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|((a, x), (b, y))| a == b && same_value(x, y))
+}
+
+// Numbers compare by value since the server may return Int32 for what was sent as Int64
+fn same_value(a: &bson::Bson, b: &bson::Bson) -> bool {
+    match (a, b) {
+        (bson::Bson::Document(a), bson::Bson::Document(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_some_and(|x| same_value(value, x)))
+        }
+        (bson::Bson::Array(a), bson::Bson::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        },
+    }
+}
+
+fn as_number(value: &bson::Bson) -> Option<f64> {
+    match value {
+        bson::Bson::Int32(x) => Some(*x as f64),
+        bson::Bson::Int64(x) => Some(*x as f64),
+        bson::Bson::Double(x) => Some(*x),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_matches_keys_by_value_in_order() {
+        let existing = |keys: bson::Document, name: &str| {
+            let options = mongodb::options::IndexOptions::builder().name(name.to_string()).build();
+            mongodb::IndexModel::builder().keys(keys).options(options).build()
+        };
+        let declared = [Index::asc("a"), Index::asc("b").then_asc("c")];
+
+        let drift = IndexDrift::compare(
+            &declared,
+            &[existing(bson::doc! { "a": 1.0 }, "a_1"), existing(bson::doc! { "b": 1_i64, "c": 1 }, "b_1_c_1")],
+        );
+        assert!(drift.is_clean(), "{:?}", drift);
+
+        let drift = IndexDrift::compare(&declared, &[existing(bson::doc! { "c": 1, "b": 1 }, "c_1_b_1")]);
+        assert_eq!(drift.missing.len(), 2);
+        assert_eq!(drift.extra, ["c_1_b_1"]);
+    }
+}
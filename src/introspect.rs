@@ -0,0 +1,188 @@
+// SCHEMA REPORT ===================================================================================================
+// `schema_report()` describes every registered model as data: its fields with their declared and stored BSON
+// types, its indexes, relations and field rules. For generated docs, or to audit the production data:
+//
+//     let report = rms::schema_report_with(500).await?;
+//     std::fs::write("schema.json", serde_json::to_string_pretty(&report)?)?;
+//
+//     for model in &report.models {
+//         for field in model.fields.iter().filter(|x| !x.in_model) {
+//             println!("{}.{} is stored but not in the struct", model.collection, field.name);
+//         }
+//     }
+//
+// Struct fields come from the model's `Deserialize` (serialized names, `#[serde(flatten)]` or a hand-written
+// impl leaves them out), declared types from `json_schema()`. With a sample, `observed` counts the BSON types of
+// each top-level field over that many random documents, `present` how many of them had it at all.
+
+use std::collections::BTreeMap;
+
+use futures::TryStreamExt;
+
+use crate::{error, projection, registry, Error, ErrorContext, Index, RustMongoDBModelMethods};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SchemaReport {
+    pub models: Vec<ModelSchema>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ModelSchema {
+    pub model: &'static str,
+    pub collection: String,
+    pub database: String,
+    pub logical_database: &'static str,
+    // Struct fields first, in declaration order, then the ones found only in the schema or the sample
+    pub fields: Vec<FieldSchema>,
+    pub indexes: Vec<IndexSchema>,
+    pub references: Vec<ReferenceSchema>,
+    pub unique: Vec<Vec<String>>,
+    pub immutable: Vec<String>,
+    pub sensitive: Vec<String>,
+    pub shard_key: Vec<String>,
+    // Documents the `observed` counts are over
+    pub sampled: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct FieldSchema {
+    pub name: String,
+    pub in_model: bool,
+    // `bsonType` of its `json_schema()` property
+    pub declared: Vec<String>,
+    // In the `required` of `json_schema()`
+    pub required: bool,
+    pub present: u64,
+    // BSON type names, e.g. `"string"` or `"objectId"`, and how many sampled documents had each
+    pub observed: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct IndexSchema {
+    pub name: Option<String>,
+    pub keys: bson::Document,
+    pub unique: bool,
+    pub sparse: bool,
+    pub expire_after_secs: Option<u64>,
+    pub partial_filter: Option<bson::Document>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ReferenceSchema {
+    pub field: String,
+    pub collection: String,
+}
+
+impl From<&Index> for IndexSchema {
+    fn from(index: &Index) -> Self {
+        Self {
+            name: index.options.name.clone(),
+            keys: index.keys_document(),
+            unique: index.options.unique.unwrap_or(false),
+            sparse: index.options.sparse.unwrap_or(false),
+            expire_after_secs: index.options.expire_after.map(|x| x.as_secs()),
+            partial_filter: index.options.partial_filter_expression.clone(),
+        }
+    }
+}
+
+pub async fn schema_report() -> Result<SchemaReport, Error> {
+    schema_report_with(0).await
+}
+
+// With the types of `sample` random documents per collection
+pub async fn schema_report_with(sample: u32) -> Result<SchemaReport, Error> {
+    let mut models = Vec::new();
+    for model in registry::models() {
+        let mut schema = model.schema();
+        if sample > 0 {
+            let (sampled, fields) = observe(&model.collection(), sample).await?;
+            schema.sampled = sampled;
+            for (name, observed) in fields {
+                let present = observed.values().sum();
+                match schema.fields.iter_mut().find(|x| x.name == name) {
+                    Some(field) => (field.present, field.observed) = (present, observed),
+                    None => schema.fields.push(FieldSchema { name, present, observed, ..Default::default() }),
+                }
+            }
+        }
+        models.push(schema);
+    }
+    Ok(SchemaReport { models })
+}
+
+// What the model declares, without looking at the data
+pub(crate) fn declared<M, E>() -> ModelSchema
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let collection = M::collection();
+    let strings = |x: &[&str]| -> Vec<String> { x.iter().map(|x| x.to_string()).collect() };
+
+    let mut fields: Vec<FieldSchema> = projection::fields_of::<M>()
+        .unwrap_or_default()
+        .iter()
+        .map(|x| FieldSchema { name: x.to_string(), in_model: true, ..Default::default() })
+        .collect();
+    let schema = M::json_schema().unwrap_or_default();
+    let properties = schema.get_document("properties").cloned().unwrap_or_default();
+    for (name, property) in &properties {
+        let declared = match property.as_document().and_then(|x| x.get("bsonType")) {
+            Some(bson::Bson::String(x)) => vec![x.clone()],
+            Some(bson::Bson::Array(x)) => x.iter().filter_map(|x| x.as_str()).map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+        match fields.iter_mut().find(|x| &x.name == name) {
+            Some(field) => field.declared = declared,
+            None => fields.push(FieldSchema { name: name.clone(), declared, ..Default::default() }),
+        }
+    }
+    let required = schema.get_array("required").map(|x| x.as_slice()).unwrap_or_default();
+    for name in required.iter().filter_map(|x| x.as_str()) {
+        match fields.iter_mut().find(|x| x.name == name) {
+            Some(field) => field.required = true,
+            None => fields.push(FieldSchema { name: name.to_string(), required: true, ..Default::default() }),
+        }
+    }
+
+    let unique = crate::unique::groups(M::unique_fields(), M::unique_together());
+    ModelSchema {
+        model: error::model_name::<M>(),
+        collection: collection.name().to_string(),
+        database: collection.namespace().db,
+        logical_database: M::logical_database(),
+        fields,
+        indexes: crate::declared_indexes::<M, E>().iter().map(IndexSchema::from).collect(),
+        references: M::references()
+            .into_iter()
+            .map(|x| ReferenceSchema { field: x.field, collection: x.collection })
+            .collect(),
+        unique: unique.into_iter().map(strings).collect(),
+        immutable: strings(M::immutable_fields()),
+        sensitive: strings(M::sensitive_fields()),
+        shard_key: strings(M::shard_key()),
+        sampled: 0,
+    }
+}
+
+// BSON types of the top-level fields of `size` random documents, with how many documents had each
+pub(crate) async fn observe(
+    collection: &mongodb::Collection<bson::Document>,
+    size: u32,
+) -> Result<(u64, BTreeMap<String, BTreeMap<String, u64>>), Error> {
+    let context = ErrorContext::new(collection.name(), "observe");
+    let pipeline = vec![bson::doc! { "$sample": { "size": i64::from(size) } }];
+    let mut cursor = collection.aggregate(pipeline, None).await.map_err(|x| context.wrap(x))?;
+
+    let mut sampled = 0;
+    let mut fields: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+    while let Some(document) = cursor.try_next().await.map_err(|x| context.wrap(x))? {
+        sampled += 1;
+        for (name, value) in &document {
+            let types = fields.entry(name.clone()).or_default();
+            *types.entry(error::type_name(value).to_string()).or_default() += 1;
+        }
+    }
+    Ok((sampled, fields))
+}
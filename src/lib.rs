@@ -9,27 +9,346 @@
  * cargo add async-trait futures mongodb serde bson
 */
 
+pub mod archive;
+pub mod array;
+pub mod breaker;
+pub mod buffer;
+pub mod cancel;
+pub mod codegen;
+pub mod config;
+pub mod connection;
+pub mod cursor;
+pub mod datetime;
+#[cfg(feature = "decimal")]
+pub mod decimal;
+pub mod derived;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod enums;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod field;
+pub mod filter;
+pub mod geo;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod group;
+#[cfg(feature = "password")]
+pub mod hashed;
+pub mod health;
+pub mod id_type;
+#[cfg(feature = "string_as_id")]
+pub mod ids;
+pub mod immutable;
+pub mod indexes;
+pub mod introspect;
+pub mod map;
+pub mod materialize;
+pub mod mirror;
+#[cfg(feature = "utoipa")]
+pub mod openapi;
+pub mod page;
+pub mod params;
+pub mod prelude;
+pub mod projection;
+pub mod query_log;
+pub mod read;
+pub mod read_only;
+pub mod redact;
+pub mod registry;
+pub mod relations;
+pub mod scheduled;
+pub mod schema;
+#[cfg(feature = "atlas_search")]
+pub mod search;
+pub mod session;
+pub mod shard;
+pub mod startup;
+#[cfg(feature = "sync")]
+pub mod sync;
+pub mod typed_id;
+pub mod unique;
+pub mod watch;
+#[cfg(feature = "axum")]
+pub mod web;
+pub mod write;
+
+pub use archive::ArchiveProgress;
+pub use array::Push;
+pub use breaker::CircuitBreaker;
+pub use buffer::{BufferedWriter, FlushSummary};
+pub use codegen::generate_model;
+pub use bson;
+pub use config::Config;
+pub use connection::{
+    client, client_of, connect, database, database_of, init, init_router, pool_stats, PoolConfig, Router,
+};
+pub use derived::DerivedFields;
+pub use error::{Error, ErrorContext};
+pub use field::Field;
+pub use filter::FilterExpr;
+pub use group::{Accumulator, Duplicate, GroupBy};
+#[cfg(feature = "password")]
+pub use hashed::Hashed;
+pub use health::{health, warmup, warmup_of};
+pub use id_type::IdType;
+pub use indexes::{Index, IndexDrift, IndexKind};
+pub use introspect::{schema_report, schema_report_with, ModelSchema, SchemaReport};
+pub use materialize::{Merge, Output, WhenMatched, WhenNotMatched};
+pub use mirror::Mirror;
+pub use page::{FacetBucket, FacetedResults, Page, PageOptions};
+pub use params::{Direction, ListParams, ListRules};
+pub use query_log::{Masking, QueryLog};
+pub use registry::{register, register_as, ModelStats, RegisteredModel};
+pub use relations::{BelongsTo, Orphan, Reference};
+pub use schema::{SchemaProblem, SchemaViolation};
+pub use startup::{verify_startup, verify_startup_with, ModelReport, StartupOptions, StartupReport};
+pub use typed_id::{Id, IdOf};
+pub use unique::{FieldError, ValidationErrors};
+pub use write::{BackfillOptions, BackfillProgress, UpdateSummary, UpsertStatus};
 
 use futures::TryStreamExt;
 
-#[derive(Debug)]
-pub enum Error {
-    NotFound,
-    DBError(mongodb::error::Error),
-    BSONSerError(bson::ser::Error),
-    CreateFailed(String),
-    UpdateFailed(String),
-    DeleteFailed(String),
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(feature = "backup")]
+const BACKUP_FORMAT: &str = "rust_mongodb_model_methods/backup/v1";
+
+
+fn as_u64(value: &bson::Bson) -> Option<u64> {
+    match value {
+        bson::Bson::Int32(x) => Some(*x as u64),
+        bson::Bson::Int64(x) => Some(*x as u64),
+        bson::Bson::Double(x) => Some(*x as u64),
+        _ => None,
+    }
+}
+
+// Value at a dotted `path` of `document`, also when `document` is a `$set` with the path as a key
+fn path_value<'a>(document: &'a bson::Document, path: &str) -> Option<&'a bson::Bson> {
+    if let Some(x) = document.get(path) {
+        return Some(x);
+    }
+    let (head, rest) = path.split_once('.')?;
+    path_value(document.get_document(head).ok()?, rest)
+}
+
+// `max_of` / `min_of` pipeline, a single group over all matches
+fn extremum(operator: &str, field: &str, filter: bson::Document) -> Vec<bson::Document> {
+    vec![
+        bson::doc! { "$match": filter },
+        bson::doc! { "$group": { "_id": bson::Bson::Null, "value": { operator: format!("${}", field) } } },
+    ]
+}
+
+fn extremum_value(mut result: Vec<bson::Document>) -> bson::Bson {
+    result.pop().and_then(|mut x| x.remove("value")).unwrap_or(bson::Bson::Null)
+}
+
+// Reads right after a write go to the primary, a model reading from secondaries could miss the write otherwise
+fn read_back() -> mongodb::options::FindOneOptions {
+    let primary = mongodb::options::SelectionCriteria::ReadPreference(mongodb::options::ReadPreference::Primary);
+    mongodb::options::FindOneOptions::builder().selection_criteria(primary).build()
+}
+
+// `relations::check` against the references `M` declares, free when it declares none
+async fn check_references<M, E>(document: &bson::Document) -> Result<(), Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let references = M::references();
+    if references.is_empty() {
+        return Ok(());
+    }
+    let collection = M::collection();
+    let database = collection.client().database(&collection.namespace().db);
+    relations::check(&database, &references, document, M::circuit_breaker()).await
+}
+
+// `check_references` of each of `documents`, one lookup per reference for all of them
+async fn check_references_many<M, E>(documents: &[&bson::Document]) -> Result<Vec<Option<Error>>, Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let references = M::references();
+    if references.is_empty() {
+        return Ok(documents.iter().map(|_| None).collect());
+    }
+    let collection = M::collection();
+    let database = collection.client().database(&collection.namespace().db);
+    relations::check_many(&database, &references, documents, M::circuit_breaker()).await
+}
+
+// Fails with `Error::ReadOnly` while `M` is read-only, first thing in every write method
+fn writable<M, E>() -> Result<(), Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    match M::read_only() {
+        true => Err(Error::ReadOnly),
+        false => Ok(()),
+    }
+}
+
+// `collection()` of `M` with its `write_concern()`, for write methods
+fn writes<M, E>() -> mongodb::Collection<M>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    write::with_write_concern(M::collection(), M::write_concern())
+}
+
+// Fails with `Error::Validation` when `document` sets one of the `unique_fields` or `unique_together` of `M` to
+// values of a document outside `exclude`, free when it declares none
+async fn check_unique<M, E>(document: &bson::Document, exclude: bson::Document) -> Result<(), Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let groups = unique::groups(M::unique_fields(), M::unique_together());
+    if !groups.iter().any(|x| unique::values(x, document).is_some()) {
+        return Ok(());
+    }
+    let collection = M::collection().clone_with_type::<bson::Document>();
+    let errors = unique::conflicts(&collection, &groups, document, &exclude, M::collation(), M::circuit_breaker());
+    match errors.await? {
+        x if x.is_empty() => Ok(()),
+        x => Err(Error::Validation(x)),
+    }
+}
+
+// `check_unique` of each `(document, exclude)` of `items`, batched by `unique::conflicts_many`
+async fn check_unique_many<M, E>(items: &[(&bson::Document, &bson::Document)]) -> Result<Vec<Option<Error>>, Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let groups = unique::groups(M::unique_fields(), M::unique_together());
+    if !items.iter().any(|(x, _)| groups.iter().any(|group| unique::values(group, x).is_some())) {
+        return Ok(items.iter().map(|_| None).collect());
+    }
+    let collection = M::collection().clone_with_type::<bson::Document>();
+    let errors = unique::conflicts_many(&collection, &groups, items, M::collation(), M::circuit_breaker()).await?;
+    Ok(errors.into_iter().map(|x| (!x.is_empty()).then_some(Error::Validation(x))).collect())
+}
+
+// `indexes()` of `M` and the compound unique indexes of its `unique_together`
+fn declared_indexes<M, E>() -> Vec<Index>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let mut indexes = M::indexes();
+    indexes.extend(unique::indexes(M::unique_together(), &indexes));
+    indexes
+}
+
+// `Error::Validation` for a duplicate key error on the unique index of a `unique_fields` field or `unique_together`
+// combination of `M`, `error` for any other
+fn duplicate_key<M, E>(error: Error) -> Error
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let groups = unique::groups(M::unique_fields(), M::unique_together());
+    unique::duplicate_key(error, &groups, &declared_indexes::<M, E>())
+}
+
+// `mirror::propagate` for the mirrors of `M` that `pick` keeps, free when it has none
+async fn propagate_mirrors<M, E>(item: &M, pick: fn(&Mirror) -> bool) -> Result<u64, Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let mirrors = M::mirrors();
+    if !mirrors.iter().any(pick) {
+        return Ok(0);
+    }
+    propagate_mirrors_of::<M, E>(&bson::to_document(item)?, pick).await
+}
+
+// `propagate_mirrors` from the stored form of the item
+async fn propagate_mirrors_of<M, E>(source: &bson::Document, pick: fn(&Mirror) -> bool) -> Result<u64, Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let mirrors = M::mirrors();
+    if !mirrors.iter().any(pick) {
+        return Ok(0);
+    }
+    writable::<M, E>()?;
+    let collection = M::collection();
+    let database = collection.client().database(&collection.namespace().db);
+    mirror::propagate(&database, mirrors.iter().filter(|x| pick(x)), source, M::circuit_breaker()).await
+}
+
+// `update_one_modified` before `map_error`, for callers looking at the crate's error
+async fn update_set_unmapped<M, E>(filter: bson::Document, set: bson::Bson) -> Result<(M, bool), Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let context = ErrorContext::new(M::collection().name(), "update_one").filter(&filter);
+    writable::<M, E>().map_err(|x| context.wrap(x))?;
+    if let bson::Bson::Document(set) = &set {
+        check_references::<M, E>(set).await.map_err(|x| context.wrap(x))?;
+        check_unique::<M, E>(set, filter.clone()).await.map_err(|x| context.wrap(x))?;
+    }
+    update_one_unmapped::<M, E>(filter, bson::doc! { "$set": set }.into()).await
 }
 
+// `update_one_with` before `map_error`
+async fn update_one_unmapped<M, E>(
+    filter: bson::Document,
+    update: mongodb::options::UpdateModifications,
+) -> Result<(M, bool), Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let collection = writes::<M, E>();
+    let context = ErrorContext::new(collection.name(), "update_one").filter(&filter);
+    writable::<M, E>().map_err(|x| context.wrap(x))?;
+    shard::check_filter(M::shard_key(), &filter, true).map_err(|x| context.wrap(x))?;
+    immutable::check(M::immutable_fields(), &update).map_err(|x| context.wrap(x))?;
+
+    // The document as it was, then read back by its `_id`: the update may change what `filter` selects on
+    let documents = collection.clone_with_type::<bson::Document>();
+    let options = mongodb::options::FindOneAndUpdateOptions::builder()
+        .return_document(mongodb::options::ReturnDocument::Before)
+        .collation(M::collation())
+        .build();
+    let update = documents.find_one_and_update(filter.clone(), update, options);
+    let before = breaker::guard(M::circuit_breaker(), update)
+        .await
+        .map_err(duplicate_key::<M, E>)
+        .map_err(|x| context.wrap(x))?;
+    let not_found = || context.wrap(Error::not_found_filter::<M>(&filter));
+    let before = before.ok_or_else(not_found)?;
 
-#[cfg(feature = "oid_as_id")]
-type IdType = bson::oid::ObjectId;
-#[cfg(feature = "uuid_as_id")]
-type IdType = bson::Uuid;
+    let by_id = bson::doc! { "_id": before.get("_id").cloned().unwrap_or_default() };
+    let after = breaker::guard(M::circuit_breaker(), documents.find_one(by_id, read_back()))
+        .await
+        .map_err(|x| context.wrap(x))?;
+    let after = after.ok_or_else(not_found)?;
+    let modified = after != before;
+    let item: M = bson::from_document(after).map_err(|x| context.wrap(x))?;
+    if modified {
+        propagate_mirrors::<M, E>(&item, |x| !x.deferred).await.map_err(|x| context.wrap(x))?;
+    }
+    Ok((item, modified))
+}
 
+// `E` is the error type every method returns. It defaults to `Error`, so `impl RustMongoDBModelMethods for User`
+// is enough unless the model maps errors into its own type (see `map_error`).
 #[async_trait::async_trait]
-pub trait RustMongoDBModelMethods<E>
+pub trait RustMongoDBModelMethods<E = Error>
 where
     Self: serde::ser::Serialize + serde::de::DeserializeOwned + Send + Sync + Unpin + 'static,
     E: From<Error>,
@@ -39,121 +358,2187 @@ where
     fn collection() -> mongodb::Collection<Self>;
     fn id_value(&self) -> &IdType;
 
+    // Optional: client-side ID for documents created without one (missing, null, nil UUID, zero ObjectId or
+    // empty string `_id`). Knowing the ID before the insert makes retries idempotent and lets related documents
+    // reference it up front. Defaults to a new ObjectId / random UUID / ULID depending on the ID feature.
+    fn generate_id() -> IdType {
+        id_type::generate()
+    }
+
+    // Optional: collation applied by the query helpers. Set it to the collation of a case-insensitive index
+    // (`indexes::case_insensitive()`) so lookups on that field can use the index and match regardless of case.
+    fn collation() -> Option<mongodb::options::Collation> {
+        None
+    }
+
+    // Optional: limits, sortable and filterable fields for `find_list`
+    fn list_rules() -> ListRules {
+        ListRules::default()
+    }
+
+    // Optional: indexes created by `ensure_indexes()`
+    fn indexes() -> Vec<Index> {
+        Vec::new()
+    }
+
+    // Optional: the route of `init_router` that `collection()` is on, see `connection`
+    fn logical_database() -> &'static str {
+        connection::DEFAULT_DATABASE
+    }
+
+    // Optional: read preference and read concern of every `find*`, `aggregate` and `count` call that doesn't
+    // set its own through the `*_with_options` variants, e.g. nearest for reference data. `None` keeps the
+    // collection's settings.
+    fn read_preference() -> Option<mongodb::options::SelectionCriteria> {
+        None
+    }
+
+    fn read_concern() -> Option<mongodb::options::ReadConcern> {
+        None
+    }
+
+    // Optional: documents per batch of the cursors opened by `find*`, `aggregate`, `tail`, exports and backups
+    // that don't set their own. `None` keeps the server's (101 documents, then 16MB per batch).
+    fn batch_size() -> Option<u32> {
+        None
+    }
+
+    // Optional: secondaries used by `find_on_secondary` and `aggregate_on_secondary`, any secondary by default
+    fn analytics() -> read::Analytics {
+        read::Analytics::default()
+    }
+
+    // Optional: breaker the find, aggregate, count, create, update and delete calls go through, see `breaker`. That
+    // includes `tail`, import, export, backup and restore; `ensure_indexes`, `check_indexes` and `watch` go to
+    // the server directly, so do the later batches of a cursor.
+    fn circuit_breaker() -> Option<&'static CircuitBreaker> {
+        None
+    }
+
+    // Optional: `$jsonSchema` the documents should follow, checked against the stored ones by `validate_collection`
+    fn json_schema() -> Option<bson::Document> {
+        None
+    }
+
+    // Optional: foreign keys checked on writes, see `relations`
+    fn references() -> Vec<Reference> {
+        Vec::new()
+    }
+
+    // Optional: copies of this model's fields kept in other collections, see `mirror`
+    fn mirrors() -> Vec<Mirror> {
+        Vec::new()
+    }
+
+    // Optional: fields no two documents may share, checked before writes, see `unique`
+    fn unique_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    // Optional: combinations of fields unique together, e.g. `&[&["tenant_id", "email"]]`, see `unique`
+    fn unique_together() -> &'static [&'static [&'static str]] {
+        &[]
+    }
+
+    // Optional: shard key of the collection, checked for by single-document writes, see `shard`
+    fn shard_key() -> &'static [&'static str] {
+        &[]
+    }
+
+    // Optional: fields updates can't change, see `immutable`
+    fn immutable_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    // Optional: fields `to_public_bson` and `find_redacted` leave out, see `redact`
+    fn sensitive_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    // Optional: personal data masked by `QueryLog::pii_of`, see `query_log`
+    fn pii_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    // Optional: write concern of every write method, `None` keeps the client's, see `write`
+    fn write_concern() -> Option<mongodb::options::WriteConcern> {
+        None
+    }
+
+    // Optional: whether write methods fail with `Error::ReadOnly`, see `read_only`
+    fn read_only() -> bool {
+        read_only::enabled()
+    }
+
+    // Optional: where `archive_older_than` moves documents, `<collection>_archive` of the same database by default
+    fn archive() -> mongodb::Collection<bson::Document> {
+        let collection = Self::collection();
+        let database = collection.client().database(&collection.namespace().db);
+        database.collection(&format!("{}_archive", collection.name()))
+    }
 
+    // Optional: translates crate errors into the model's error type, e.g. `Error::NotFound` into
+    // `AppError::UserNotFound`. Every method returns its errors through here. Defaults to `E::from`.
+    fn map_error(error: Error) -> E {
+        E::from(error)
+    }
 
     // HELPERS =====================================================================================================
     fn search_filter(&self) -> bson::Document {
-        #[cfg(feature = "oid_as_id")]
         bson::doc! { "_id": self.id_value() }
-        #[cfg(feature = "uuid_as_id")]
-        {
-            bson::doc! { "_id": self.id_value() }
+    }
+
+    // `_id` and this document's `shard_key` values, so the queries built on it go to one shard, see `shard`
+    fn scoped_filter(&self) -> Result<bson::Document, E> {
+        let context = ErrorContext::new(Self::collection().name(), "scoped_filter").id(self.id_value().to_owned());
+        let key = Self::shard_key();
+        if key.is_empty() {
+            return Ok(self.search_filter());
         }
+        let document = bson::to_document(self).map_err(context.wrapper(Self::map_error))?;
+        shard::scoped(key, self.search_filter(), &document).map_err(context.wrapper(Self::map_error))
+    }
+
+    // `filter` narrowed down to this document's shard
+    fn scoped(&self, filter: bson::Document) -> Result<bson::Document, E> {
+        let mut scoped = self.scoped_filter()?;
+        scoped.remove("_id");
+        Ok(match (filter.is_empty(), scoped.is_empty()) {
+            (_, true) => filter,
+            (true, false) => scoped,
+            (false, false) => bson::doc! { "$and": [filter, scoped] },
+        })
     }
 
     // FIND ========================================================================================================
     async fn find(filter: bson::Document) -> Result<Vec<Self>, E> {
-        let items = Self::collection()
-            .find(filter, None)
-            .await
-            .map_err(|x| (Error::DBError(x)))?
-            .try_collect::<Vec<Self>>()
+        Self::find_with_options(filter, mongodb::options::FindOptions::default()).await
+    }
+
+    async fn find_with_options(
+        filter: bson::Document,
+        mut options: mongodb::options::FindOptions,
+    ) -> Result<Vec<Self>, E> {
+        if options.collation.is_none() {
+            options.collation = Self::collation();
+        }
+        if options.selection_criteria.is_none() {
+            options.selection_criteria = Self::read_preference();
+        }
+        if options.read_concern.is_none() {
+            options.read_concern = Self::read_concern();
+        }
+        if options.batch_size.is_none() {
+            options.batch_size = Self::batch_size();
+        }
+
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "find").filter(&filter);
+        let cursor = breaker::guard(Self::circuit_breaker(), collection.find(filter, options))
             .await
-            .map_err(|x| (Error::DBError(x)))?;
+            .map_err(context.wrapper(Self::map_error))?;
+        let items = cursor.try_collect::<Vec<Self>>().await.map_err(context.wrapper(Self::map_error))?;
 
         Ok(items)
     }
 
     async fn find_one(filter: bson::Document) -> Result<Option<Self>, E> {
-        let item = Self::collection().find_one(filter, None).await.map_err(|x| (Error::DBError(x)))?;
+        Self::find_one_with_options(filter, mongodb::options::FindOneOptions::default()).await
+    }
+
+    async fn find_one_with_options(
+        filter: bson::Document,
+        mut options: mongodb::options::FindOneOptions,
+    ) -> Result<Option<Self>, E> {
+        if options.collation.is_none() {
+            options.collation = Self::collation();
+        }
+        if options.selection_criteria.is_none() {
+            options.selection_criteria = Self::read_preference();
+        }
+        if options.read_concern.is_none() {
+            options.read_concern = Self::read_concern();
+        }
+
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "find_one").filter(&filter);
+        let item = breaker::guard(Self::circuit_breaker(), collection.find_one(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
         Ok(item)
     }
 
+    // First match in `sort` order, e.g. `doc! { "created_at": -1 }` for the most recent one
+    async fn find_first(filter: bson::Document, sort: bson::Document) -> Result<Option<Self>, E> {
+        let options = mongodb::options::FindOneOptions::builder().sort(sort).build();
+        Self::find_one_with_options(filter, options).await
+    }
+
+    // Document with the highest `field`, best backed by an index on it
+    async fn find_latest(field: &str) -> Result<Option<Self>, E> {
+        Self::find_first(bson::doc! {}, bson::doc! { field: -1 }).await
+    }
+
     async fn find_one_strict(filter: bson::Document) -> Result<Self, E> {
-        let item = Self::find_one(filter).await?.ok_or(Error::NotFound.into())?;
+        let context = ErrorContext::new(Self::collection().name(), "find_one_strict").filter(&filter);
+        let not_found = Error::not_found_filter::<Self>(&filter);
+        let item = Self::find_one(filter).await?.ok_or_else(|| Self::map_error(context.wrap(not_found)))?;
         Ok(item)
     }
 
-    async fn find_by_id(id: &IdType) -> Result<Option<Self>, E> {
-        Self::find_one(bson::doc! { "_id": id}).await
+    async fn find_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<Option<Self>, E> {
+        Self::find_one(bson::doc! { "_id": id.raw_id() }).await
     }
-    async fn find_by_id_strict(id: &IdType) -> Result<Self, E> {
-        println!("🔑 Finding by ID: {:?}", bson::doc! { "_id": id });
-        Self::find_one_strict(bson::doc! { "_id": id }).await
+    async fn find_by_id_strict<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<Self, E> {
+        let context = ErrorContext::new(Self::collection().name(), "find_by_id_strict").id(id.raw_id().to_owned());
+        let not_found = || Self::map_error(context.wrap(Error::not_found_id::<Self>(id.raw_id().to_owned())));
+        Self::find_by_id(id).await?.ok_or_else(not_found)
     }
-    // CREATE ======================================================================================================
-    async fn create_one(data: &Self) -> Result<Self, E> {
-        let collection = Self::collection();
 
-        let insert_result = collection.insert_one(data, None).await.map_err(|x| (Error::DBError(x)))?;
+    // For documents written by another service: polls with backoff (50ms doubling up to 1s) until the document
+    // shows up, or fails with `Error::Timeout` once `timeout` has passed
+    async fn find_by_id_until<I: IdOf<Self> + Sync + ?Sized>(id: &I, timeout: std::time::Duration) -> Result<Self, E> {
+        let context = ErrorContext::new(Self::collection().name(), "find_by_id_until").id(id.raw_id().to_owned());
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut delay = std::time::Duration::from_millis(50);
 
-        #[cfg(feature = "oid_as_id")]
-        let some_id = insert_result.inserted_id.as_object_id();
-        #[cfg(feature = "uuid_as_id")]
-        let some_id: Option<bson::Uuid> = {
-            match insert_result.inserted_id {
-                bson::Bson::Binary(bson::Binary {
-                    subtype: bson::spec::BinarySubtype::Uuid,
-                    bytes,
-                }) => Some(bson::Uuid::from_uuid_1(uuid::Uuid::from_slice(&bytes).unwrap())),
-                _ => None,
+        loop {
+            if let Some(item) = Self::find_by_id(id).await? {
+                return Ok(item);
             }
-        };
 
-        println!("🔑 Created ID: {:?}", some_id);
-        match some_id {
-            Some(id) => Ok(Self::find_by_id_strict(&id).await?),
-            None => Err(Error::CreateFailed("No ID returned".to_string()).into()),
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            if remaining.is_zero() {
+                return Err(Self::map_error(context.wrap(Error::Timeout(timeout))));
+            }
+            tokio::time::sleep(delay.min(remaining)).await;
+            delay = (delay * 2).min(std::time::Duration::from_secs(1));
         }
     }
+    // List endpoints: paging, sorting and whitelisted filters straight from the query string
+    async fn find_list(params: &ListParams) -> Result<Page<Self>, E> {
+        let rules = Self::list_rules();
+        let context = ErrorContext::new(Self::collection().name(), "find_list");
+        let filter = params.filter(&rules).map_err(context.wrapper(Self::map_error))?;
+        let options = params.page_options(&rules).map_err(context.wrapper(Self::map_error))?;
+        Self::find_page_with_total(filter, options).await
+    }
 
-    // UPDATE ======================================================================================================
-    async fn update_one<D: serde::Serialize + Send>(filter: bson::Document, data: D) -> Result<Self, E> {
-        let collection = Self::collection();
+    async fn find_by_ids<I: IdOf<Self> + Sync>(ids: &[I]) -> Result<Vec<Self>, E> {
+        let ids: Vec<bson::Bson> = ids.iter().map(|x| x.raw_id().to_owned().into()).collect();
+        Self::find(bson::doc! { "_id": { "$in": ids } }).await
+    }
+
+    // For joins in application code: fetch the related documents once, then look them up by ID
+    async fn find_map_by_id(filter: bson::Document) -> Result<std::collections::HashMap<IdType, Self>, E> {
+        let items = Self::find(filter).await?;
+        Ok(items.into_iter().map(|x| (x.id_value().to_owned(), x)).collect())
+    }
+
+    // Keyed by any field instead (dotted paths work), the last document wins when several share a key. Documents
+    // without the field are left out.
+    async fn find_map_by<K>(field: &str, filter: bson::Document) -> Result<std::collections::HashMap<K, Self>, E>
+    where
+        K: serde::de::DeserializeOwned + Eq + std::hash::Hash + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "find_map_by").filter(&filter);
+        let pipeline = vec![
+            bson::doc! { "$match": filter },
+            bson::doc! { "$project": { "_id": 0, "key": format!("${}", field), "item": "$$ROOT" } },
+        ];
 
-        let set = bson::to_bson(&data).map_err(|x| (Error::BSONSerError(x)))?;
+        let mut items = std::collections::HashMap::new();
+        for mut document in Self::aggregate::<bson::Document>(pipeline).await? {
+            let Some(key) = document.remove("key") else {
+                continue;
+            };
+            let key = bson::from_bson::<K>(key).map_err(context.wrapper(Self::map_error))?;
+            let item = bson::from_bson::<Self>(document.remove("item").unwrap_or_default());
+            items.insert(key, item.map_err(context.wrapper(Self::map_error))?);
+        }
+        Ok(items)
+    }
+
+    // Reads into `P` instead of the model, see `projection`. `None` derives the projection from `P`'s fields.
+    async fn find_as<P>(filter: bson::Document, projection: Option<bson::Document>) -> Result<Vec<P>, E>
+    where
+        P: serde::de::DeserializeOwned + Send + Sync + Unpin,
+    {
+        let options = mongodb::options::FindOptions::builder()
+            .projection(projection.or_else(projection::projection_of::<P>))
+            .collation(Self::collation())
+            .selection_criteria(Self::read_preference())
+            .read_concern(Self::read_concern())
+            .batch_size(Self::batch_size())
+            .build();
 
-        let update_result = collection
-            .update_one(filter.clone(), bson::doc! { "$set": set }, None)
+        let collection = Self::collection().clone_with_type::<P>();
+        let context = ErrorContext::new(collection.name(), "find_as").filter(&filter);
+        let cursor = breaker::guard(Self::circuit_breaker(), collection.find(filter, options))
             .await
-            .map_err(|x| (Error::DBError(x)))?;
+            .map_err(context.wrapper(Self::map_error))?;
+        let items = cursor.try_collect::<Vec<P>>().await.map_err(context.wrapper(Self::map_error))?;
 
-        if update_result.modified_count != 1 {
-            return Err(Error::UpdateFailed("No record updated".to_string()).into());
-        };
+        Ok(items)
+    }
 
-        Self::find_one_strict(filter).await
+    // `find_as` with the projection of a view tied to this model, e.g. by `#[derive(Projection)]`
+    async fn find_projected<P: projection::Projection<Of = Self>>(filter: bson::Document) -> Result<Vec<P>, E> {
+        Self::find_as::<P>(filter, Some(P::projection())).await
     }
 
-    async fn update_by_id<D: serde::Serialize + Send>(id: &IdType, data: D) -> Result<Self, E> {
-        Self::update_one(bson::doc! { "_id": id }, data).await
+    // The matches without their `sensitive_fields`, which the server doesn't send
+    async fn find_redacted(filter: bson::Document) -> Result<Vec<bson::Document>, E> {
+        let projection = redact::exclusion(Self::sensitive_fields());
+        Self::find_as::<bson::Document>(filter, Some(projection)).await
     }
 
-    // DELETE ======================================================================================================
-    async fn delete_one(filter: bson::Document) -> Result<(), E> {
+    // Values of one field (dotted paths work) of the matches, skipping the ones without it: `pluck("email", ..)`
+    async fn pluck<T: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Vec<T>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "pluck").filter(&filter);
+        let pipeline = vec![
+            bson::doc! { "$match": filter },
+            bson::doc! { "$project": { "_id": 0, "value": format!("${}", field) } },
+        ];
+
+        let documents = Self::aggregate::<bson::Document>(pipeline).await?;
+        let values = documents
+            .into_iter()
+            .filter_map(|mut x| x.remove("value"))
+            .map(bson::from_bson::<T>)
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(values)
+    }
+
+    // One `$facet` aggregation returns both the requested slice and the total, instead of find + count_documents
+    async fn find_page_with_total(filter: bson::Document, options: PageOptions) -> Result<Page<Self>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "find_page_with_total").filter(&filter);
+        let stages = options.facet_stages().map_err(context.wrapper(Self::map_error))?;
+        let pipeline = vec![
+            bson::doc! { "$match": filter },
+            bson::doc! { "$facet": { "data": stages, "total": [{ "$count": "count" }] } },
+        ];
+
+        let result = Self::aggregate::<bson::Document>(pipeline).await?.pop().unwrap_or_default();
+        let page = Page::from_facet(&result, &options).map_err(context.wrapper(Self::map_error))?;
+        Ok(page)
+    }
+
+    async fn faceted_search(
+        filter: bson::Document,
+        facets: &[&str],
+        options: PageOptions,
+    ) -> Result<FacetedResults<Self>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "faceted_search").filter(&filter);
+        let stages = options.facet_stages().map_err(context.wrapper(Self::map_error))?;
+        let mut facet = bson::doc! { "data": stages, "total": [{ "$count": "count" }] };
+        // $facet output names can't contain dots, so buckets are keyed by position and renamed afterwards
+        for (index, field) in facets.iter().enumerate() {
+            facet.insert(format!("facet_{}", index), vec![bson::doc! { "$sortByCount": format!("${}", field) }]);
+        }
+
+        let pipeline = vec![bson::doc! { "$match": filter }, bson::doc! { "$facet": facet }];
+
+        let result = Self::aggregate::<bson::Document>(pipeline).await?.pop().unwrap_or_default();
+        let page = Page::from_facet(&result, &options).map_err(context.wrapper(Self::map_error))?;
+
+        let mut buckets = std::collections::BTreeMap::new();
+        for (index, field) in facets.iter().enumerate() {
+            let values = result
+                .get_array(format!("facet_{}", index))
+                .map(|x| x.iter().filter_map(|x| x.as_document()).map(FacetBucket::from_document).collect())
+                .unwrap_or_default();
+            buckets.insert(field.to_string(), values);
+        }
+
+        Ok(FacetedResults { page, facets: buckets })
+    }
+
+    // RAW =========================================================================================================
+    // Documents as raw BSON, skipping deserialization into the model, for scans that only read a couple of fields
+    // from millions of documents. `get_str("email")` and friends read straight from the buffer.
+    async fn find_raw(
+        filter: bson::Document,
+    ) -> Result<futures::stream::BoxStream<'static, Result<bson::RawDocumentBuf, E>>, E>
+    where
+        E: Send + 'static,
+    {
+        Self::find_raw_with_options(filter, mongodb::options::FindOptions::default()).await
+    }
+
+    // Only the fields in `projection` come back, so there's less to transfer as well
+    async fn find_project_raw(
+        filter: bson::Document,
+        projection: bson::Document,
+    ) -> Result<futures::stream::BoxStream<'static, Result<bson::RawDocumentBuf, E>>, E>
+    where
+        E: Send + 'static,
+    {
+        let options = mongodb::options::FindOptions::builder().projection(projection).build();
+        Self::find_raw_with_options(filter, options).await
+    }
+
+    async fn find_raw_with_options(
+        filter: bson::Document,
+        mut options: mongodb::options::FindOptions,
+    ) -> Result<futures::stream::BoxStream<'static, Result<bson::RawDocumentBuf, E>>, E>
+    where
+        E: Send + 'static,
+    {
+        use futures::StreamExt;
+
+        if options.collation.is_none() {
+            options.collation = Self::collation();
+        }
+        if options.selection_criteria.is_none() {
+            options.selection_criteria = Self::read_preference();
+        }
+        if options.read_concern.is_none() {
+            options.read_concern = Self::read_concern();
+        }
+        if options.batch_size.is_none() {
+            options.batch_size = Self::batch_size();
+        }
+
+        let collection = Self::collection().clone_with_type::<bson::RawDocumentBuf>();
+        let context = ErrorContext::new(collection.name(), "find_raw").filter(&filter);
+        let cursor = breaker::guard(Self::circuit_breaker(), collection.find(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+
+        Ok(cursor.map_err(move |x| Self::map_error(context.wrap(x))).boxed())
+    }
+
+    // PARTITIONED SCAN ============================================================================================
+    // Splits the collection into up to `partitions` `_id` ranges and returns one stream per range, for workers
+    // going through a big collection in parallel. The split points come from a `$sample` of the `_id`s, so ranges
+    // hold roughly the same number of documents. Ranges don't overlap and together cover every `_id` of the
+    // model's ID type, including documents inserted while the scan runs.
+    async fn partitioned_scan(
+        partitions: usize,
+    ) -> Result<Vec<futures::stream::BoxStream<'static, Result<Self, E>>>, E>
+    where
+        E: Send + 'static,
+    {
+        use futures::StreamExt;
+        const SAMPLES_PER_PARTITION: usize = 10;
+
         let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "partitioned_scan");
 
-        let delete_result = collection.delete_one(filter, None).await.map_err(|x| (Error::DBError(x)))?;
+        let samples = (partitions.max(1) * SAMPLES_PER_PARTITION) as i64;
+        let pipeline = vec![
+            bson::doc! { "$sample": { "size": samples } },
+            bson::doc! { "$project": { "_id": 1 } },
+            bson::doc! { "$sort": { "_id": 1 } },
+        ];
+        let mut ids: Vec<bson::Bson> = Self::aggregate::<bson::Document>(pipeline)
+            .await?
+            .into_iter()
+            .filter_map(|mut x| x.remove("_id"))
+            .collect();
+        ids.dedup();
 
-        if delete_result.deleted_count != 1 {
-            return Err(Error::DeleteFailed("No record deleted".to_string()).into());
+        // The sampled `_id`s in `partitions` equal groups, each group but the first starts a new range
+        let step = ids.len().div_ceil(partitions.max(1)).max(1);
+        let bounds: Vec<bson::Bson> = ids.into_iter().skip(step).step_by(step).collect();
+        let lower = std::iter::once(None).chain(bounds.iter().cloned().map(Some));
+        let upper = bounds.iter().cloned().map(Some).chain(std::iter::once(None));
+
+        let streams = lower
+            .zip(upper)
+            .map(|(lower, upper)| {
+                let mut range = bson::Document::new();
+                if let Some(x) = lower {
+                    range.insert("$gte", x);
+                }
+                if let Some(x) = upper {
+                    range.insert("$lt", x);
+                }
+                let filter = if range.is_empty() { bson::doc! {} } else { bson::doc! { "_id": range } };
+                let context = context.clone().filter(&filter);
+
+                let options = mongodb::options::FindOptions::builder().batch_size(Self::batch_size()).build();
+                futures::stream::once(async move {
+                    let cursor = breaker::guard(Self::circuit_breaker(), Self::collection().find(filter, options))
+                        .await
+                        .map_err(|x| Self::map_error(context.wrap(x)))?;
+                    Ok::<_, E>(cursor.map_err(move |x| Self::map_error(context.wrap(x))))
+                })
+                .try_flatten()
+                .boxed()
+            })
+            .collect();
+
+        Ok(streams)
+    }
+
+    // TAIL ========================================================================================================
+    // Follows a capped collection like `tail -f`, with a tailable awaitData cursor. When the cursor dies (empty
+    // collection, failover, network) it's reopened after a second, resuming after the last `_id` seen, so this
+    // relies on `_id`s growing in insertion order (ObjectIds, ULIDs). Errors are yielded and the stream goes on.
+    fn tail(filter: bson::Document) -> futures::stream::BoxStream<'static, Result<Self, E>>
+    where
+        E: Send + 'static,
+    {
+        use futures::StreamExt;
+
+        struct Tail {
+            filter: bson::Document,
+            context: ErrorContext,
+            cursor: Option<mongodb::Cursor<bson::Document>>,
+            last_id: Option<bson::Bson>,
+            reconnect: bool,
+        }
+
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "tail").filter(&filter);
+        let state = Tail { filter, context, cursor: None, last_id: None, reconnect: false };
+
+        futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if state.cursor.is_none() {
+                    if state.reconnect {
+                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    }
+                    state.reconnect = true;
+
+                    let filter = match &state.last_id {
+                        Some(id) => bson::doc! { "$and": [state.filter.clone(), { "_id": { "$gt": id.clone() } }] },
+                        None => state.filter.clone(),
+                    };
+                    let options = mongodb::options::FindOptions::builder()
+                        .cursor_type(mongodb::options::CursorType::TailableAwait)
+                        .batch_size(Self::batch_size())
+                        .build();
+                    let collection = Self::collection().clone_with_type::<bson::Document>();
+                    match breaker::guard(Self::circuit_breaker(), collection.find(filter, options)).await {
+                        Ok(cursor) => state.cursor = Some(cursor),
+                        Err(x) => return Some((Err(Self::map_error(state.context.wrap(x))), state)),
+                    }
+                }
+
+                let next = match state.cursor.as_mut() {
+                    Some(cursor) => cursor.try_next().await,
+                    None => continue,
+                };
+                match next {
+                    Ok(Some(document)) => {
+                        state.last_id = document.get("_id").cloned();
+                        let item = bson::from_document::<Self>(document);
+                        return Some((item.map_err(|x| Self::map_error(state.context.wrap(x))), state));
+                    }
+                    Ok(None) => state.cursor = None,
+                    Err(x) => {
+                        state.cursor = None;
+                        return Some((Err(Self::map_error(state.context.wrap(x))), state));
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
+    // WATCH =======================================================================================================
+    // Change stream of the collection with full documents looked up for updates, `pipeline` filters the events.
+    // Reconnects by itself, see `watch` module.
+    fn watch(
+        pipeline: Vec<bson::Document>,
+    ) -> futures::stream::BoxStream<'static, Result<mongodb::change_stream::event::ChangeStreamEvent<Self>, E>>
+    where
+        E: Send + 'static,
+    {
+        watch::stream::<Self, E>(pipeline, None)
+    }
+
+    // Starts after the token `store` has saved for `name` and saves every consumed event's token
+    fn watch_resumable<S: watch::ResumeTokenStore + 'static>(
+        name: &str,
+        pipeline: Vec<bson::Document>,
+        store: S,
+    ) -> futures::stream::BoxStream<'static, Result<mongodb::change_stream::event::ChangeStreamEvent<Self>, E>>
+    where
+        E: Send + 'static,
+    {
+        watch::stream::<Self, E>(pipeline, Some((name.to_string(), std::sync::Arc::new(store))))
+    }
+
+    // Every new version of one document, for pushing live updates. Ends when the document is deleted.
+    fn watch_by_id<I: IdOf<Self> + ?Sized>(id: &I) -> futures::stream::BoxStream<'static, Result<Self, E>>
+    where
+        E: Send + 'static,
+    {
+        let pipeline = vec![bson::doc! { "$match": { "documentKey._id": id.raw_id() } }];
+        watch::versions(Self::watch(pipeline))
+    }
+
+    // Keeps the `deferred()` mirrors up to date from the change stream, yielding how many dependents each update
+    // or replace changed. Reconnects like `watch`, restarting it after a crash may miss the changes in between
+    // (`apply_mirrors` catches up one document).
+    fn sync_mirrors() -> futures::stream::BoxStream<'static, Result<u64, E>>
+    where
+        E: Send + 'static,
+    {
+        use futures::StreamExt;
+
+        let pipeline = vec![bson::doc! { "$match": { "operationType": { "$in": ["update", "replace"] } } }];
+        Self::watch(pipeline)
+            .and_then(|event| async move {
+                let Some(item) = event.full_document else {
+                    return Ok(0);
+                };
+                let context = ErrorContext::new(Self::collection().name(), "sync_mirrors");
+                propagate_mirrors::<Self, E>(&item, |x| x.deferred).await.map_err(context.wrapper(Self::map_error))
+            })
+            .boxed()
+    }
+
+    // AGGREGATE ===================================================================================================
+    async fn aggregate<T: serde::de::DeserializeOwned + Send>(pipeline: Vec<bson::Document>) -> Result<Vec<T>, E> {
+        Self::aggregate_with_options(pipeline, mongodb::options::AggregateOptions::default()).await
+    }
+
+    async fn aggregate_with_options<T: serde::de::DeserializeOwned + Send>(
+        pipeline: Vec<bson::Document>,
+        mut options: mongodb::options::AggregateOptions,
+    ) -> Result<Vec<T>, E> {
+        if options.collation.is_none() {
+            options.collation = Self::collation();
+        }
+        if options.selection_criteria.is_none() {
+            options.selection_criteria = Self::read_preference();
+        }
+        if options.read_concern.is_none() {
+            options.read_concern = Self::read_concern();
+        }
+        if options.batch_size.is_none() {
+            options.batch_size = Self::batch_size();
+        }
+
+        let collection = Self::collection();
+        // The `$match` of the first stage, if any, stands in for the filter
+        let context = match pipeline.first().and_then(|x| x.get_document("$match").ok()) {
+            Some(filter) => ErrorContext::new(collection.name(), "aggregate").filter(filter),
+            None => ErrorContext::new(collection.name(), "aggregate"),
         };
 
-        Ok(())
+        let cursor = breaker::guard(Self::circuit_breaker(), collection.aggregate(pipeline, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        let documents = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+
+        let items = documents
+            .into_iter()
+            .map(bson::from_document::<T>)
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(context.wrapper(Self::map_error))?;
+
+        Ok(items)
     }
 
-    async fn delete_by_id(id: &IdType) -> Result<(), E> {
-        Self::delete_one(bson::doc! { "_id": id }).await
+    async fn sample(n: u64, filter: bson::Document) -> Result<Vec<Self>, E> {
+        let pipeline = vec![bson::doc! { "$match": filter }, bson::doc! { "$sample": { "size": n as i64 } }];
+        Self::aggregate::<Self>(pipeline).await
     }
 
-    // Instance Methods
-    async fn create(&self) -> Result<Self, E> {
-        Self::create_one(self).await
+    fn group_by(field: &str) -> GroupBy<Self, E> {
+        GroupBy::new(field)
     }
-    async fn update<D: serde::Serialize + Send>(&self, data: D) -> Result<Self, E> {
-        Self::update_by_id(self.id_value(), data).await
+
+    async fn count_by<K: serde::de::DeserializeOwned>(field: &str) -> Result<Vec<(K, u64)>, E> {
+        Self::group_by(field).agg("count", Accumulator::Count).run().await
     }
-    async fn delete(&self) -> Result<(), E> {
-        Self::delete_by_id(self.id_value()).await
+
+    async fn sum_by<K, V>(group_field: &str, value_field: &str) -> Result<Vec<(K, V)>, E>
+    where
+        K: serde::de::DeserializeOwned,
+        V: serde::de::DeserializeOwned,
+    {
+        Self::group_by(group_field).agg("sum", Accumulator::sum(value_field)).run().await
+    }
+
+    // Groups of matching documents with equal `fields`, most duplicated first. Uses disk for large collections.
+    async fn find_duplicates(fields: &[&str], filter: bson::Document) -> Result<Vec<Duplicate>, E> {
+        let pipeline = group::duplicates_pipeline(fields, filter);
+        let options = mongodb::options::AggregateOptions::builder().allow_disk_use(true).build();
+        Self::aggregate_with_options::<Duplicate>(pipeline, options).await
+    }
+
+    // Highest value of `field` among the matches, `None` when nothing matches or none of them has the field
+    async fn max_of<V: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Option<V>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "max_of").filter(&filter);
+        let result = Self::aggregate::<bson::Document>(extremum("$max", field, filter)).await?;
+        bson::from_bson(extremum_value(result)).map_err(context.wrapper(Self::map_error))
+    }
+
+    async fn min_of<V: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Option<V>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "min_of").filter(&filter);
+        let result = Self::aggregate::<bson::Document>(extremum("$min", field, filter)).await?;
+        bson::from_bson(extremum_value(result)).map_err(context.wrapper(Self::map_error))
+    }
+
+    // MATERIALIZED VIEWS ==========================================================================================
+    // Runs `pipeline` into `T`'s collection and records the refresh, see `materialize`. `every` is only recorded.
+    async fn materialize_into<T>(
+        mut pipeline: Vec<bson::Document>,
+        every: std::time::Duration,
+    ) -> Result<materialize::Refresh, E>
+    where
+        T: RustMongoDBModelMethods<E>,
+    {
+        let collection = writes::<Self, E>();
+        let target = T::collection().namespace();
+        let context = ErrorContext::new(collection.name(), "materialize_into");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let started = std::time::Instant::now();
+        pipeline.push(materialize::Output::Merge(materialize::Merge::default()).stage(&target));
+        let options = mongodb::options::AggregateOptions::builder().allow_disk_use(true).build();
+        // `$merge` returns no documents, the cursor only has to be drained
+        Self::aggregate_with_options::<bson::Document>(pipeline, options).await?;
+
+        let refresh = materialize::Refresh {
+            target: target.to_string(),
+            source: collection.namespace().to_string(),
+            refreshed_at: bson::DateTime::now(),
+            took_ms: started.elapsed().as_millis() as u64,
+            every_ms: every.as_millis() as u64,
+        };
+        let database = collection.client().database(&collection.namespace().db);
+        let refreshes = database.collection::<materialize::Refresh>(materialize::COLLECTION);
+        let options = mongodb::options::ReplaceOptions::builder().upsert(true).build();
+        let replace = refreshes.replace_one(bson::doc! { "_id": &refresh.target }, &refresh, options);
+        breaker::guard(Self::circuit_breaker(), replace).await.map_err(context.wrapper(Self::map_error))?;
+
+        Ok(refresh)
+    }
+
+    // Writes the results of `pipeline` to `target`, a collection of this model's database
+    async fn aggregate_out(
+        mut pipeline: Vec<bson::Document>,
+        target: &str,
+        output: materialize::Output,
+    ) -> Result<(), E> {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "aggregate_out");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let namespace = mongodb::Namespace::new(collection.namespace().db, target);
+        pipeline.push(output.stage(&namespace));
+        let options = mongodb::options::AggregateOptions::builder().allow_disk_use(true).build();
+        Self::aggregate_with_options::<bson::Document>(pipeline, options).await?;
+        Ok(())
+    }
+
+    // Last `materialize_into::<T>` run from any model, `None` before the first
+    async fn last_materialized<T>() -> Result<Option<materialize::Refresh>, E>
+    where
+        T: RustMongoDBModelMethods<E>,
+    {
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "last_materialized");
+        let database = collection.client().database(&collection.namespace().db);
+        let refreshes = database.collection::<materialize::Refresh>(materialize::COLLECTION);
+        let find = refreshes.find_one(bson::doc! { "_id": T::collection().namespace().to_string() }, read_back());
+        let refresh = breaker::guard(Self::circuit_breaker(), find).await.map_err(context.wrapper(Self::map_error))?;
+        Ok(refresh)
+    }
+
+    // ATLAS SEARCH ================================================================================================
+    #[cfg(feature = "atlas_search")]
+    async fn search(query: search::SearchQuery) -> Result<Vec<(Self, f64)>, E> {
+        let hits = Self::search_highlighted(query).await?;
+        Ok(hits.into_iter().map(|x| (x.item, x.score)).collect())
+    }
+
+    #[cfg(feature = "atlas_search")]
+    async fn search_highlighted(query: search::SearchQuery) -> Result<Vec<search::SearchHit<Self>>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "search");
+        let documents = Self::aggregate::<bson::Document>(query.pipeline()).await?;
+        let hits = documents
+            .into_iter()
+            .map(search::SearchHit::from_document)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(hits)
+    }
+
+    // Name of the Atlas Vector Search index used by `vector_search`
+    #[cfg(feature = "atlas_search")]
+    fn vector_search_index() -> &'static str {
+        "vector_index"
+    }
+
+    // `filter` may only reference fields declared as filter fields in the vector index
+    #[cfg(feature = "atlas_search")]
+    async fn vector_search(
+        field: &str,
+        embedding: &[f32],
+        k: u64,
+        filter: bson::Document,
+    ) -> Result<Vec<(Self, f64)>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "vector_search").filter(&filter);
+        let pipeline = search::vector_search_pipeline(Self::vector_search_index(), field, embedding, k, filter);
+        let pipeline = pipeline.map_err(context.wrapper(Self::map_error))?;
+        let documents = Self::aggregate::<bson::Document>(pipeline).await?;
+        let hits = documents
+            .into_iter()
+            .map(|x| search::SearchHit::from_document(x).map(|x| (x.item, x.score)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(hits)
+    }
+
+    // ANALYTICS ===================================================================================================
+    // Reads from the secondaries described by `analytics()`, unless `options` already select servers
+    async fn find_on_secondary(
+        filter: bson::Document,
+        mut options: mongodb::options::FindOptions,
+    ) -> Result<Vec<Self>, E> {
+        if options.selection_criteria.is_none() {
+            options.selection_criteria = Some(Self::analytics().selection_criteria());
+        }
+        Self::find_with_options(filter, options).await
+    }
+
+    async fn aggregate_on_secondary<T: serde::de::DeserializeOwned + Send>(
+        pipeline: Vec<bson::Document>,
+    ) -> Result<Vec<T>, E> {
+        let options = mongodb::options::AggregateOptions::builder()
+            .selection_criteria(Self::analytics().selection_criteria())
+            .build();
+        Self::aggregate_with_options(pipeline, options).await
+    }
+
+    // GEO =========================================================================================================
+    async fn geo_near(query: geo::GeoNear) -> Result<Vec<(Self, f64)>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "geo_near");
+        let documents = Self::aggregate::<bson::Document>(query.pipeline()).await?;
+        let items = documents
+            .into_iter()
+            .map(|mut x| {
+                let distance = x.remove(geo::DISTANCE_FIELD).and_then(|x| x.as_f64()).unwrap_or(0.0);
+                bson::from_document::<Self>(x).map(|x| (x, distance))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(items)
+    }
+
+    // SCHEMA ======================================================================================================
+    // Stored documents not matching `json_schema()`, fails with `Error::InvalidParams` when the model has none
+    async fn validate_collection() -> Result<Vec<SchemaViolation>, E> {
+        let context = ErrorContext::new(Self::collection().name(), "validate_collection");
+        let Some(json_schema) = Self::json_schema() else {
+            let missing = Error::InvalidParams("The model declares no `json_schema()`".to_string());
+            return Err(Self::map_error(context.wrap(missing)));
+        };
+
+        let (pipeline, checks) = schema::validation_pipeline(&json_schema);
+        let options = mongodb::options::AggregateOptions::builder().allow_disk_use(true).build();
+        let mut result = Self::aggregate_with_options::<bson::Document>(pipeline, options).await?;
+        Ok(result.pop().map(|x| schema::violations(x, checks)).unwrap_or_default())
+    }
+
+    // RELATIONS ===================================================================================================
+    // Children whose `BelongsTo<P>` foreign key points to no `P`, see `relations`
+    async fn find_orphans<P>() -> Result<Vec<Orphan>, E>
+    where
+        Self: BelongsTo<P>,
+        P: RustMongoDBModelMethods<E>,
+    {
+        let parents = P::collection();
+        let pipeline = relations::orphans_pipeline(<Self as BelongsTo<P>>::FOREIGN_KEY, parents.name());
+        let options = mongodb::options::AggregateOptions::builder().allow_disk_use(true).build();
+        let orphans = Self::aggregate_with_options::<bson::Document>(pipeline, options).await?;
+        Ok(orphans.into_iter().map(relations::orphan).collect())
+    }
+
+    // INDEXES =====================================================================================================
+    async fn ensure_indexes() -> Result<(), E> {
+        let models: Vec<mongodb::IndexModel> = declared_indexes::<Self, E>().iter().map(Index::to_model).collect();
+        if models.is_empty() {
+            return Ok(());
+        }
+
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "ensure_indexes");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        collection.create_indexes(models, None).await.map_err(context.wrapper(Self::map_error))?;
+        Ok(())
+    }
+
+    async fn check_indexes() -> Result<IndexDrift, E> {
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "check_indexes");
+        let existing: Vec<mongodb::IndexModel> = match collection.list_indexes(None).await {
+            Ok(cursor) => cursor.try_collect().await.map_err(context.wrapper(Self::map_error))?,
+            // No collection yet, every declared index is missing
+            Err(x) if matches!(x.kind.as_ref(), mongodb::error::ErrorKind::Command(x) if x.code == 26) => Vec::new(),
+            Err(x) => return Err(Self::map_error(context.wrap(x))),
+        };
+
+        Ok(IndexDrift::compare(&declared_indexes::<Self, E>(), &existing))
+    }
+
+    // For startup: fails with `Error::IndexDrift` unless the server matches the declarations exactly
+    async fn check_indexes_strict() -> Result<(), E> {
+        let drift = Self::check_indexes().await?;
+        if !drift.is_clean() {
+            let context = ErrorContext::new(Self::collection().name(), "check_indexes_strict");
+            return Err(Self::map_error(context.wrap(Error::IndexDrift(drift))));
+        }
+        Ok(())
+    }
+
+    // For startup: fails with `Error::InvalidParams` when `collection()` is in another database than the route of
+    // `logical_database()`. Clients can't be compared, only the database names are.
+    fn check_database() -> Result<(), E> {
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "check_database");
+        let logical = Self::logical_database();
+        let routed = connection::database_of(logical).map_err(context.wrapper(Self::map_error))?;
+        if routed.name() != collection.namespace().db {
+            let (name, database) = (collection.name(), collection.namespace().db);
+            let message = format!("`{}` is in `{}`, `{}` routes to `{}`", name, database, logical, routed.name());
+            return Err(Self::map_error(context.wrap(Error::InvalidParams(message))));
+        }
+        Ok(())
+    }
+
+    // COUNT =======================================================================================================
+    async fn count(filter: bson::Document) -> Result<u64, E> {
+        let options = mongodb::options::CountOptions::builder()
+            .collation(Self::collation())
+            .selection_criteria(Self::read_preference())
+            .read_concern(Self::read_concern())
+            .build();
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "count").filter(&filter);
+        let count = breaker::guard(Self::circuit_breaker(), collection.count_documents(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(count)
+    }
+
+    async fn count_where(filter: FilterExpr) -> Result<u64, E> {
+        Self::count(filter.to_document()).await
+    }
+
+    // Stops at the first match and never fetches the document
+    async fn exists(filter: bson::Document) -> Result<bool, E> {
+        let options = mongodb::options::CountOptions::builder()
+            .limit(1)
+            .collation(Self::collation())
+            .selection_criteria(Self::read_preference())
+            .read_concern(Self::read_concern())
+            .build();
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "exists").filter(&filter);
+        let count = breaker::guard(Self::circuit_breaker(), collection.count_documents(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(count > 0)
+    }
+
+    async fn exists_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<bool, E> {
+        Self::exists(bson::doc! { "_id": id.raw_id() }).await
+    }
+
+    // Reads the collection metadata instead of scanning, so it's instant but can be off after unclean
+    // shutdowns or while writes are in flight on sharded clusters. Use `count` when the number must be exact.
+    async fn estimated_count() -> Result<u64, E> {
+        let collection = Self::collection();
+        let context = ErrorContext::new(collection.name(), "estimated_count");
+        let count = breaker::guard(Self::circuit_breaker(), collection.estimated_document_count(None))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(count)
+    }
+
+    // CREATE ======================================================================================================
+    async fn create_one(data: &Self) -> Result<Self, E> {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let mut context = ErrorContext::new(collection.name(), "create_one");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let mut document = bson::to_document(data).map_err(context.wrapper(Self::map_error))?;
+        if document.get("_id").is_none_or(id_type::is_unset) {
+            document.insert("_id", Self::generate_id());
+        }
+        context = context.id(document.get("_id").cloned().unwrap_or_default());
+        shard::check_document(Self::shard_key(), &document).map_err(context.wrapper(Self::map_error))?;
+        check_references::<Self, E>(&document).await.map_err(context.wrapper(Self::map_error))?;
+        let exclude = bson::doc! { "_id": document.get("_id") };
+        check_unique::<Self, E>(&document, exclude).await.map_err(context.wrapper(Self::map_error))?;
+
+        let insert_result = breaker::guard(Self::circuit_breaker(), collection.insert_one(document, None))
+            .await
+            .map_err(duplicate_key::<Self, E>)
+            .map_err(context.wrapper(Self::map_error))?;
+
+        let some_id = id_type::from_bson(&insert_result.inserted_id);
+        match some_id {
+            Some(id) => {
+                let item = Self::find_one_with_options(bson::doc! { "_id": &id }, read_back()).await?;
+                item.ok_or_else(|| Self::map_error(context.wrap(Error::not_found_id::<Self>(id))))
+            }
+            None => Err(Self::map_error(context.wrap(Error::CreateFailed("No ID returned".to_string())))),
+        }
+    }
+
+    // UPDATE ======================================================================================================
+    // Fails with `Error::NotFound` when nothing matches. Updates that leave the document as it was succeed,
+    // use `update_one_modified` to tell them apart.
+    async fn update_one<D: serde::Serialize + Send>(filter: bson::Document, data: D) -> Result<Self, E> {
+        let (item, _) = Self::update_one_modified(filter, data).await?;
+        Ok(item)
+    }
+
+    // Also returns whether the document changed, `false` for no-op updates
+    async fn update_one_modified<D>(filter: bson::Document, data: D) -> Result<(Self, bool), E>
+    where
+        D: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "update_one").filter(&filter);
+        let set = bson::to_bson(&data).map_err(context.wrapper(Self::map_error))?;
+        update_set_unmapped::<Self, E>(filter, set).await.map_err(Self::map_error)
+    }
+
+    // Any update: operators other than `$set`, or a pipeline computing fields from other fields
+    //
+    //     let pipeline = vec![doc! { "$set": { "total": { "$add": ["$subtotal", "$tax"] } } }];
+    //     Order::update_one_with(doc! { "_id": id }, pipeline.into()).await?;
+    async fn update_one_with(
+        filter: bson::Document,
+        update: mongodb::options::UpdateModifications,
+    ) -> Result<(Self, bool), E> {
+        update_one_unmapped::<Self, E>(filter, update).await.map_err(Self::map_error)
+    }
+
+    async fn set_field<I, T>(id: &I, field: Field<Self, T>, value: T) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        T: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "set_field").id(id.raw_id().to_owned());
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let set = bson::doc! { field.name(): bson::to_bson(&value).map_err(context.wrapper(Self::map_error))? };
+        check_references::<Self, E>(&set).await.map_err(context.wrapper(Self::map_error))?;
+        let exclude = bson::doc! { "_id": id.raw_id() };
+        check_unique::<Self, E>(&set, exclude).await.map_err(context.wrapper(Self::map_error))?;
+        let update = bson::doc! { "$set": set };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    async fn unset_field<I, T>(id: &I, field: Field<Self, T>) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        T: Send,
+    {
+        let update = bson::doc! { "$unset": { field.name(): "" } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // Removes `field` from the matches that have it, the usual step after dropping it from the struct. Returns
+    // how many documents lost it, or with `dry_run` how many would, without touching them.
+    async fn drop_field(field: &str, filter: bson::Document, dry_run: bool) -> Result<u64, E> {
+        let filter = bson::doc! { "$and": [filter, { field: { "$exists": true } }] };
+        if dry_run {
+            return Self::count(filter).await;
+        }
+        let summary = Self::update_many_with(filter, bson::doc! { "$unset": { field: "" } }.into()).await?;
+        Ok(summary.modified)
+    }
+
+    // `$rename` in batches of 1000 by `_id`, calling `progress` with the running total after each. Fails with
+    // `Error::UpdateFailed` if matches still carry `old` at the end (written meanwhile by code using the old name).
+    async fn rename_field<P>(old: &str, new: &str, filter: bson::Document, mut progress: P) -> Result<u64, E>
+    where
+        P: FnMut(u64) + Send,
+    {
+        const BATCH_SIZE: i64 = 1000;
+
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let filter = bson::doc! { "$and": [filter, { old: { "$exists": true } }] };
+        let context = ErrorContext::new(collection.name(), "rename_field").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let rename = bson::doc! { "$rename": { old: new } };
+        immutable::check(Self::immutable_fields(), &rename.clone().into()).map_err(context.wrapper(Self::map_error))?;
+
+        let mut renamed = 0;
+        let mut after: Option<bson::Bson> = None;
+        loop {
+            let mut batch_filter = filter.clone();
+            if let Some(after) = after.take() {
+                batch_filter = bson::doc! { "$and": [batch_filter, { "_id": { "$gt": after } }] };
+            }
+            let options = mongodb::options::FindOptions::builder()
+                .projection(bson::doc! { "_id": 1 })
+                .sort(bson::doc! { "_id": 1 })
+                .limit(BATCH_SIZE)
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let cursor = breaker::guard(Self::circuit_breaker(), collection.find(batch_filter, options))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let batch = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+            let ids: Vec<bson::Bson> = batch.into_iter().filter_map(|mut x| x.remove("_id")).collect();
+            let Some(last) = ids.last().cloned() else {
+                break;
+            };
+
+            let summary = Self::update_many_with(bson::doc! { "_id": { "$in": ids } }, rename.clone().into()).await?;
+            renamed += summary.modified;
+            progress(renamed);
+            after = Some(last);
+        }
+
+        let options = mongodb::options::CountOptions::builder()
+            .selection_criteria(read_back().selection_criteria)
+            .build();
+        let remaining = breaker::guard(Self::circuit_breaker(), collection.count_documents(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        if remaining > 0 {
+            let message = format!("{} documents still have `{}`", remaining, old);
+            return Err(Self::map_error(context.wrap(Error::UpdateFailed(message))));
+        }
+        Ok(renamed)
+    }
+
+    // Sets `field` on the matches that don't have it yet to `value` of the stored document (not `Self`, which
+    // may not deserialize without the field). Batches are sorted by `_id`, `progress` gets the running totals after
+    // each one. A document given the field meanwhile by someone else keeps their value.
+    async fn backfill<V, F, P>(
+        field: &str,
+        filter: bson::Document,
+        options: BackfillOptions,
+        value: F,
+        mut progress: P,
+    ) -> Result<BackfillProgress, E>
+    where
+        V: serde::Serialize,
+        F: Fn(&bson::Document) -> Option<V> + Send + Sync,
+        P: FnMut(&BackfillProgress) + Send,
+    {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let filter = bson::doc! { "$and": [filter, { field: { "$exists": false } }] };
+        let context = ErrorContext::new(collection.name(), "backfill").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let last_id = options.resume_after.clone().unwrap_or_default();
+        let mut done = BackfillProgress { last_id, ..Default::default() };
+        loop {
+            let batch_filter = match &done.last_id {
+                bson::Bson::Null => filter.clone(),
+                last_id => bson::doc! { "$and": [&filter, { "_id": { "$gt": last_id } }] },
+            };
+            let find_options = mongodb::options::FindOptions::builder()
+                .sort(bson::doc! { "_id": 1 })
+                .limit(i64::from(options.batch_size.max(1)))
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let cursor = breaker::guard(Self::circuit_breaker(), collection.find(batch_filter, find_options))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let batch = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+            let Some(last_id) = batch.last().and_then(|x| x.get("_id")).cloned() else {
+                break;
+            };
+
+            let mut updates = Vec::with_capacity(batch.len());
+            for document in &batch {
+                let Some(computed) = value(document) else {
+                    done.skipped += 1;
+                    continue;
+                };
+                let computed = bson::to_bson(&computed).map_err(context.wrapper(Self::map_error))?;
+                let id = document.get("_id").cloned().unwrap_or_default();
+                updates.push(bson::doc! {
+                    "q": { "_id": id, field: { "$exists": false } },
+                    "u": { "$set": { field: computed } },
+                });
+            }
+            let updated = write::update_all(&collection, updates, Self::circuit_breaker()).await;
+            done.updated += updated.map_err(context.wrapper(Self::map_error))?;
+
+            done.last_id = last_id;
+            progress(&done);
+            if !options.pause.is_zero() {
+                tokio::time::sleep(options.pause).await;
+            }
+        }
+
+        Ok(done)
+    }
+
+    // Recomputes the `DerivedFields` of the matches in batches of 500 by `_id` and writes back the ones that
+    // changed, returns how many documents were updated
+    async fn refresh_derived(filter: bson::Document) -> Result<u64, E>
+    where
+        Self: DerivedFields,
+    {
+        const BATCH_SIZE: i64 = 500;
+
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "refresh_derived").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let mut updated = 0;
+        let mut last_id = bson::Bson::Null;
+        loop {
+            let batch_filter = match &last_id {
+                bson::Bson::Null => filter.clone(),
+                last_id => bson::doc! { "$and": [&filter, { "_id": { "$gt": last_id } }] },
+            };
+            let options = mongodb::options::FindOptions::builder()
+                .sort(bson::doc! { "_id": 1 })
+                .limit(BATCH_SIZE)
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let cursor = breaker::guard(Self::circuit_breaker(), collection.find(batch_filter, options))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let batch = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+            let Some(last) = batch.last().and_then(|x| x.get("_id")).cloned() else {
+                break;
+            };
+
+            let mut updates = Vec::new();
+            for before in batch {
+                let mut item: Self = bson::from_document(before.clone()).map_err(context.wrapper(Self::map_error))?;
+                item.recompute();
+                let after = bson::to_document(&item).map_err(context.wrapper(Self::map_error))?;
+                let set = derived::changes(Self::FIELDS, &before, &after);
+                if !set.is_empty() {
+                    let id = before.get("_id").cloned().unwrap_or_default();
+                    updates.push(bson::doc! { "q": { "_id": id }, "u": { "$set": set } });
+                }
+            }
+            let modified = write::update_all(&collection, updates, Self::circuit_breaker()).await;
+            updated += modified.map_err(context.wrapper(Self::map_error))?;
+            last_id = last;
+        }
+
+        Ok(updated)
+    }
+
+    // Rewrites the matches with the current key of the keyring, in batches of 500 by `_id`, so the retired keys
+    // can be dropped after a rotation. A document changed between the read and its rewrite is left as it is and
+    // not counted, run it again until it returns 0. Returns how many documents were rewritten.
+    #[cfg(feature = "encryption")]
+    async fn reencrypt(filter: bson::Document) -> Result<u64, E> {
+        const BATCH_SIZE: i64 = 500;
+
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "reencrypt").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let mut rewritten = 0;
+        let mut last_id = bson::Bson::Null;
+        loop {
+            let batch_filter = match &last_id {
+                bson::Bson::Null => filter.clone(),
+                last_id => bson::doc! { "$and": [&filter, { "_id": { "$gt": last_id } }] },
+            };
+            let options = mongodb::options::FindOptions::builder()
+                .sort(bson::doc! { "_id": 1 })
+                .limit(BATCH_SIZE)
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let cursor = breaker::guard(Self::circuit_breaker(), collection.find(batch_filter, options))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let batch = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+            let Some(last) = batch.last().and_then(|x| x.get("_id")).cloned() else {
+                break;
+            };
+
+            let mut updates = Vec::with_capacity(batch.len());
+            for before in batch {
+                let item: Self = bson::from_document(before.clone()).map_err(context.wrapper(Self::map_error))?;
+                let after = bson::to_document(&item).map_err(context.wrapper(Self::map_error))?;
+                let id = before.get("_id").cloned().unwrap_or_default();
+                let unchanged = bson::doc! { "$eq": ["$$ROOT", { "$literal": before }] };
+                updates.push(bson::doc! { "q": { "_id": id, "$expr": unchanged }, "u": after });
+            }
+            let modified = write::update_all(&collection, updates, Self::circuit_breaker()).await;
+            rewritten += modified.map_err(context.wrapper(Self::map_error))?;
+            last_id = last;
+        }
+
+        Ok(rewritten)
+    }
+
+    async fn update_one_pipeline(filter: bson::Document, pipeline: Vec<bson::Document>) -> Result<Self, E> {
+        let (item, _) = Self::update_one_with(filter, pipeline.into()).await?;
+        Ok(item)
+    }
+
+    // Every match, `not_found` stays empty
+    async fn update_many_with(
+        filter: bson::Document,
+        update: mongodb::options::UpdateModifications,
+    ) -> Result<UpdateSummary, E> {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "update_many").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        immutable::check(Self::immutable_fields(), &update).map_err(context.wrapper(Self::map_error))?;
+
+        let options = mongodb::options::UpdateOptions::builder().collation(Self::collation()).build();
+        let update = collection.update_many(filter, update, options);
+        let update_result = breaker::guard(Self::circuit_breaker(), update)
+            .await
+            .map_err(duplicate_key::<Self, E>)
+            .map_err(context.wrapper(Self::map_error))?;
+
+        Ok(UpdateSummary {
+            matched: update_result.matched_count,
+            modified: update_result.modified_count,
+            not_found: Vec::new(),
+        })
+    }
+
+    async fn update_many_pipeline(filter: bson::Document, pipeline: Vec<bson::Document>) -> Result<UpdateSummary, E> {
+        Self::update_many_with(filter, pipeline.into()).await
+    }
+
+    async fn update_by_id<I, D>(id: &I, data: D) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        D: serde::Serialize + Send,
+    {
+        Self::update_one(bson::doc! { "_id": id.raw_id() }, data).await
+    }
+
+    // Swaps the whole matching document for `data` in one step and returns what it was, `None` when nothing
+    // matched (and `data` was inserted, with `upsert`). An unset `_id` in `data` keeps the replaced document's,
+    // documents inserted that way get a server ObjectId unless `filter` pins the `_id`. Fails with
+    // `Error::ImmutableField` when `data` changes one of `immutable_fields()`.
+    async fn find_one_and_replace(filter: bson::Document, data: &Self, upsert: bool) -> Result<Option<Self>, E> {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "find_one_and_replace").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        shard::check_filter(Self::shard_key(), &filter, false).map_err(context.wrapper(Self::map_error))?;
+
+        let mut replacement = bson::to_document(data).map_err(context.wrapper(Self::map_error))?;
+        if replacement.get("_id").is_some_and(id_type::is_unset) {
+            replacement.remove("_id");
+        }
+        // Checked against the matched document, pinned by its `_id` so that's the one replaced
+        let filter = match Self::immutable_fields() {
+            [] => filter,
+            immutable => {
+                let options = mongodb::options::FindOneOptions::builder().collation(Self::collation()).build();
+                let current = breaker::guard(Self::circuit_breaker(), collection.find_one(filter.clone(), options))
+                    .await
+                    .map_err(context.wrapper(Self::map_error))?;
+                match current {
+                    Some(current) => {
+                        immutable::check_replacement(immutable, &current, &replacement)
+                            .map_err(context.wrapper(Self::map_error))?;
+                        let mut pinned = filter;
+                        pinned.insert("_id", current.get("_id").cloned().unwrap_or_default());
+                        pinned
+                    }
+                    None => filter,
+                }
+            }
+        };
+        check_references::<Self, E>(&replacement).await.map_err(context.wrapper(Self::map_error))?;
+        check_unique::<Self, E>(&replacement, filter.clone()).await.map_err(context.wrapper(Self::map_error))?;
+
+        let options = mongodb::options::FindOneAndReplaceOptions::builder()
+            .return_document(mongodb::options::ReturnDocument::Before)
+            .upsert(upsert)
+            .collation(Self::collation())
+            .build();
+        let replace = collection.find_one_and_replace(filter, replacement.clone(), options);
+        let previous = breaker::guard(Self::circuit_breaker(), replace)
+            .await
+            .map_err(duplicate_key::<Self, E>)
+            .map_err(context.wrapper(Self::map_error))?;
+
+        if let Some(previous) = &previous {
+            if !replacement.contains_key("_id") {
+                replacement.insert("_id", previous.get("_id").cloned().unwrap_or_default());
+            }
+            let propagate = propagate_mirrors_of::<Self, E>(&replacement, |x| !x.deferred);
+            propagate.await.map_err(context.wrapper(Self::map_error))?;
+        }
+        previous.map(bson::from_document).transpose().map_err(context.wrapper(Self::map_error))
+    }
+
+    // Duplicate IDs count once. Updates are atomic per document only, a failure can leave some of them updated.
+    async fn update_by_ids<I, D>(ids: &[I], data: D) -> Result<UpdateSummary, E>
+    where
+        I: IdOf<Self> + Sync,
+        D: serde::Serialize + Send,
+    {
+        let ids: std::collections::HashSet<&IdType> = ids.iter().map(|x| x.raw_id()).collect();
+        let filter = bson::doc! { "_id": { "$in": ids.iter().map(|x| bson::Bson::from(*x)).collect::<Vec<_>>() } };
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "update_by_ids").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let set = bson::to_bson(&data).map_err(context.wrapper(Self::map_error))?;
+        if let bson::Bson::Document(set) = &set {
+            check_references::<Self, E>(set).await.map_err(context.wrapper(Self::map_error))?;
+            check_unique::<Self, E>(set, filter.clone()).await.map_err(context.wrapper(Self::map_error))?;
+        }
+        let mut summary = Self::update_many_with(filter.clone(), bson::doc! { "$set": set }.into()).await?;
+        if summary.matched < ids.len() as u64 {
+            let options = mongodb::options::DistinctOptions::builder()
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let distinct = collection.distinct("_id", filter, options);
+            let found = breaker::guard(Self::circuit_breaker(), distinct)
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let found: std::collections::HashSet<IdType> = found.iter().filter_map(id_type::from_bson).collect();
+            summary.not_found = ids.into_iter().filter(|x| !found.contains(*x)).cloned().collect();
+        }
+
+        Ok(summary)
+    }
+
+    // One unordered `update` command per 1000 pairs, fewer when they'd take it over the 16MB BSON limit, statuses
+    // in the order of `pairs`. Only a command itself failing is an error, items rejected by the server come back
+    // as `UpsertStatus::Failed`, so do items naming a missing parent or taking a unique value, which aren't sent.
+    // Their lookups are batched, one per reference and one `$unionWith` aggregate (MongoDB 4.4+) per 100 unique
+    // checks for every 1000 pairs.
+    async fn upsert_many<D: serde::Serialize + Sync>(pairs: &[(bson::Document, D)]) -> Result<Vec<UpsertStatus>, E> {
+        const BATCH_SIZE: usize = 1000;
+
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "upsert_many");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let database = collection.client().database(&collection.namespace().db);
+        let collation = Self::collation();
+        let immutable = Self::immutable_fields();
+
+        let mut statuses = Vec::with_capacity(pairs.len());
+        for batch in pairs.chunks(BATCH_SIZE) {
+            let mut sets = Vec::with_capacity(batch.len());
+            for (filter, data) in batch {
+                shard::check_filter(Self::shard_key(), filter, false).map_err(context.wrapper(Self::map_error))?;
+                sets.push(bson::to_document(data).map_err(context.wrapper(Self::map_error))?);
+            }
+            let documents: Vec<&bson::Document> = sets.iter().collect();
+            let broken = check_references_many::<Self, E>(&documents).await;
+            let broken = broken.map_err(context.wrapper(Self::map_error))?;
+            let items: Vec<_> = sets.iter().zip(batch).map(|(set, (filter, _))| (set, filter)).collect();
+            let taken = check_unique_many::<Self, E>(&items).await.map_err(context.wrapper(Self::map_error))?;
+
+            // Indexes in `statuses` of the pairs sent
+            let first = statuses.len();
+            let mut sent = Vec::with_capacity(batch.len());
+            let mut updates = Vec::with_capacity(batch.len());
+            let rejected = broken.into_iter().zip(taken).map(|(x, y)| x.or(y));
+            for (index, (((filter, _), set), rejected)) in batch.iter().zip(sets).zip(rejected).enumerate() {
+                if let Some(error) = rejected {
+                    statuses.push(UpsertStatus::Failed { code: 0, message: error.to_string() });
+                    continue;
+                }
+                let statement =
+                    write::upsert_statement(filter.clone(), set, immutable, Self::generate_id, collation.as_ref());
+                updates.push(statement.map_err(context.wrapper(Self::map_error))?);
+                statuses.push(UpsertStatus::Updated);
+                sent.push(first + index);
+            }
+
+            let batches = write::batches(updates, BATCH_SIZE).map_err(context.wrapper(Self::map_error))?;
+            let mut sent = sent.into_iter();
+            for batch in batches {
+                let len = batch.len();
+                let command = write::update_command(collection.name(), batch, collection.write_concern());
+                let command = command.map_err(context.wrapper(Self::map_error))?;
+                let response = breaker::guard(Self::circuit_breaker(), database.run_command(command, None))
+                    .await
+                    .map_err(context.wrapper(Self::map_error))?;
+                let batch_statuses = write::upsert_statuses(&response, len).map_err(context.wrapper(Self::map_error))?;
+                for (index, status) in sent.by_ref().zip(batch_statuses) {
+                    statuses[index] = status;
+                }
+            }
+        }
+
+        Ok(statuses)
+    }
+
+    // Adds `by` to `field` and returns the new value in one atomic step, a missing field starts from zero
+    async fn increment_and_get<I, N>(id: &I, field: &str, by: N) -> Result<N, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        N: Into<bson::Bson> + serde::de::DeserializeOwned + Send,
+    {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "increment_and_get").id(id.raw_id().to_owned());
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let by_id = bson::doc! { "_id": id.raw_id() };
+        shard::check_filter(Self::shard_key(), &by_id, false).map_err(context.wrapper(Self::map_error))?;
+
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(mongodb::options::ReturnDocument::After)
+            .projection(bson::doc! { "_id": 0, field: 1 })
+            .build();
+        let update = bson::doc! { "$inc": { field: by.into() } };
+        immutable::check(Self::immutable_fields(), &update.clone().into()).map_err(context.wrapper(Self::map_error))?;
+        let update = collection.find_one_and_update(by_id, update, options);
+        let updated = breaker::guard(Self::circuit_breaker(), update)
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+
+        let not_found = Error::not_found_id::<Self>(id.raw_id().to_owned());
+        let updated = updated.ok_or_else(|| Self::map_error(context.wrap(not_found)))?;
+        let value = path_value(&updated, field).cloned().unwrap_or(bson::Bson::Null);
+        bson::from_bson(value).map_err(context.wrapper(Self::map_error))
+    }
+
+    #[cfg(feature = "decimal")]
+    async fn increment_decimal_by_id<I: IdOf<Self> + Sync + ?Sized>(
+        id: &I,
+        field: &str,
+        amount: &rust_decimal::Decimal,
+    ) -> Result<Self, E> {
+        let update = decimal::inc(field, amount);
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // ARRAYS ======================================================================================================
+    async fn push<I, A>(id: &I, field: Field<Self, A>, push: Push<A::Item>) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "push").id(id.raw_id().to_owned());
+        let push = push.to_bson().map_err(context.wrapper(Self::map_error))?;
+        let update = bson::doc! { "$push": { field.name(): push } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // Removes every element equal to `value`
+    async fn pull<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "pull").id(id.raw_id().to_owned());
+        let value = bson::to_bson(&value).map_err(context.wrapper(Self::map_error))?;
+        let update = bson::doc! { "$pull": { field.name(): value } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // Removes every element matching `condition`, a query on the element's fields or `{ "$gte": 5 }`-style
+    async fn pull_where<I, A>(id: &I, field: Field<Self, A>, condition: bson::Document) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+    {
+        let update = bson::doc! { "$pull": { field.name(): condition } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // Adds `value` unless the array already has an equal element, documents compare field by field in order
+    async fn add_to_set<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "add_to_set").id(id.raw_id().to_owned());
+        let value = bson::to_bson(&value).map_err(context.wrapper(Self::map_error))?;
+        let update = bson::doc! { "$addToSet": { field.name(): value } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // `pull` under the name that reads right next to `add_to_set`
+    async fn remove_from_set<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        Self::pull(id, field, value).await
+    }
+
+    // Removes and returns the first element, `None` when the array is empty or missing
+    async fn pop_first<I, A>(id: &I, field: Field<Self, A>) -> Result<Option<A::Item>, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::de::DeserializeOwned + Send,
+    {
+        Self::pop(id.raw_id(), field.name(), -1).await
+    }
+
+    async fn pop_last<I, A>(id: &I, field: Field<Self, A>) -> Result<Option<A::Item>, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::de::DeserializeOwned + Send,
+    {
+        Self::pop(id.raw_id(), field.name(), 1).await
+    }
+
+    // `$pop` returning the removed element: the document before the update, projected down to it
+    async fn pop<T: serde::de::DeserializeOwned>(id: &IdType, field: &str, pop: i32) -> Result<Option<T>, E> {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "pop").id(id.to_owned());
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let by_id = bson::doc! { "_id": id };
+        shard::check_filter(Self::shard_key(), &by_id, false).map_err(context.wrapper(Self::map_error))?;
+
+        let slice = if pop < 0 { 1 } else { -1 };
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(mongodb::options::ReturnDocument::Before)
+            .projection(bson::doc! { field: { "$slice": slice } })
+            .build();
+        let update = bson::doc! { "$pop": { field: pop } };
+        immutable::check(Self::immutable_fields(), &update.clone().into()).map_err(context.wrapper(Self::map_error))?;
+        let update = collection.find_one_and_update(by_id, update, options);
+        let before = breaker::guard(Self::circuit_breaker(), update)
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+
+        let not_found = Error::not_found_id::<Self>(id.to_owned());
+        let before = before.ok_or_else(|| Self::map_error(context.wrap(not_found)))?;
+        let popped = match path_value(&before, field).and_then(|x| x.as_array()) {
+            Some(x) if !x.is_empty() => x[0].clone(),
+            _ => return Ok(None),
+        };
+        bson::from_bson(popped).map(Some).map_err(context.wrapper(Self::map_error))
+    }
+
+    // Keeps the first `n` elements, `push` with `Push::each(vec![]).slice(-n)` keeps the last ones
+    async fn truncate_array<I, A>(id: &I, field: Field<Self, A>, n: u32) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+    {
+        let slice = i32::try_from(n).unwrap_or(i32::MAX);
+        let update = bson::doc! { "$push": { field.name(): { "$each": [], "$slice": slice } } };
+        let (item, _) = Self::update_one_with(bson::doc! { "_id": id.raw_id() }, update.into()).await?;
+        Ok(item)
+    }
+
+    // MAPS ========================================================================================================
+    async fn set_map_entry<I, M>(id: &I, field: Field<Self, M>, key: &str, value: M::Value) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        M: map::Map + Send,
+        M::Value: serde::Serialize + Send,
+    {
+        let context = ErrorContext::new(Self::collection().name(), "set_map_entry").id(id.raw_id().to_owned());
+        let value = bson::to_bson(&value).map_err(context.wrapper(Self::map_error))?;
+        let pipeline = map::entry_pipeline(field.name(), key, Some(value));
+        Self::update_one_pipeline(bson::doc! { "_id": id.raw_id() }, pipeline).await
+    }
+
+    async fn remove_map_entry<I, M>(id: &I, field: Field<Self, M>, key: &str) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        M: map::Map + Send,
+    {
+        let pipeline = map::entry_pipeline(field.name(), key, None);
+        Self::update_one_pipeline(bson::doc! { "_id": id.raw_id() }, pipeline).await
+    }
+
+    // SCHEDULED WRITES ============================================================================================
+    // `$set`s `changes` on the document at `at`, see `scheduled`. Returns the operation's ID.
+    async fn schedule_update<I, D>(id: &I, at: bson::DateTime, changes: D) -> Result<bson::oid::ObjectId, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        D: serde::Serialize + Send,
+    {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "schedule_update").id(id.raw_id().to_owned());
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let set = bson::to_document(&changes).map_err(context.wrapper(Self::map_error))?;
+        let update = bson::doc! { "$set": &set };
+        immutable::check(Self::immutable_fields(), &update.into()).map_err(context.wrapper(Self::map_error))?;
+
+        let operation = scheduled::ScheduledOp {
+            id: bson::oid::ObjectId::new(),
+            namespace: collection.namespace().to_string(),
+            target: id.raw_id().to_owned().into(),
+            set,
+            due_at: at,
+            status: scheduled::Status::Pending,
+            claimed_at: None,
+            error: None,
+            attempts: 0,
+        };
+        let database = collection.client().database(&collection.namespace().db);
+        let operations = database.collection::<scheduled::ScheduledOp>(scheduled::COLLECTION);
+        let insert = operations.insert_one(&operation, None);
+        breaker::guard(Self::circuit_breaker(), insert).await.map_err(context.wrapper(Self::map_error))?;
+        Ok(operation.id)
+    }
+
+    // `false` when the operation already ran or is running
+    async fn cancel_scheduled(operation: bson::oid::ObjectId) -> Result<bool, E> {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "cancel_scheduled").id(operation);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let database = collection.client().database(&collection.namespace().db);
+        let operations = database.collection::<bson::Document>(scheduled::COLLECTION);
+
+        let filter = bson::doc! { "_id": operation, "status": "pending" };
+        let update = operations.update_one(filter, bson::doc! { "$set": { "status": "cancelled" } }, None);
+        let result = breaker::guard(Self::circuit_breaker(), update)
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        Ok(result.modified_count == 1)
+    }
+
+    // Applies this model's due operations, returns how many were claimed. A failed operation is marked `failed`
+    // with its error and doesn't stop the others; one hitting an error that may go away is put back and ends the
+    // run with it, see `scheduled`.
+    async fn run_due_operations() -> Result<u64, E>
+    where
+        E: std::fmt::Display,
+    {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "run_due_operations");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        let database = collection.client().database(&collection.namespace().db);
+        let operations = database.collection::<scheduled::ScheduledOp>(scheduled::COLLECTION);
+        let namespace = collection.namespace().to_string();
+
+        let mut claimed = 0;
+        loop {
+            let claim = bson::doc! { "$set": { "status": "running", "claimed_at": bson::DateTime::now() } };
+            let options = mongodb::options::FindOneAndUpdateOptions::builder()
+                .sort(bson::doc! { "due_at": 1 })
+                .return_document(mongodb::options::ReturnDocument::After)
+                .build();
+            let next = operations.find_one_and_update(scheduled::due_filter(&namespace), claim, options);
+            let next = breaker::guard(Self::circuit_breaker(), next)
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let Some(operation) = next else {
+                break;
+            };
+            claimed += 1;
+
+            let outcome = match id_type::from_bson(&operation.target) {
+                Some(id) => {
+                    let filter = bson::doc! { "_id": id };
+                    update_set_unmapped::<Self, E>(filter, operation.set.into()).await.err()
+                }
+                None => Some(Error::InvalidId(format!("Target ID of unexpected type: {}", operation.target))),
+            };
+            let (done, retry) = match outcome {
+                None => (bson::doc! { "$set": { "status": "done" } }, None),
+                Some(error) if breaker::is_transient(&error) => {
+                    let message = error.to_string();
+                    let pending = bson::doc! {
+                        "$set": { "status": "pending", "error": message },
+                        "$unset": { "claimed_at": "" },
+                        "$inc": { "attempts": 1 },
+                    };
+                    (pending, Some(error))
+                }
+                Some(error) => {
+                    let message = Self::map_error(error).to_string();
+                    (bson::doc! { "$set": { "status": "failed", "error": message } }, None)
+                }
+            };
+            let finish = operations.update_one(bson::doc! { "_id": operation.id }, done, None);
+            breaker::guard(Self::circuit_breaker(), finish).await.map_err(context.wrapper(Self::map_error))?;
+            // Claiming on would pick the same operation up again
+            if let Some(error) = retry {
+                return Err(Self::map_error(context.wrap(error)));
+            }
+        }
+
+        Ok(claimed)
+    }
+
+    // DELETE ======================================================================================================
+    async fn delete_one(filter: bson::Document) -> Result<(), E> {
+        let collection = writes::<Self, E>();
+        let context = ErrorContext::new(collection.name(), "delete_one").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+        shard::check_filter(Self::shard_key(), &filter, true).map_err(context.wrapper(Self::map_error))?;
+
+        let options = mongodb::options::DeleteOptions::builder().collation(Self::collation()).build();
+        let delete_result = breaker::guard(Self::circuit_breaker(), collection.delete_one(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+
+        if delete_result.deleted_count != 1 {
+            return Err(Self::map_error(context.wrap(Error::DeleteFailed("No record deleted".to_string()))));
+        };
+
+        Ok(())
+    }
+
+    async fn delete_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<(), E> {
+        Self::delete_one(bson::doc! { "_id": id.raw_id() }).await
+    }
+
+    // Moves the documents whose `field` is before `cutoff` to `archive()`, `batch_size` at a time by `_id` and
+    // waiting `pause` between batches. `progress` gets the running totals after each batch, see `archive`.
+    async fn archive_older_than<P>(
+        field: &str,
+        cutoff: bson::DateTime,
+        batch_size: u32,
+        pause: std::time::Duration,
+        mut progress: P,
+    ) -> Result<ArchiveProgress, E>
+    where
+        P: FnMut(&ArchiveProgress) + Send,
+    {
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let archive = Self::archive();
+        let filter = bson::doc! { field: { "$lt": cutoff } };
+        let context = ErrorContext::new(collection.name(), "archive_older_than").filter(&filter);
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let mut done = ArchiveProgress::default();
+        loop {
+            let batch_filter = match &done.last_id {
+                bson::Bson::Null => filter.clone(),
+                last_id => bson::doc! { "$and": [&filter, { "_id": { "$gt": last_id } }] },
+            };
+            let find_options = mongodb::options::FindOptions::builder()
+                .sort(bson::doc! { "_id": 1 })
+                .limit(i64::from(batch_size.max(1)))
+                .selection_criteria(read_back().selection_criteria)
+                .build();
+            let cursor = breaker::guard(Self::circuit_breaker(), collection.find(batch_filter, find_options))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            let batch = cursor.try_collect::<Vec<bson::Document>>().await.map_err(context.wrapper(Self::map_error))?;
+            let Some(last_id) = batch.last().and_then(|x| x.get("_id")).cloned() else {
+                break;
+            };
+            // Copies replace what an interrupted run left, only the versions copied are deleted: a document
+            // updated in between stays live for the next run to copy again
+            let mut copies = Vec::with_capacity(batch.len());
+            let mut deletes = Vec::with_capacity(batch.len());
+            for document in batch {
+                let id = document.get("_id").cloned().unwrap_or_default();
+                let unchanged = bson::doc! { "$eq": ["$$ROOT", { "$literal": &document }] };
+                deletes.push(bson::doc! { "q": { "_id": &id, "$expr": unchanged }, "limit": 1 });
+                copies.push(bson::doc! { "q": { "_id": id }, "u": document, "upsert": true });
+            }
+            let copied = write::update_all(&archive, copies, Self::circuit_breaker()).await;
+            copied.map_err(context.wrapper(Self::map_error))?;
+            let deleted = write::delete_all(&collection, deletes, Self::circuit_breaker()).await;
+            let deleted = deleted.map_err(context.wrapper(Self::map_error))?;
+
+            done.archived += deleted;
+            done.last_id = last_id;
+            progress(&done);
+            if !pause.is_zero() {
+                tokio::time::sleep(pause).await;
+            }
+        }
+
+        Ok(done)
+    }
+
+    // IMPORT / EXPORT =============================================================================================
+    // One canonical Extended JSON document per line, so dumps can be streamed, diffed and split with plain tools
+    async fn export_extjson<W: std::io::Write + Send>(filter: bson::Document, writer: &mut W) -> Result<u64, E> {
+        Self::export_extjson_cancellable(filter, writer, &cancel::CancellationToken::new()).await
+    }
+
+    // Fails with `Error::Cancelled` once `token` is cancelled, after writing the documents read until then
+    async fn export_extjson_cancellable<W: std::io::Write + Send>(
+        filter: bson::Document,
+        writer: &mut W,
+        token: &cancel::CancellationToken,
+    ) -> Result<u64, E> {
+        let collection = Self::collection().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "export_extjson").filter(&filter);
+
+        let options = mongodb::options::FindOptions::builder()
+            .collation(Self::collation())
+            .batch_size(Self::batch_size())
+            .build();
+        let find = breaker::guard(Self::circuit_breaker(), collection.find(filter, options));
+        let find = cancel::or_cancelled(token, find);
+        let mut cursor = find.await.map_err(context.wrapper(Self::map_error))?;
+
+        let mut exported = 0;
+        loop {
+            let next = cancel::or_cancelled(token, cursor.try_next()).await;
+            let Some(document) = next.map_err(context.wrapper(Self::map_error))? else {
+                break;
+            };
+            if let Err(x) = bson::from_document::<Self>(document.clone()) {
+                let id = document.get("_id").cloned().unwrap_or(bson::Bson::Null);
+                let error = Error::ExportFailed(format!("Document {} doesn't match the model: {}", id, x));
+                return Err(Self::map_error(context.wrap(error)));
+            }
+
+            let line = bson::Bson::Document(document).into_canonical_extjson().to_string();
+            writeln!(writer, "{}", line).map_err(context.wrapper(Self::map_error))?;
+            exported += 1;
+        }
+        writer.flush().map_err(context.wrapper(Self::map_error))?;
+
+        Ok(exported)
+    }
+
+    // Writes every document matching `filter` without deserializing it into the model, see `export`. `progress`
+    // gets the running totals after each document, the final ones are returned.
+    async fn stream_to_writer<W, P>(
+        filter: bson::Document,
+        format: export::Format,
+        writer: &mut W,
+        mut progress: P,
+    ) -> Result<export::Progress, E>
+    where
+        W: std::io::Write + Send,
+        P: FnMut(export::Progress) + Send,
+    {
+        let collection = Self::collection().clone_with_type::<bson::RawDocumentBuf>();
+        let context = ErrorContext::new(collection.name(), "stream_to_writer").filter(&filter);
+
+        let options = mongodb::options::FindOptions::builder()
+            .collation(Self::collation())
+            .selection_criteria(Self::read_preference())
+            .read_concern(Self::read_concern())
+            .batch_size(Self::batch_size())
+            .build();
+        let mut cursor = breaker::guard(Self::circuit_breaker(), collection.find(filter, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+
+        let mut totals = export::Progress::default();
+        while let Some(document) = cursor.try_next().await.map_err(context.wrapper(Self::map_error))? {
+            let bytes = match format {
+                export::Format::Bson => document.into_bytes(),
+                export::Format::Ndjson | export::Format::ExtJson => {
+                    let document = document
+                        .to_document()
+                        .map_err(|x| Self::map_error(context.wrap(Error::ExportFailed(x.to_string()))))?;
+                    let json = match format {
+                        export::Format::Ndjson => bson::Bson::Document(document).into_relaxed_extjson(),
+                        _ => bson::Bson::Document(document).into_canonical_extjson(),
+                    };
+                    format!("{}\n", json).into_bytes()
+                }
+            };
+            writer.write_all(&bytes).map_err(context.wrapper(Self::map_error))?;
+
+            totals.documents += 1;
+            totals.bytes += bytes.len() as u64;
+            progress(totals);
+        }
+        writer.flush().map_err(context.wrapper(Self::map_error))?;
+
+        Ok(totals)
+    }
+
+    async fn import_extjson<R: std::io::BufRead + Send>(reader: R) -> Result<u64, E> {
+        Self::import_extjson_cancellable(reader, &cancel::CancellationToken::new()).await
+    }
+
+    // Fails with `Error::Cancelled` once `token` is cancelled, the batches inserted until then stay
+    async fn import_extjson_cancellable<R: std::io::BufRead + Send>(
+        reader: R,
+        token: &cancel::CancellationToken,
+    ) -> Result<u64, E> {
+        const BATCH_SIZE: usize = 1000;
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "import_extjson");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let mut imported = 0;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for (index, line) in reader.lines().enumerate() {
+            if token.is_cancelled() {
+                return Err(Self::map_error(context.wrap(Error::Cancelled)));
+            }
+            let line = line.map_err(context.wrapper(Self::map_error))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let document = serde_json::from_str::<serde_json::Value>(&line)
+                .map_err(|x| x.to_string())
+                .and_then(|x| bson::Bson::try_from(x).map_err(|x| x.to_string()))
+                .and_then(|x| match x {
+                    bson::Bson::Document(document) => Ok(document),
+                    _ => Err("not a document".to_string()),
+                })
+                .and_then(|x| bson::from_document::<Self>(x.clone()).map(|_| x).map_err(|x| x.to_string()))
+                .and_then(|x| shard::check_document(Self::shard_key(), &x).map(|_| x).map_err(|x| x.to_string()))
+                .map_err(|x| Self::map_error(context.wrap(Error::ImportFailed(format!("Line {}: {}", index + 1, x)))))?;
+
+            batch.push(document);
+            if batch.len() == BATCH_SIZE {
+                let insert = breaker::guard(Self::circuit_breaker(), collection.insert_many(batch.drain(..), None));
+                cancel::or_cancelled(token, insert).await.map_err(context.wrapper(Self::map_error))?;
+                imported += BATCH_SIZE as u64;
+            }
+        }
+        if !batch.is_empty() {
+            let remaining = batch.len() as u64;
+            let insert = breaker::guard(Self::circuit_breaker(), collection.insert_many(batch, None));
+            cancel::or_cancelled(token, insert).await.map_err(context.wrapper(Self::map_error))?;
+            imported += remaining;
+        }
+
+        Ok(imported)
+    }
+
+    // BACKUP / RESTORE ============================================================================================
+    // Gzipped stream of raw BSON: a header document with the index definitions, then every document as stored.
+    // Restore expects an empty collection, it recreates the indexes first and then inserts the documents.
+    #[cfg(feature = "backup")]
+    async fn backup_to<P: AsRef<std::path::Path> + Send>(path: P) -> Result<u64, E> {
+        use std::io::Write;
+
+        let collection = Self::collection().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "backup_to");
+
+        let mut indexes = Vec::new();
+        let mut cursor = breaker::guard(Self::circuit_breaker(), collection.list_indexes(None))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        while let Some(index) = cursor.try_next().await.map_err(context.wrapper(Self::map_error))? {
+            indexes.push(bson::to_bson(&index).map_err(context.wrapper(Self::map_error))?);
+        }
+
+        let file = std::fs::File::create(path).map_err(context.wrapper(Self::map_error))?;
+        let mut writer = flate2::write::GzEncoder::new(std::io::BufWriter::new(file), flate2::Compression::default());
+
+        let header = bson::doc! { "format": BACKUP_FORMAT, "collection": collection.name(), "indexes": indexes };
+        header.to_writer(&mut writer).map_err(context.wrapper(Self::map_error))?;
+
+        let mut backed_up = 0;
+        let options = mongodb::options::FindOptions::builder().batch_size(Self::batch_size()).build();
+        let mut cursor = breaker::guard(Self::circuit_breaker(), collection.find(None, options))
+            .await
+            .map_err(context.wrapper(Self::map_error))?;
+        while let Some(document) = cursor.try_next().await.map_err(context.wrapper(Self::map_error))? {
+            document.to_writer(&mut writer).map_err(context.wrapper(Self::map_error))?;
+            backed_up += 1;
+        }
+        writer.finish().and_then(|mut x| x.flush()).map_err(context.wrapper(Self::map_error))?;
+
+        Ok(backed_up)
+    }
+
+    #[cfg(feature = "backup")]
+    async fn restore_from<P: AsRef<std::path::Path> + Send>(path: P) -> Result<u64, E> {
+        use std::io::BufRead;
+        const BATCH_SIZE: usize = 1000;
+
+        let collection = writes::<Self, E>().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "restore_from");
+        writable::<Self, E>().map_err(context.wrapper(Self::map_error))?;
+
+        let file = std::fs::File::open(path).map_err(context.wrapper(Self::map_error))?;
+        let mut reader = std::io::BufReader::new(flate2::read::GzDecoder::new(file));
+
+        let header = bson::Document::from_reader(&mut reader).map_err(context.wrapper(Self::map_error))?;
+        if header.get_str("format") != Ok(BACKUP_FORMAT) {
+            return Err(Self::map_error(context.wrap(Error::RestoreFailed("Not a backup file".to_string()))));
+        }
+
+        let mut indexes = Vec::new();
+        let declared = header
+            .get_array("indexes")
+            .map_err(|x| Self::map_error(context.wrap(Error::RestoreFailed(x.to_string()))))?;
+        for index in declared {
+            let index: mongodb::IndexModel = bson::from_bson(index.clone()).map_err(context.wrapper(Self::map_error))?;
+            let is_id_index = index.options.as_ref().and_then(|x| x.name.as_deref()) == Some("_id_");
+            if !is_id_index {
+                indexes.push(index);
+            }
+        }
+        if !indexes.is_empty() {
+            breaker::guard(Self::circuit_breaker(), collection.create_indexes(indexes, None))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+        }
+
+        let mut restored = 0;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        while !reader.fill_buf().map_err(context.wrapper(Self::map_error))?.is_empty() {
+            batch.push(bson::Document::from_reader(&mut reader).map_err(context.wrapper(Self::map_error))?);
+            if batch.len() == BATCH_SIZE {
+                breaker::guard(Self::circuit_breaker(), collection.insert_many(batch.drain(..), None))
+                    .await
+                    .map_err(context.wrapper(Self::map_error))?;
+                restored += BATCH_SIZE as u64;
+            }
+        }
+        if !batch.is_empty() {
+            let remaining = batch.len() as u64;
+            breaker::guard(Self::circuit_breaker(), collection.insert_many(batch, None))
+                .await
+                .map_err(context.wrapper(Self::map_error))?;
+            restored += remaining;
+        }
+
+        Ok(restored)
+    }
+
+    // Instance Methods
+    async fn create(&self) -> Result<Self, E> {
+        Self::create_one(self).await
+    }
+    async fn update<D: serde::Serialize + Send>(&self, data: D) -> Result<Self, E> {
+        let filter = self.scoped_filter()?;
+        Self::update_one(filter, data).await
+    }
+    async fn delete(&self) -> Result<(), E> {
+        let filter = self.scoped_filter()?;
+        Self::delete_one(filter).await
+    }
+    // This document as stored now, read from its shard only
+    async fn reload(&self) -> Result<Self, E> {
+        let filter = self.scoped_filter()?;
+        Self::find_one_strict(filter).await
+    }
+    // Fails with `Error::Validation` listing the `fields` another document already has this document's value of
+    async fn validate_unique(&self, fields: &[&str]) -> Result<(), E> {
+        let collection = Self::collection().clone_with_type::<bson::Document>();
+        let context = ErrorContext::new(collection.name(), "validate_unique").id(self.id_value().to_owned());
+        let document = bson::to_document(self).map_err(context.wrapper(Self::map_error))?;
+        let exclude = bson::doc! { "_id": self.id_value() };
+        // With the combinations ending in one of `fields`
+        let together = Self::unique_together().iter().filter(|x| x.last().is_some_and(|x| fields.contains(x)));
+        let groups: Vec<&[&str]> = fields.iter().map(std::slice::from_ref).chain(together.copied()).collect();
+        let (collation, breaker) = (Self::collation(), Self::circuit_breaker());
+        let errors = unique::conflicts(&collection, &groups, &document, &exclude, collation, breaker);
+        match errors.await.map_err(context.wrapper(Self::map_error))? {
+            x if x.is_empty() => Ok(()),
+            x => Err(Self::map_error(context.wrap(Error::Validation(x)))),
+        }
+    }
+    // This document without its `sensitive_fields`
+    fn to_public_bson(&self) -> Result<bson::Document, E> {
+        let context = ErrorContext::new(Self::collection().name(), "to_public_bson").id(self.id_value().to_owned());
+        let mut document = bson::to_document(self).map_err(context.wrapper(Self::map_error))?;
+        for field in Self::sensitive_fields() {
+            redact::remove(&mut document, field);
+        }
+        Ok(document)
+    }
+    // Copies this document's mirrored fields into its dependents now, deferred mirrors included
+    async fn apply_mirrors(&self) -> Result<u64, E> {
+        let context = ErrorContext::new(Self::collection().name(), "apply_mirrors").id(self.id_value().to_owned());
+        propagate_mirrors::<Self, E>(self, |_| true).await.map_err(context.wrapper(Self::map_error))
     }
 }
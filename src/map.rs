@@ -0,0 +1,38 @@
+// MAPS ============================================================================================================
+// Entries of `HashMap<String, T>` / `BTreeMap<String, T>` fields, set and removed one at a time:
+//
+//     User::set_map_entry(&id, User::fields().preferences, "ui.theme", "dark".to_string()).await?;
+//     User::remove_map_entry(&id, User::fields().preferences, "ui.theme").await?;
+//
+// A plain `$set: { "preferences.ui.theme": .. }` would write `{ ui: { theme: .. } }` and a key starting with `$`
+// would be rejected, so keys go through `$setField` / `$unsetField` in an update pipeline, which take them
+// literally (MongoDB 5.0+). Keys are stored as they are, the way serde writes the map.
+
+// Field types stored as documents with one field per key
+pub trait Map {
+    type Value;
+}
+
+impl<V, S> Map for std::collections::HashMap<String, V, S> {
+    type Value = V;
+}
+
+impl<V> Map for std::collections::BTreeMap<String, V> {
+    type Value = V;
+}
+
+impl<M: Map> Map for Option<M> {
+    type Value = M::Value;
+}
+
+// Pipeline setting `key` of the document at `field` to `value`, `None` removes it. A missing or null map starts
+// out empty.
+pub(crate) fn entry_pipeline(field: &str, key: &str, value: Option<bson::Bson>) -> Vec<bson::Document> {
+    let input = bson::doc! { "$ifNull": [format!("${}", field), {}] };
+    let key = bson::doc! { "$literal": key };
+    let map = match value {
+        Some(value) => bson::doc! { "$setField": { "field": key, "input": input, "value": { "$literal": value } } },
+        None => bson::doc! { "$unsetField": { "field": key, "input": input } },
+    };
+    vec![bson::doc! { "$set": { field: map } }]
+}
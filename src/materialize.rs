@@ -0,0 +1,119 @@
+// MATERIALIZED VIEWS ==============================================================================================
+// `materialize_into::<Target>` runs an aggregation over this model and `$merge`s the results into `Target`'s
+// collection, the pipeline's `_id`s as keys: existing rows are replaced, new ones inserted, rows the pipeline no
+// longer produces stay. Each run is recorded in `_materializations` so jobs and dashboards can tell how fresh a
+// reporting table is:
+//
+//     let pipeline = vec![doc! { "$group": { "_id": "$country", "revenue": { "$sum": "$total" } } }];
+//     if Order::last_materialized::<RevenueByCountry>().await?.is_none_or(|x| x.is_due()) {
+//         Order::materialize_into::<RevenueByCountry>(pipeline, Duration::from_secs(3600)).await?;
+//     }
+//
+// `aggregate_out` writes a pipeline's results to any collection of the database, either replacing it (`$out`) or
+// merged into it with explicit conflict handling:
+//
+//     let merge = Merge::on(&["sku"]).when_matched(WhenMatched::Merge).when_not_matched(WhenNotMatched::Discard);
+//     Product::aggregate_out(pipeline, "catalog", Output::Merge(merge)).await?;
+//
+// Merging on other fields than `_id` needs a unique index on them in the target collection.
+
+pub(crate) const COLLECTION: &str = "_materializations";
+
+// One `{ _id: "<db>.<target>", source, refreshed_at, took_ms, every_ms }` document per target
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Refresh {
+    #[serde(rename = "_id")]
+    pub target: String,
+    pub source: String,
+    pub refreshed_at: bson::DateTime,
+    pub took_ms: u64,
+    // How often the caller meant to refresh, recorded as given
+    pub every_ms: u64,
+}
+
+impl Refresh {
+    // The hinted interval has passed since the last refresh
+    pub fn is_due(&self) -> bool {
+        let age = bson::DateTime::now().timestamp_millis() - self.refreshed_at.timestamp_millis();
+        age >= self.every_ms as i64
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    // `$out`: the results replace the target's documents in one step, its indexes are kept
+    Replace,
+    Merge(Merge),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    pub on: Vec<String>,
+    pub when_matched: WhenMatched,
+    pub when_not_matched: WhenNotMatched,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenMatched {
+    Replace,
+    KeepExisting,
+    // Top-level fields of the result overwrite the existing document's, others stay
+    Merge,
+    // The aggregation fails, writes made before the conflict stay
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenNotMatched {
+    Insert,
+    Discard,
+    Fail,
+}
+
+// On `_id`, replacing matches and inserting the rest
+impl Default for Merge {
+    fn default() -> Self {
+        let on = vec!["_id".to_string()];
+        Self { on, when_matched: WhenMatched::Replace, when_not_matched: WhenNotMatched::Insert }
+    }
+}
+
+impl Merge {
+    pub fn on(fields: &[&str]) -> Self {
+        Self { on: fields.iter().map(|x| x.to_string()).collect(), ..Self::default() }
+    }
+
+    pub fn when_matched(mut self, when_matched: WhenMatched) -> Self {
+        self.when_matched = when_matched;
+        self
+    }
+
+    pub fn when_not_matched(mut self, when_not_matched: WhenNotMatched) -> Self {
+        self.when_not_matched = when_not_matched;
+        self
+    }
+}
+
+impl Output {
+    pub fn stage(&self, target: &mongodb::Namespace) -> bson::Document {
+        let into = bson::doc! { "db": &target.db, "coll": &target.coll };
+        let merge = match self {
+            Self::Replace => return bson::doc! { "$out": into },
+            Self::Merge(x) => x,
+        };
+        let when_matched = match merge.when_matched {
+            WhenMatched::Replace => "replace",
+            WhenMatched::KeepExisting => "keepExisting",
+            WhenMatched::Merge => "merge",
+            WhenMatched::Fail => "fail",
+        };
+        let when_not_matched = match merge.when_not_matched {
+            WhenNotMatched::Insert => "insert",
+            WhenNotMatched::Discard => "discard",
+            WhenNotMatched::Fail => "fail",
+        };
+        bson::doc! {
+            "$merge": { "into": into, "on": &merge.on, "whenMatched": when_matched, "whenNotMatched": when_not_matched }
+        }
+    }
+}
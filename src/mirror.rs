@@ -0,0 +1,73 @@
+// DENORMALIZED FIELDS =============================================================================================
+// A source model lists the copies of its fields kept in other collections, e.g. `orders.customer_name` mirroring
+// `customers.name` for the orders whose `customer_id` is the customer's `_id`:
+//
+//     impl RustMongoDBModelMethods for Customer {
+//         fn mirrors() -> Vec<Mirror> {
+//             vec![Mirror::new("orders", "customer_id").field("name", "customer_name")]
+//         }
+//     }
+//
+// Mirrors update as part of `update_one_with` and everything built on it (`update_one`, `update_by_id`,
+// `set_field`, the array and map helpers), once the source document changed. `deferred()` ones are left to
+// `sync_mirrors()` instead, a change stream consumer that also catches writes made around this crate:
+//
+//     tokio::spawn(Customer::sync_mirrors().try_for_each(|_| async { Ok(()) }));
+//
+// Dependents already holding the current values aren't written again. Index the foreign key of the dependents.
+
+use crate::{breaker, CircuitBreaker, Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mirror {
+    // Collection of the copies, in the source's database
+    pub dependents: String,
+    // Field of the dependents holding the source `_id`
+    pub foreign_key: String,
+    // (source field, dependent field) pairs, dotted paths work on both sides
+    pub fields: Vec<(String, String)>,
+    pub deferred: bool,
+}
+
+impl Mirror {
+    pub fn new(dependents: &str, foreign_key: &str) -> Self {
+        let (dependents, foreign_key) = (dependents.to_string(), foreign_key.to_string());
+        Self { dependents, foreign_key, fields: Vec::new(), deferred: false }
+    }
+
+    pub fn field(mut self, source: &str, copy: &str) -> Self {
+        self.fields.push((source.to_string(), copy.to_string()));
+        self
+    }
+
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+}
+
+// Copies the mirrored fields of `source` into its dependents, returns how many changed
+pub(crate) async fn propagate<'a>(
+    database: &mongodb::Database,
+    mirrors: impl Iterator<Item = &'a Mirror> + Send,
+    source: &bson::Document,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<u64, Error> {
+    let id = source.get("_id").cloned().unwrap_or_default();
+    let mut modified = 0;
+    for mirror in mirrors.filter(|x| !x.fields.is_empty()) {
+        let mut set = bson::Document::new();
+        let mut stale = Vec::new();
+        for (from, to) in &mirror.fields {
+            let value = crate::path_value(source, from).cloned().unwrap_or(bson::Bson::Null);
+            stale.push(bson::doc! { to: { "$ne": &value } });
+            set.insert(to, value);
+        }
+
+        let filter = bson::doc! { &mirror.foreign_key: &id, "$or": stale };
+        let dependents = database.collection::<bson::Document>(&mirror.dependents);
+        let update = dependents.update_many(filter, bson::doc! { "$set": set }, None);
+        modified += breaker::guard(breaker, update).await?.modified_count;
+    }
+    Ok(modified)
+}
@@ -0,0 +1,63 @@
+// OPENAPI =========================================================================================================
+// utoipa schemas that describe the JSON the API actually returns (feature "utoipa"). `Page<T>` and `Direction`
+// derive `ToSchema`, `Id<M>` shares the `Id` component and raw `IdType` fields point at `IdSchema`:
+//
+//     #[derive(Serialize, Deserialize, ToSchema)]
+//     struct User {
+//         #[serde(rename = "_id")]
+//         #[schema(value_type = rust_mongodb_model_methods::openapi::IdSchema)]
+//         id: IdType,
+//         name: String,
+//     }
+//
+//     #[utoipa::path(get, path = "/users", responses((status = 200, body = Page<User>)))]
+//
+// The schema follows what serde_json writes for the selected ID type: UUIDs are strings with the `uuid` format,
+// ObjectIds the extended JSON `{"$oid": "<24 hex characters>"}` object and string IDs plain strings.
+
+#[cfg(all(feature = "uuid_as_id", not(feature = "oid_as_id"), not(feature = "string_as_id")))]
+use utoipa::openapi::schema::SchemaFormat;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, Type};
+use utoipa::openapi::RefOr;
+use utoipa::{PartialSchema, ToSchema};
+
+use crate::Id;
+
+pub struct IdSchema;
+
+impl PartialSchema for IdSchema {
+    fn schema() -> RefOr<Schema> {
+        let builder = ObjectBuilder::new().schema_type(Type::String);
+
+        // serde_json writes an ObjectId as extended JSON, `{"$oid": "665f1f77bcf86cd799439011"}`
+        #[cfg(all(feature = "oid_as_id", not(feature = "string_as_id")))]
+        let builder = ObjectBuilder::new()
+            .schema_type(Type::Object)
+            .property("$oid", builder.pattern(Some("^[0-9a-f]{24}$")))
+            .required("$oid");
+
+        #[cfg(all(feature = "uuid_as_id", not(feature = "oid_as_id"), not(feature = "string_as_id")))]
+        let builder = builder.format(Some(SchemaFormat::Custom("uuid".to_string())));
+
+        builder.into()
+    }
+}
+
+impl ToSchema for IdSchema {
+    fn name() -> std::borrow::Cow<'static, str> {
+        "Id".into()
+    }
+}
+
+// Every `Id<M>` shares the one `Id` component
+impl<M> PartialSchema for Id<M> {
+    fn schema() -> RefOr<Schema> {
+        IdSchema::schema()
+    }
+}
+
+impl<M> ToSchema for Id<M> {
+    fn name() -> std::borrow::Cow<'static, str> {
+        IdSchema::name()
+    }
+}
@@ -0,0 +1,120 @@
+// PAGINATION ======================================================================================================
+
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub struct PageOptions {
+    // 1-based page number
+    pub page: u64,
+    pub per_page: u64,
+    pub sort: Option<bson::Document>,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self { page: 1, per_page: 20, sort: None }
+    }
+}
+
+impl PageOptions {
+    pub fn new(page: u64, per_page: u64) -> Self {
+        Self { page, per_page, sort: None }
+    }
+
+    pub fn sort(mut self, sort: bson::Document) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    // `Error::InvalidParams` when the documents before this page don't fit in the `i64` the server takes
+    pub fn skip(&self) -> Result<u64, Error> {
+        let skip = self.page.saturating_sub(1).checked_mul(self.per_page).filter(|x| i64::try_from(*x).is_ok());
+        skip.ok_or_else(|| Error::InvalidParams(format!("`page` {} is too large", self.page)))
+    }
+
+    // Stages selecting this page, used as the data branch of a `$facet`. The server rejects `$limit: 0`.
+    pub(crate) fn facet_stages(&self) -> Result<Vec<bson::Document>, Error> {
+        if self.per_page == 0 {
+            return Err(Error::InvalidParams("`per_page` must be at least 1".to_string()));
+        }
+        let skip = self.skip()? as i64;
+        let limit = i64::try_from(self.per_page)
+            .map_err(|_| Error::InvalidParams(format!("`per_page` {} is too large", self.per_page)))?;
+        let mut stages = Vec::new();
+        if let Some(sort) = self.sort.clone() {
+            stages.push(bson::doc! { "$sort": sort });
+        }
+        stages.push(bson::doc! { "$skip": skip });
+        stages.push(bson::doc! { "$limit": limit });
+        Ok(stages)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page: u64,
+    pub per_page: u64,
+}
+
+impl<T> Page<T> {
+    pub fn total_pages(&self) -> u64 {
+        if self.per_page == 0 {
+            return 0;
+        }
+        self.total.div_ceil(self.per_page)
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages()
+    }
+}
+
+impl<T: serde::de::DeserializeOwned> Page<T> {
+    // Reads the `{ data: [...], total: [{ count }] }` shape produced by a `$facet` stage
+    pub(crate) fn from_facet(result: &bson::Document, options: &PageOptions) -> Result<Self, bson::de::Error> {
+        let items = match result.get_array("data") {
+            Ok(data) => data
+                .iter()
+                .filter_map(|x| x.as_document().cloned())
+                .map(bson::from_document::<T>)
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(_) => Vec::new(),
+        };
+        let total = result
+            .get_array("total")
+            .ok()
+            .and_then(|x| x.first())
+            .and_then(|x| x.as_document())
+            .and_then(|x| x.get("count"))
+            .and_then(crate::as_u64)
+            .unwrap_or(0);
+
+        Ok(Self { items, total, page: options.page, per_page: options.per_page })
+    }
+}
+
+// FACETS ==========================================================================================================
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FacetBucket {
+    pub value: bson::Bson,
+    pub count: u64,
+}
+
+impl FacetBucket {
+    pub(crate) fn from_document(document: &bson::Document) -> Self {
+        Self {
+            value: document.get("_id").cloned().unwrap_or(bson::Bson::Null),
+            count: document.get("count").and_then(crate::as_u64).unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FacetedResults<T> {
+    pub page: Page<T>,
+    // Buckets per requested field, most frequent value first
+    pub facets: std::collections::BTreeMap<String, Vec<FacetBucket>>,
+}
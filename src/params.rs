@@ -0,0 +1,133 @@
+// LIST PARAMS =====================================================================================================
+// Query string parameters for list endpoints: `?page=2&per_page=50&sort=created_at&direction=desc&status=active`.
+// Everything besides the four paging keys is a filter and must be whitelisted by the model's `list_rules()`:
+//
+//     fn list_rules() -> ListRules {
+//         ListRules { sortable: &["created_at", "name"], filterable: &["status"], ..ListRules::default() }
+//     }
+//
+//     async fn list(Query(params): Query<ListParams>) -> Result<Json<Page<User>>, Error> {
+//         Ok(Json(User::find_list(&params).await?))
+//     }
+//
+// Filter values are matched as strings.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Error, PageOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl Direction {
+    pub fn to_bson(self) -> bson::Bson {
+        match self {
+            Self::Asc => bson::Bson::Int32(1),
+            Self::Desc => bson::Bson::Int32(-1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ListRules {
+    pub default_per_page: u64,
+    pub max_per_page: u64,
+    pub sortable: &'static [&'static str],
+    pub filterable: &'static [&'static str],
+    pub default_sort: Option<(&'static str, Direction)>,
+}
+
+impl Default for ListRules {
+    fn default() -> Self {
+        Self { default_per_page: 20, max_per_page: 100, sortable: &[], filterable: &[], default_sort: None }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct ListParams {
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+    pub sort: Option<String>,
+    pub direction: Option<Direction>,
+    pub filters: BTreeMap<String, String>,
+}
+
+// Parsed by hand rather than with `#[serde(flatten)]`, which can't read numbers out of query strings
+impl TryFrom<HashMap<String, String>> for ListParams {
+    type Error = String;
+
+    fn try_from(mut query: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let number = |key: &str, value: Option<String>| {
+            value.map(|x| x.parse::<u64>().map_err(|_| format!("`{}` must be a positive integer", key))).transpose()
+        };
+
+        let page = number("page", query.remove("page"))?;
+        let per_page = number("per_page", query.remove("per_page"))?;
+        let sort = query.remove("sort").filter(|x| !x.is_empty());
+        let direction = match query.remove("direction").as_deref() {
+            None | Some("") => None,
+            Some("asc") => Some(Direction::Asc),
+            Some("desc") => Some(Direction::Desc),
+            Some(_) => return Err("`direction` must be `asc` or `desc`".to_string()),
+        };
+
+        Ok(Self { page, per_page, sort, direction, filters: query.into_iter().collect() })
+    }
+}
+
+impl ListParams {
+    pub fn filter(&self, rules: &ListRules) -> Result<bson::Document, Error> {
+        let mut filter = bson::Document::new();
+        for (field, value) in &self.filters {
+            if !rules.filterable.contains(&field.as_str()) {
+                return Err(Error::InvalidParams(format!("Filtering by `{}` is not allowed", field)));
+            }
+            filter.insert(field.clone(), value.clone());
+        }
+        Ok(filter)
+    }
+
+    pub fn sort_document(&self, rules: &ListRules) -> Result<Option<bson::Document>, Error> {
+        let direction = self.direction.unwrap_or_default();
+        match (&self.sort, rules.default_sort) {
+            (Some(field), _) if !rules.sortable.contains(&field.as_str()) => {
+                Err(Error::InvalidParams(format!("Sorting by `{}` is not allowed", field)))
+            }
+            (Some(field), _) => Ok(Some(bson::doc! { field: direction.to_bson() })),
+            (None, Some((field, direction))) => Ok(Some(bson::doc! { field: direction.to_bson() })),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn page_options(&self, rules: &ListRules) -> Result<PageOptions, Error> {
+        let page = self.page.unwrap_or(1);
+        let per_page = self.per_page.unwrap_or(rules.default_per_page);
+        if page == 0 {
+            return Err(Error::InvalidParams("`page` starts at 1".to_string()));
+        }
+        if per_page == 0 || per_page > rules.max_per_page {
+            return Err(Error::InvalidParams(format!("`per_page` must be between 1 and {}", rules.max_per_page)));
+        }
+
+        let options = PageOptions { page, per_page, sort: self.sort_document(rules)? };
+        options.skip()?;
+        Ok(options)
+    }
+
+    pub fn find_options(&self, rules: &ListRules) -> Result<mongodb::options::FindOptions, Error> {
+        let page = self.page_options(rules)?;
+        let options = mongodb::options::FindOptions::builder()
+            .sort(page.sort.clone())
+            .skip(page.skip()?)
+            .limit(page.per_page as i64)
+            .build();
+        Ok(options)
+    }
+}
@@ -0,0 +1,18 @@
+// PRELUDE =========================================================================================================
+// Everything a model file usually needs:
+//
+//     use rust_mongodb_model_methods::prelude::*;
+//
+// The blocking trait stays out of it, import `sync::RustMongoDBModelMethodsSync` instead of the prelude's
+// async trait when using the `sync` feature. The option types are the ones the `_with` methods take.
+
+pub use crate::bson::{self, doc};
+pub use crate::read::Analytics;
+pub use mongodb::options::{
+    AggregateOptions, Collation, CountOptions, DeleteOptions, FindOneAndReplaceOptions, FindOneAndUpdateOptions,
+    FindOneOptions, FindOptions, ReplaceOptions, ReturnDocument, UpdateModifications, UpdateOptions,
+};
+pub use crate::{
+    Accumulator, BelongsTo, DerivedFields, Direction, Error, ErrorContext, Field, FilterExpr, Id, IdOf, IdType, Index,
+    IndexKind, ListParams, ListRules, Page, PageOptions, Push, Result, RustMongoDBModelMethods, UpsertStatus,
+};
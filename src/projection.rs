@@ -0,0 +1,70 @@
+// PROJECTIONS =====================================================================================================
+// `find_as::<P>` reads into a smaller struct than the model, asking the server only for the fields it needs:
+//
+//     #[derive(Deserialize)]
+//     struct UserSummary { #[serde(rename = "_id")] id: IdType, name: String, email: String }
+//
+//     let rows: Vec<UserSummary> = User::find_as(doc! { "active": true }, None).await?;
+//
+// Without an explicit projection one is derived from the struct's serde field names (renames included). Structs
+// serde can't list the fields of (`#[serde(flatten)]`, maps, enums) get the whole document.
+//
+// With the `derive` feature, `#[derive(Projection)]` ties a view struct to its model and checks its fields against
+// the model's at compile time, see `find_projected`.
+
+use serde::de::value::Error as IntrospectError;
+
+#[cfg(feature = "derive")]
+pub use rust_mongodb_model_methods_derive::Projection;
+
+// View of the model `Of`, usually derived
+pub trait Projection: serde::de::DeserializeOwned + Send + Sync + Unpin {
+    type Of;
+
+    fn projection() -> bson::Document {
+        projection_of::<Self>().unwrap_or_default()
+    }
+}
+
+// `{ field: 1, ... }` for every field of `P`, `_id` only if `P` has it
+pub fn projection_of<P: serde::de::DeserializeOwned>() -> Option<bson::Document> {
+    let fields = fields_of::<P>()?;
+    let mut projection: bson::Document = fields.iter().map(|x| (x.to_string(), bson::Bson::Int32(1))).collect();
+    if !fields.contains(&"_id") {
+        projection.insert("_id", 0);
+    }
+    Some(projection)
+}
+
+// The field names serde expects for `P`, read without deserializing anything
+pub fn fields_of<P: serde::de::DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = P::deserialize(FieldsOf(&mut fields));
+    fields.filter(|x| !x.is_empty())
+}
+
+// Deserializer that fails on everything, after writing down the fields it was asked for
+struct FieldsOf<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> serde::Deserializer<'de> for FieldsOf<'_> {
+    type Error = IntrospectError;
+
+    fn deserialize_any<V: serde::de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(serde::de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: serde::de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(serde::de::Error::custom("fields read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
@@ -0,0 +1,195 @@
+// QUERY LOGGING ===================================================================================================
+// `QueryLog` is a command monitor writing one line per command the client sends, with the values of PII fields
+// masked, so filters and update documents can be logged for debugging and still pass a compliance review:
+//
+//     let log = QueryLog::new(|x| tracing::debug!("{x}")).pii_of::<User>().pii("orders", &["shipping.address"]);
+//     options.command_event_handler = Some(Arc::new(log));
+//     rms::init(options, "shop")?;
+//
+//     find `shop.users` #12 {"find":"users","filter":{"email":"<masked>","active":true},...}
+//     find #12 done in 3ms
+//
+// Fields are matched by their last path segment anywhere in the commands on their collection, more is masked
+// rather than less: `email` covers `filter.email`, `updates.u.$set.email`, `profile.email` of inserted documents
+// and the operands of `{ email: { $in: [...] } }`. `pii_of` takes the model's `pii_fields` and `sensitive_fields`.
+// `Masking::Hash` writes an HMAC-SHA256 keyed by the salt instead, equal values get equal hashes so lines can still
+// be correlated, also across processes and releases sharing the salt. Keep the salt out of the logs, with it
+// short or guessable values can be found by trying them. Replies aren't logged, failures only with their error
+// code: server messages quote values (duplicate keys).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent};
+
+use crate::RustMongoDBModelMethods;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Masking {
+    // `"<masked>"`
+    Mask,
+    // `"#<16 hex digits>"`, the start of the HMAC
+    Hash { salt: String },
+}
+
+pub struct QueryLog {
+    sink: Arc<dyn Fn(&str) + Send + Sync>,
+    // Last path segments of the PII fields, by collection
+    pii: HashMap<String, Vec<String>>,
+    masking: Masking,
+}
+
+impl QueryLog {
+    pub fn new(sink: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self { sink: Arc::new(sink), pii: HashMap::new(), masking: Masking::Mask }
+    }
+
+    pub fn pii(mut self, collection: &str, fields: &[&str]) -> Self {
+        let segments = fields.iter().map(|x| x.rsplit('.').next().unwrap_or(x).to_string());
+        self.pii.entry(collection.to_string()).or_default().extend(segments);
+        self
+    }
+
+    pub fn pii_of<M: RustMongoDBModelMethods>(self) -> Self {
+        let collection = M::collection();
+        self.pii(collection.name(), M::pii_fields()).pii(collection.name(), M::sensitive_fields())
+    }
+
+    pub fn masking(mut self, masking: Masking) -> Self {
+        self.masking = masking;
+        self
+    }
+
+    // `command` as logged, for commands on `collection`
+    pub fn mask(&self, collection: &str, command: &bson::Document) -> bson::Document {
+        match self.pii.get(collection) {
+            Some(fields) => self.mask_document(fields, command),
+            None => command.clone(),
+        }
+    }
+
+    fn mask_document(&self, fields: &[String], document: &bson::Document) -> bson::Document {
+        let pii = |key: &str| fields.iter().any(|x| key.rsplit('.').next() == Some(x));
+        document
+            .iter()
+            .map(|(key, value)| match pii(key) {
+                true => (key.clone(), self.masked(value)),
+                false => (key.clone(), self.mask_value(fields, value)),
+            })
+            .collect()
+    }
+
+    fn mask_value(&self, fields: &[String], value: &bson::Bson) -> bson::Bson {
+        match value {
+            bson::Bson::Document(x) => bson::Bson::Document(self.mask_document(fields, x)),
+            bson::Bson::Array(x) => bson::Bson::Array(x.iter().map(|x| self.mask_value(fields, x)).collect()),
+            x => x.clone(),
+        }
+    }
+
+    // Operators and array shapes stay, the values they hold don't
+    fn masked(&self, value: &bson::Bson) -> bson::Bson {
+        match value {
+            bson::Bson::Document(x) if x.keys().all(|x| x.starts_with('$')) && !x.is_empty() => {
+                bson::Bson::Document(x.iter().map(|(k, v)| (k.clone(), self.masked(v))).collect())
+            }
+            bson::Bson::Array(x) => bson::Bson::Array(x.iter().map(|x| self.masked(x)).collect()),
+            x => match &self.masking {
+                Masking::Mask => bson::Bson::String("<masked>".to_string()),
+                Masking::Hash { salt } => {
+                    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, salt.as_bytes());
+                    let tag = ring::hmac::sign(&key, x.clone().into_canonical_extjson().to_string().as_bytes());
+                    let hex: String = tag.as_ref()[..8].iter().map(|x| format!("{:02x}", x)).collect();
+                    bson::Bson::String(format!("#{}", hex))
+                }
+            },
+        }
+    }
+}
+
+impl CommandEventHandler for QueryLog {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        let collection = event.command.values().next().and_then(|x| x.as_str()).unwrap_or_default();
+        let command = bson::Bson::Document(self.mask(collection, &event.command)).into_relaxed_extjson();
+        let line = format!("{} `{}.{}` #{} {}", event.command_name, event.db, collection, event.request_id, command);
+        (self.sink)(&line);
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        let line = format!("{} #{} done in {}ms", event.command_name, event.request_id, event.duration.as_millis());
+        (self.sink)(&line);
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        let code = match event.failure.kind.as_ref() {
+            mongodb::error::ErrorKind::Command(x) => format!("{} ({})", x.code_name, x.code),
+            mongodb::error::ErrorKind::Io(_) => "I/O error".to_string(),
+            _ => "driver error".to_string(),
+        };
+        let duration = event.duration.as_millis();
+        let line = format!("{} #{} failed in {}ms: {}", event.command_name, event.request_id, duration, code);
+        (self.sink)(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    fn log() -> QueryLog {
+        QueryLog::new(|_| {}).pii("users", &["email", "profile.phone"])
+    }
+
+    #[test]
+    fn masks_pii_fields_anywhere_in_the_command() {
+        let command = doc! {
+            "update": "users",
+            "updates": [{ "q": { "email": "a@b.c", "active": true }, "u": { "$set": { "profile.phone": "555" } } }],
+        };
+        let expected = doc! {
+            "update": "users",
+            "updates": [{
+                "q": { "email": "<masked>", "active": true },
+                "u": { "$set": { "profile.phone": "<masked>" } },
+            }],
+        };
+        assert_eq!(log().mask("users", &command), expected);
+
+        let insert =
+            doc! { "insert": "users", "documents": [{ "name": "a", "profile": { "phone": 555, "city": "x" } }] };
+        let expected =
+            doc! { "insert": "users", "documents": [{ "name": "a", "profile": { "phone": "<masked>", "city": "x" } }] };
+        assert_eq!(log().mask("users", &insert), expected);
+    }
+
+    #[test]
+    fn keeps_operators_and_array_shapes() {
+        let command = doc! { "find": "users", "filter": { "email": { "$in": ["a", "b"], "$ne": null } } };
+        let expected =
+            doc! { "find": "users", "filter": { "email": { "$in": ["<masked>", "<masked>"], "$ne": "<masked>" } } };
+        assert_eq!(log().mask("users", &command), expected);
+        let nested = doc! { "filter": { "email": { "domain": "b.c" } } };
+        assert_eq!(log().mask("users", &nested), doc! { "filter": { "email": "<masked>" } });
+    }
+
+    #[test]
+    fn leaves_other_collections_alone() {
+        let command = doc! { "find": "orders", "filter": { "email": "a@b.c" } };
+        assert_eq!(log().mask("orders", &command), command);
+    }
+
+    #[test]
+    fn hashes_equal_values_equally_under_one_salt() {
+        let hashed = |salt: &str, value: &str| {
+            let log = log().masking(Masking::Hash { salt: salt.to_string() });
+            let masked = log.mask("users", &doc! { "email": value });
+            masked.get_str("email").unwrap().to_string()
+        };
+        let hash = hashed("pepper", "a@b.c");
+        assert!(hash.starts_with('#') && hash.len() == 17);
+        assert_eq!(hash, hashed("pepper", "a@b.c"));
+        assert_ne!(hash, hashed("pepper", "x@b.c"));
+        assert_ne!(hash, hashed("salt", "a@b.c"));
+    }
+}
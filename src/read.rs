@@ -0,0 +1,50 @@
+// READ ROUTING ====================================================================================================
+// `Analytics` pins reporting queries to secondaries, optionally to the ones tagged for it, so heavy scans never
+// compete with the application's traffic on the primary:
+//
+//     fn analytics() -> Analytics {
+//         Analytics::new().tags(&[("workload", "analytics")]).max_staleness(Duration::from_secs(120))
+//     }
+//
+//     let rows = Order::find_on_secondary(doc! { "year": 2024 }, FindOptions::default()).await?;
+//
+// Tag sets are tried in order and the query fails when no secondary matches any of them, add `.any_secondary()`
+// last to fall back to untagged secondaries instead.
+
+use std::collections::HashMap;
+
+use mongodb::options::{ReadPreference, ReadPreferenceOptions, SelectionCriteria};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Analytics {
+    pub tag_sets: Vec<HashMap<String, String>>,
+    pub max_staleness: Option<std::time::Duration>,
+}
+
+impl Analytics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tags(mut self, tags: &[(&str, &str)]) -> Self {
+        self.tag_sets.push(tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+        self
+    }
+
+    pub fn any_secondary(mut self) -> Self {
+        self.tag_sets.push(HashMap::new());
+        self
+    }
+
+    // At least 90 seconds, the server rejects shorter values
+    pub fn max_staleness(mut self, max_staleness: std::time::Duration) -> Self {
+        self.max_staleness = Some(max_staleness);
+        self
+    }
+
+    pub fn selection_criteria(&self) -> SelectionCriteria {
+        let tag_sets = (!self.tag_sets.is_empty()).then(|| self.tag_sets.clone());
+        let options = ReadPreferenceOptions::builder().tag_sets(tag_sets).max_staleness(self.max_staleness).build();
+        SelectionCriteria::ReadPreference(ReadPreference::Secondary { options })
+    }
+}
@@ -0,0 +1,39 @@
+// READ-ONLY MODE ==================================================================================================
+// While read-only, every write method fails with `Error::ReadOnly` before reaching the database, reads keep
+// working. Switch it for the whole process, e.g. for a maintenance window or while the primary fails over:
+//
+//     read_only::set(true);
+//
+// or for one task and what it awaits, e.g. a report job pointed at an analytics replica:
+//
+//     read_only::scope(true, run_report()).await;
+//
+// A scope wins over the process switch, `scope(false, ...)` lets a migration write during maintenance. Models
+// read it through `read_only()`, override that to add their own switch:
+//
+//     fn read_only() -> bool {
+//         read_only::enabled() || ARCHIVED.load(Ordering::Relaxed)
+//     }
+//
+// `Session` writes, `EventSourced` appends and snapshots are covered too.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+tokio::task_local! {
+    static SCOPED: bool;
+}
+
+pub fn set(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+// The current task's scope if it runs in one, the process switch otherwise
+pub fn enabled() -> bool {
+    SCOPED.try_with(|x| *x).unwrap_or_else(|_| READ_ONLY.load(Ordering::Relaxed))
+}
+
+pub async fn scope<F: std::future::Future>(read_only: bool, future: F) -> F::Output {
+    SCOPED.scope(read_only, future).await
+}
@@ -0,0 +1,32 @@
+// REDACTION =======================================================================================================
+// Fields that stay in the data layer, e.g. password hashes and API tokens:
+//
+//     fn sensitive_fields() -> &'static [&'static str] {
+//         &["password_hash", "tokens.refresh"]
+//     }
+//
+// `to_public_bson()` is the model without them, to hand to serializers, templates and logs; `find_redacted`
+// reads documents that never had them, the projection drops them on the server:
+//
+//     let body = user.to_public_bson()?;
+//     let users = User::find_redacted(doc! { "team": team }).await?;
+//
+// Both return documents, not the model, which usually can't deserialize without its sensitive fields. Dotted
+// paths reach into sub-documents, not into arrays of them.
+
+// Removes the value at a dotted `path`, if any
+pub(crate) fn remove(document: &mut bson::Document, path: &str) {
+    if document.remove(path).is_some() {
+        return;
+    }
+    if let Some((head, rest)) = path.split_once('.') {
+        if let Ok(x) = document.get_document_mut(head) {
+            remove(x, rest);
+        }
+    }
+}
+
+// `{ field: 0, ... }`, empty (everything) without sensitive fields
+pub(crate) fn exclusion(fields: &[&str]) -> bson::Document {
+    fields.iter().map(|x| (x.to_string(), bson::Bson::Int32(0))).collect()
+}
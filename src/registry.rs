@@ -0,0 +1,227 @@
+// MODEL REGISTRY ==================================================================================================
+// Models registered at startup can be iterated without naming their types, for tools working on every model:
+//
+//     rms::register::<User>();
+//     rms::register_as::<Invoice, AppError>();
+//
+//     #[derive(Serialize, Deserialize, Register)]
+//     struct Order { ... }
+//
+//     registry::ensure_all_indexes().await?;
+//     for stats in registry::stats().await? {
+//         println!("{}: {} documents, {} indexes", stats.collection, stats.documents, stats.indexes);
+//     }
+//     for model in registry::models() {
+//         seed(&model.collection()).await?;
+//     }
+//
+// Register next to `init`, once per model, a second registration is ignored. With the `derive` feature,
+// `#[derive(Register)]` on the model does it instead, collected before `main` by `inventory`; `models()` lists the
+// explicitly registered ones first, then the derived ones by name. Entries work on the collection as BSON
+// documents and return `Error`, whatever the model's own error type. `verify_registered` runs the checks of
+// `verify_startup` on all of them.
+
+use std::any::TypeId;
+use std::sync::RwLock;
+
+use futures::future::BoxFuture;
+
+use crate::introspect::{self, ModelSchema};
+use crate::startup::{self, ModelReport, StartupOptions};
+use crate::{error, Error, ErrorContext, Index, RustMongoDBModelMethods};
+
+static MODELS: RwLock<Vec<RegisteredModel>> = RwLock::new(Vec::new());
+
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub use inventory;
+#[cfg(feature = "derive")]
+pub use rust_mongodb_model_methods_derive::Register;
+
+// What `#[derive(Register)]` submits
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub struct Registration(pub fn() -> RegisteredModel);
+
+#[cfg(feature = "derive")]
+inventory::collect!(Registration);
+
+// The entry of `M`, for `#[derive(Register)]`
+#[doc(hidden)]
+pub fn entry<M, E>() -> RegisteredModel
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error> + 'static,
+{
+    RegisteredModel::of::<M, E>()
+}
+
+#[derive(Clone)]
+pub struct RegisteredModel {
+    // The type name without its path
+    pub model: &'static str,
+    pub type_id: TypeId,
+    pub logical_database: &'static str,
+    collection: fn() -> mongodb::Collection<bson::Document>,
+    indexes: fn() -> Vec<Index>,
+    read_only: fn() -> bool,
+    schema: fn() -> ModelSchema,
+    verify: fn(StartupOptions) -> BoxFuture<'static, Result<ModelReport, Error>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ModelStats {
+    pub model: &'static str,
+    pub collection: String,
+    // Estimated from the collection metadata, not counted
+    pub documents: u64,
+    pub indexes: u64,
+}
+
+impl std::fmt::Debug for RegisteredModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegisteredModel")
+            .field("model", &self.model)
+            .field("logical_database", &self.logical_database)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RegisteredModel {
+    fn of<M, E>() -> Self
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error> + 'static,
+    {
+        Self {
+            model: error::model_name::<M>(),
+            type_id: TypeId::of::<M>(),
+            logical_database: M::logical_database(),
+            collection: || crate::writes::<M, E>().clone_with_type(),
+            indexes: crate::declared_indexes::<M, E>,
+            read_only: M::read_only,
+            schema: introspect::declared::<M, E>,
+            verify: |options| Box::pin(startup::verify_model::<M, E>(options)),
+        }
+    }
+
+    // The model's `collection()`, with its `write_concern()`
+    pub fn collection(&self) -> mongodb::Collection<bson::Document> {
+        (self.collection)()
+    }
+
+    // `indexes()` and the unique indexes of `unique_together`
+    pub fn indexes(&self) -> Vec<Index> {
+        (self.indexes)()
+    }
+
+    // What the model declares, see `introspect`
+    pub fn schema(&self) -> ModelSchema {
+        (self.schema)()
+    }
+
+    pub async fn ensure_indexes(&self) -> Result<(), Error> {
+        let collection = self.collection();
+        let context = ErrorContext::new(collection.name(), "ensure_indexes");
+        if (self.read_only)() {
+            return Err(context.wrap(Error::ReadOnly));
+        }
+        let models: Vec<mongodb::IndexModel> = self.indexes().iter().map(Index::to_model).collect();
+        if !models.is_empty() {
+            collection.create_indexes(models, None).await.map_err(|x| context.wrap(x))?;
+        }
+        Ok(())
+    }
+
+    pub async fn stats(&self) -> Result<ModelStats, Error> {
+        let collection = self.collection();
+        let context = ErrorContext::new(collection.name(), "stats");
+        let documents = collection.estimated_document_count(None).await.map_err(|x| context.wrap(x))?;
+        let indexes = match collection.list_index_names().await {
+            Ok(x) => x.len() as u64,
+            // No collection yet
+            Err(x) if matches!(x.kind.as_ref(), mongodb::error::ErrorKind::Command(x) if x.code == 26) => 0,
+            Err(x) => return Err(context.wrap(x)),
+        };
+        Ok(ModelStats { model: self.model, collection: collection.name().to_string(), documents, indexes })
+    }
+
+    pub async fn verify(&self, options: StartupOptions) -> Result<ModelReport, Error> {
+        (self.verify)(options).await
+    }
+}
+
+// `register_as` for models whose error type is `Error`
+pub fn register<M: RustMongoDBModelMethods>() -> bool {
+    register_as::<M, Error>()
+}
+
+// `false` when `M` was registered already
+pub fn register_as<M, E>() -> bool
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error> + 'static,
+{
+    if derived().iter().any(|x| x.type_id == TypeId::of::<M>()) {
+        return false;
+    }
+    let mut models = MODELS.write().unwrap_or_else(|x| x.into_inner());
+    if models.iter().any(|x| x.type_id == TypeId::of::<M>()) {
+        return false;
+    }
+    models.push(RegisteredModel::of::<M, E>());
+    true
+}
+
+// In registration order, then the derived ones by name
+pub fn models() -> Vec<RegisteredModel> {
+    let mut models = MODELS.read().unwrap_or_else(|x| x.into_inner()).clone();
+    for model in derived() {
+        if !models.iter().any(|x| x.type_id == model.type_id) {
+            models.push(model);
+        }
+    }
+    models
+}
+
+// Models of `#[derive(Register)]`
+fn derived() -> Vec<RegisteredModel> {
+    #[cfg(feature = "derive")]
+    {
+        let mut models: Vec<RegisteredModel> = inventory::iter::<Registration>().map(|x| (x.0)()).collect();
+        models.sort_by_key(|x| x.model);
+        models
+    }
+    #[cfg(not(feature = "derive"))]
+    Vec::new()
+}
+
+pub fn find(model: &str) -> Option<RegisteredModel> {
+    models().into_iter().find(|x| x.model == model)
+}
+
+pub async fn ensure_all_indexes() -> Result<(), Error> {
+    for model in models() {
+        model.ensure_indexes().await?;
+    }
+    Ok(())
+}
+
+pub async fn stats() -> Result<Vec<ModelStats>, Error> {
+    let mut stats = Vec::new();
+    for model in models() {
+        stats.push(model.stats().await?);
+    }
+    Ok(stats)
+}
+
+// `verify_startup_with` for every registered model
+pub async fn verify_registered(options: StartupOptions) -> Result<startup::StartupReport, Error> {
+    let latency_ms = startup::ping().await?;
+    let mut reports = Vec::new();
+    for model in models() {
+        reports.push(model.verify(options).await?);
+    }
+    startup::finish(startup::StartupReport { latency_ms, models: reports })
+}
@@ -0,0 +1,146 @@
+// RELATIONS =======================================================================================================
+// A child model declares the field holding its parent's `_id`:
+//
+//     impl BelongsTo<Customer> for Order {
+//         const FOREIGN_KEY: &'static str = "customer_id";
+//     }
+//
+//     for x in Order::find_orphans::<Customer>().await? {
+//         println!("order {} points to missing customer {}", x.id, x.missing);
+//     }
+//
+// The parent collection has to be in the child's database, `$lookup` can't join across databases, and the server
+// on MongoDB 5.0+. Children without the field (or with `null`) aren't orphans. An array foreign key counts as
+// orphaned only when none of its elements exists.
+//
+// Writes check their references when the model lists them: `create_one`, `update_one*`, `update_by_ids`,
+// `set_field`, `find_one_and_replace` and their `Session` counterparts then fail with `Error::BrokenReference`
+// instead of storing a dangling ID, `upsert_many` reports the item as `UpsertStatus::Failed`:
+//
+//     fn references() -> Vec<Reference> {
+//         vec![Reference::of::<Order, Customer>()]
+//     }
+//
+// Each referenced field costs one `_id` lookup per write that sets it, per batch for `upsert_many`. `import_extjson`,
+// `restore_from` and `BufferedWriter` write documents as they are, unchecked.
+
+use futures::TryStreamExt;
+
+use crate::{breaker, CircuitBreaker, Error, RustMongoDBModelMethods};
+
+pub trait BelongsTo<Parent> {
+    const FOREIGN_KEY: &'static str;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    // Dotted path of the foreign key, its value or the elements of its array are parent `_id`s
+    pub field: String,
+    // Parent collection, in the child's database
+    pub collection: String,
+}
+
+impl Reference {
+    pub fn new(field: &str, collection: &str) -> Self {
+        Self { field: field.to_string(), collection: collection.to_string() }
+    }
+
+    pub fn of<C: BelongsTo<P>, P: RustMongoDBModelMethods>() -> Self {
+        Self::new(C::FOREIGN_KEY, P::collection().name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Orphan {
+    // `_id` of the child, as stored
+    pub id: bson::Bson,
+    // Its foreign key value no parent has
+    pub missing: bson::Bson,
+}
+
+pub(crate) fn orphans_pipeline(foreign_key: &str, parents: &str) -> Vec<bson::Document> {
+    let reference = format!("${}", foreign_key);
+    vec![
+        bson::doc! { "$match": { foreign_key: { "$exists": true, "$ne": bson::Bson::Null } } },
+        bson::doc! { "$project": { "_id": 1, "missing": &reference } },
+        bson::doc! {
+            "$lookup": {
+                "from": parents,
+                "localField": "missing",
+                "foreignField": "_id",
+                "pipeline": [{ "$project": { "_id": 1 } }],
+                "as": "parents",
+            }
+        },
+        bson::doc! { "$match": { "parents": { "$size": 0 } } },
+        bson::doc! { "$project": { "parents": 0 } },
+    ]
+}
+
+pub(crate) fn orphan(mut document: bson::Document) -> Orphan {
+    let id = document.remove("_id").unwrap_or_default();
+    Orphan { id, missing: document.remove("missing").unwrap_or_default() }
+}
+
+// Fails with `Error::BrokenReference` for the first reference of `document` naming a missing parent
+pub(crate) async fn check(
+    database: &mongodb::Database,
+    references: &[Reference],
+    document: &bson::Document,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<(), Error> {
+    match check_many(database, references, &[document], breaker).await?.pop().flatten() {
+        Some(x) => Err(x),
+        None => Ok(()),
+    }
+}
+
+// `check` of each of `documents`, one `_id` lookup per reference for all of them
+pub(crate) async fn check_many(
+    database: &mongodb::Database,
+    references: &[Reference],
+    documents: &[&bson::Document],
+    breaker: Option<&CircuitBreaker>,
+) -> Result<Vec<Option<Error>>, Error> {
+    let mut broken: Vec<Option<Error>> = documents.iter().map(|_| None).collect();
+    for reference in references {
+        let ids: Vec<Vec<bson::Bson>> = documents.iter().map(|x| referenced(x, &reference.field)).collect();
+        let mut all: Vec<&bson::Bson> = Vec::new();
+        for id in ids.iter().flatten() {
+            if !all.contains(&id) {
+                all.push(id);
+            }
+        }
+        if all.is_empty() {
+            continue;
+        }
+
+        // Existence right after the parent was written, so from the primary
+        let primary = mongodb::options::SelectionCriteria::ReadPreference(mongodb::options::ReadPreference::Primary);
+        let options = mongodb::options::FindOptions::builder()
+            .projection(bson::doc! { "_id": 1 })
+            .selection_criteria(primary)
+            .build();
+        let parents = database.collection::<bson::Document>(&reference.collection);
+        let cursor = breaker::guard(breaker, parents.find(bson::doc! { "_id": { "$in": all } }, options)).await?;
+        let found: Vec<bson::Document> = cursor.try_collect().await?;
+        let found: Vec<&bson::Bson> = found.iter().filter_map(|x| x.get("_id")).collect();
+
+        for (ids, broken) in ids.into_iter().zip(broken.iter_mut()).filter(|(_, x)| x.is_none()) {
+            let missing: Vec<bson::Bson> = ids.into_iter().filter(|x| !found.contains(&x)).collect();
+            if !missing.is_empty() {
+                *broken = Some(Error::broken_reference(&reference.field, &reference.collection, &missing));
+            }
+        }
+    }
+    Ok(broken)
+}
+
+// The parent `_id`s `document` names in `field`
+fn referenced(document: &bson::Document, field: &str) -> Vec<bson::Bson> {
+    match crate::path_value(document, field) {
+        None | Some(bson::Bson::Null) => Vec::new(),
+        Some(bson::Bson::Array(x)) => x.iter().filter(|x| **x != bson::Bson::Null).cloned().collect(),
+        Some(x) => vec![x.clone()],
+    }
+}
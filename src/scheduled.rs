@@ -0,0 +1,62 @@
+// SCHEDULED WRITES ================================================================================================
+// `schedule_update` stores a `$set` to apply later in `_scheduled_ops`, `run_due_operations` applies the ones
+// that are due. Call it from a timer in every instance, pollers claim operations one by one so none runs twice:
+//
+//     Post::schedule_update(&id, midnight, doc! { "published": false }).await?;
+//
+//     let mut every_minute = tokio::time::interval(Duration::from_secs(60));
+//     loop {
+//         every_minute.tick().await;
+//         Post::run_due_operations().await?;
+//     }
+//
+// Operations are applied like `update_by_id`, so they fail like it: a deleted document, a broken reference or an
+// immutable field is `Failed`, not retried. Errors that may go away (the circuit breaker open, network errors,
+// step-downs) put the operation back to `Pending` with the error and one more `attempts`, and end the run with
+// that error; the next run tries it again. An operation claimed by a poller that died, or one that couldn't be put
+// back, is picked up again after `STALE_AFTER`. Pollers look operations up by
+// `{ namespace: 1, due_at: 1 }`, worth an index once many are stored.
+
+pub(crate) const COLLECTION: &str = "_scheduled_ops";
+pub(crate) const STALE_AFTER: std::time::Duration = std::time::Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScheduledOp {
+    #[serde(rename = "_id")]
+    pub id: bson::oid::ObjectId,
+    // `<db>.<collection>` of the target document
+    pub namespace: String,
+    pub target: bson::Bson,
+    pub set: bson::Document,
+    pub due_at: bson::DateTime,
+    pub status: Status,
+    #[serde(default)]
+    pub claimed_at: Option<bson::DateTime>,
+    // Last error, of the failure or of the latest attempt put back to `Pending`
+    #[serde(default)]
+    pub error: Option<String>,
+    // Runs that claimed it and hit an error that may go away
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+// Due operations of `namespace`, including ones whose poller stopped before finishing them
+pub(crate) fn due_filter(namespace: &str) -> bson::Document {
+    let now = bson::DateTime::now();
+    let stale = bson::DateTime::from_millis(now.timestamp_millis() - STALE_AFTER.as_millis() as i64);
+    bson::doc! {
+        "namespace": namespace,
+        "due_at": { "$lte": now },
+        "$or": [{ "status": "pending" }, { "status": "running", "claimed_at": { "$lt": stale } }],
+    }
+}
@@ -0,0 +1,88 @@
+// SCHEMA VALIDATION ===============================================================================================
+// Models declare the `$jsonSchema` their documents should follow, `validate_collection()` reports the stored
+// documents that don't, before the schema is turned into a strict collection validator:
+//
+//     fn json_schema() -> Option<Document> {
+//         Some(doc! {
+//             "bsonType": "object",
+//             "required": ["email", "created_at"],
+//             "properties": { "email": { "bsonType": "string" }, "age": { "bsonType": "int", "minimum": 0 } },
+//         })
+//     }
+//
+//     for x in User::validate_collection().await? {
+//         println!("{}: {:?}", x.id, x.problems);
+//     }
+//
+// The server only says whether a document matches a schema, so problems are found by checking each `required`
+// field and each top-level property on its own. A document failing on something else (`additionalProperties`,
+// nested rules across properties) is reported with no problems listed. IDs of every failing document come back
+// in one result, which caps a run at several hundred thousand of them.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaProblem {
+    // A `required` field is missing
+    Missing(String),
+    // The value of a top-level property doesn't match its schema
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    // As stored, legacy documents may not have an `_id` of the model's ID type
+    pub id: bson::Bson,
+    pub problems: Vec<SchemaProblem>,
+}
+
+// The documents failing `schema`, then one `$facet` per check listing the ones failing it
+pub(crate) fn validation_pipeline(schema: &bson::Document) -> (Vec<bson::Document>, Vec<SchemaProblem>) {
+    let required = schema.get_array("required").map(|x| x.as_slice()).unwrap_or_default();
+    let checks = required.iter().filter_map(|x| x.as_str()).map(|x| SchemaProblem::Missing(x.to_string()));
+    let properties = schema.get_document("properties").ok();
+    let checks: Vec<SchemaProblem> = checks
+        .chain(properties.into_iter().flat_map(|x| x.keys().map(|x| SchemaProblem::Invalid(x.to_string()))))
+        .collect();
+
+    let only_id = bson::doc! { "$project": { "_id": 1 } };
+    let mut facets = bson::doc! { "all": [only_id.clone()] };
+    for (i, check) in checks.iter().enumerate() {
+        let failing = match check {
+            SchemaProblem::Missing(field) => bson::doc! { field: { "$exists": false } },
+            SchemaProblem::Invalid(field) => {
+                let property = properties.and_then(|x| x.get(field)).cloned().unwrap_or_default();
+                let schema = bson::doc! { "properties": { field: property } };
+                bson::doc! { "$nor": [{ "$jsonSchema": schema }] }
+            }
+        };
+        facets.insert(format!("c{i}"), vec![bson::doc! { "$match": failing }, only_id.clone()]);
+    }
+
+    let pipeline = vec![
+        bson::doc! { "$match": { "$nor": [{ "$jsonSchema": schema.clone() }] } },
+        bson::doc! { "$facet": facets },
+    ];
+    (pipeline, checks)
+}
+
+pub(crate) fn violations(mut result: bson::Document, checks: Vec<SchemaProblem>) -> Vec<SchemaViolation> {
+    let mut ids = |facet: &str| -> Vec<bson::Bson> {
+        let Some(bson::Bson::Array(documents)) = result.remove(facet) else {
+            return Vec::new();
+        };
+        documents.into_iter().filter_map(|x| x.as_document().and_then(|x| x.get("_id")).cloned()).collect()
+    };
+
+    let mut violations: Vec<SchemaViolation> =
+        ids("all").into_iter().map(|id| SchemaViolation { id, problems: Vec::new() }).collect();
+    // `Bson` isn't `Hash`, its `Display` keeps types apart (`"1"` vs `1`)
+    let positions: std::collections::HashMap<String, usize> =
+        violations.iter().enumerate().map(|(i, x)| (x.id.to_string(), i)).collect();
+    for (i, check) in checks.into_iter().enumerate() {
+        for id in ids(&format!("c{i}")) {
+            if let Some(&position) = positions.get(&id.to_string()) {
+                violations[position].problems.push(check.clone());
+            }
+        }
+    }
+    violations
+}
@@ -0,0 +1,271 @@
+// ATLAS SEARCH ====================================================================================================
+// Typed builder for the `$search` stage (feature "atlas_search"), used by `search` / `search_highlighted`:
+//
+//     let query = SearchQuery::new(
+//         Compound::new()
+//             .must(Operator::text("espresso", &["title", "description"]))
+//             .should(Operator::autocomplete("esp", "title")),
+//     )
+//     .index("products")
+//     .highlight(&["description"])
+//     .limit(20);
+//
+//     let hits: Vec<(Product, f64)> = Product::search(query).await?;
+
+use crate::Error;
+
+#[derive(Debug, Clone)]
+pub enum Operator {
+    Text { query: String, path: Vec<String>, max_edits: Option<u32> },
+    Autocomplete { query: String, path: String, max_edits: Option<u32> },
+    Phrase { query: String, path: Vec<String>, slop: Option<u32> },
+    Compound(Compound),
+    // Any operator this builder doesn't cover, e.g. `("range", doc! { ... })`
+    Raw(String, bson::Document),
+}
+
+impl Operator {
+    pub fn text(query: &str, path: &[&str]) -> Self {
+        Self::Text { query: query.to_string(), path: path.iter().map(|x| x.to_string()).collect(), max_edits: None }
+    }
+
+    pub fn fuzzy_text(query: &str, path: &[&str], max_edits: u32) -> Self {
+        Self::Text {
+            query: query.to_string(),
+            path: path.iter().map(|x| x.to_string()).collect(),
+            max_edits: Some(max_edits),
+        }
+    }
+
+    pub fn autocomplete(query: &str, path: &str) -> Self {
+        Self::Autocomplete { query: query.to_string(), path: path.to_string(), max_edits: None }
+    }
+
+    pub fn phrase(query: &str, path: &[&str]) -> Self {
+        Self::Phrase { query: query.to_string(), path: path.iter().map(|x| x.to_string()).collect(), slop: None }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Text { .. } => "text",
+            Self::Autocomplete { .. } => "autocomplete",
+            Self::Phrase { .. } => "phrase",
+            Self::Compound(_) => "compound",
+            Self::Raw(name, _) => name,
+        }
+    }
+
+    pub fn body(&self) -> bson::Document {
+        match self {
+            Self::Text { query, path, max_edits } => {
+                let mut body = bson::doc! { "query": query, "path": path };
+                if let Some(max_edits) = max_edits {
+                    body.insert("fuzzy", bson::doc! { "maxEdits": *max_edits as i32 });
+                }
+                body
+            }
+            Self::Autocomplete { query, path, max_edits } => {
+                let mut body = bson::doc! { "query": query, "path": path };
+                if let Some(max_edits) = max_edits {
+                    body.insert("fuzzy", bson::doc! { "maxEdits": *max_edits as i32 });
+                }
+                body
+            }
+            Self::Phrase { query, path, slop } => {
+                let mut body = bson::doc! { "query": query, "path": path };
+                if let Some(slop) = slop {
+                    body.insert("slop", *slop as i32);
+                }
+                body
+            }
+            Self::Compound(compound) => compound.body(),
+            Self::Raw(_, body) => body.clone(),
+        }
+    }
+
+    // `{ <operator>: <body> }`, the shape expected inside `$search` and compound clauses
+    pub fn to_document(&self) -> bson::Document {
+        bson::doc! { self.name(): self.body() }
+    }
+}
+
+impl From<Compound> for Operator {
+    fn from(compound: Compound) -> Self {
+        Self::Compound(compound)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Compound {
+    must: Vec<Operator>,
+    should: Vec<Operator>,
+    filter: Vec<Operator>,
+    must_not: Vec<Operator>,
+    minimum_should_match: Option<u32>,
+}
+
+impl Compound {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn must(mut self, operator: impl Into<Operator>) -> Self {
+        self.must.push(operator.into());
+        self
+    }
+    pub fn should(mut self, operator: impl Into<Operator>) -> Self {
+        self.should.push(operator.into());
+        self
+    }
+    pub fn filter(mut self, operator: impl Into<Operator>) -> Self {
+        self.filter.push(operator.into());
+        self
+    }
+    pub fn must_not(mut self, operator: impl Into<Operator>) -> Self {
+        self.must_not.push(operator.into());
+        self
+    }
+    pub fn minimum_should_match(mut self, n: u32) -> Self {
+        self.minimum_should_match = Some(n);
+        self
+    }
+
+    fn body(&self) -> bson::Document {
+        let mut body = bson::Document::new();
+        for (clause, operators) in
+            [("must", &self.must), ("should", &self.should), ("filter", &self.filter), ("mustNot", &self.must_not)]
+        {
+            if !operators.is_empty() {
+                let operators: Vec<bson::Document> = operators.iter().map(Operator::to_document).collect();
+                body.insert(clause, operators);
+            }
+        }
+        if let Some(n) = self.minimum_should_match {
+            body.insert("minimumShouldMatch", n as i32);
+        }
+        body
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    operator: Operator,
+    index: Option<String>,
+    highlight: Vec<String>,
+    limit: Option<u64>,
+}
+
+impl SearchQuery {
+    pub fn new(operator: impl Into<Operator>) -> Self {
+        Self { operator: operator.into(), index: None, highlight: Vec::new(), limit: None }
+    }
+
+    // Defaults to the index named "default" when not set
+    pub fn index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    pub fn highlight(mut self, paths: &[&str]) -> Self {
+        self.highlight = paths.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn to_stage(&self) -> bson::Document {
+        let mut search = self.operator.to_document();
+        if let Some(index) = &self.index {
+            search.insert("index", index);
+        }
+        if !self.highlight.is_empty() {
+            search.insert("highlight", bson::doc! { "path": &self.highlight });
+        }
+        bson::doc! { "$search": search }
+    }
+
+    pub(crate) fn pipeline(&self) -> Vec<bson::Document> {
+        let mut pipeline = vec![self.to_stage()];
+        if let Some(limit) = self.limit {
+            pipeline.push(bson::doc! { "$limit": limit as i64 });
+        }
+        let mut meta = bson::doc! { SCORE_FIELD: { "$meta": "searchScore" } };
+        if !self.highlight.is_empty() {
+            meta.insert(HIGHLIGHTS_FIELD, bson::doc! { "$meta": "searchHighlights" });
+        }
+        pipeline.push(bson::doc! { "$addFields": meta });
+        pipeline
+    }
+}
+
+// VECTOR SEARCH ===================================================================================================
+// `$vectorSearch` considers 10x `k` nearest candidates before keeping the top `k`, which is the ratio Atlas
+// recommends for a good recall/latency trade-off, up to its cap of 10000 candidates. A `k` over the cap fails
+// with `Error::InvalidParams`, the server would reject the stage.
+const MAX_CANDIDATES: u64 = 10_000;
+
+pub(crate) fn vector_search_pipeline(
+    index: &str,
+    field: &str,
+    embedding: &[f32],
+    k: u64,
+    filter: bson::Document,
+) -> Result<Vec<bson::Document>, Error> {
+    if k > MAX_CANDIDATES {
+        return Err(Error::InvalidParams(format!("`k` {} is over the {} candidates limit", k, MAX_CANDIDATES)));
+    }
+    let query_vector: Vec<f64> = embedding.iter().map(|x| *x as f64).collect();
+    let mut stage = bson::doc! {
+        "index": index,
+        "path": field,
+        "queryVector": query_vector,
+        "numCandidates": k.saturating_mul(10).min(MAX_CANDIDATES) as i64,
+        "limit": k as i64,
+    };
+    if !filter.is_empty() {
+        stage.insert("filter", filter);
+    }
+
+    Ok(vec![
+        bson::doc! { "$vectorSearch": stage },
+        bson::doc! { "$addFields": { SCORE_FIELD: { "$meta": "vectorSearchScore" } } },
+    ])
+}
+
+// RESULTS =========================================================================================================
+pub(crate) const SCORE_FIELD: &str = "_search_score";
+pub(crate) const HIGHLIGHTS_FIELD: &str = "_search_highlights";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HighlightText {
+    pub value: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Highlight {
+    pub path: String,
+    pub texts: Vec<HighlightText>,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit<T> {
+    pub item: T,
+    pub score: f64,
+    pub highlights: Vec<Highlight>,
+}
+
+impl<T: serde::de::DeserializeOwned> SearchHit<T> {
+    pub(crate) fn from_document(mut document: bson::Document) -> Result<Self, bson::de::Error> {
+        let score = document.remove(SCORE_FIELD).and_then(|x| x.as_f64()).unwrap_or(0.0);
+        let highlights = match document.remove(HIGHLIGHTS_FIELD) {
+            Some(highlights) => bson::from_bson(highlights)?,
+            None => Vec::new(),
+        };
+        Ok(Self { item: bson::from_document(document)?, score, highlights })
+    }
+}
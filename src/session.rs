@@ -0,0 +1,294 @@
+// SESSIONS ========================================================================================================
+// `with_causal_session` runs the closure with one causally consistent session, every call made through the
+// `Session` it gets sees the writes made before it, across collections and even when reading from secondaries:
+//
+//     session::with_causal_session(&client, |session| Box::pin(async move {
+//         let order = session.create_one(&order).await?;
+//         let user: User = session.update_by_id(&order.user_id, doc! { "last_order": order.id }).await?;
+//         Ok::<_, Error>((order, user))
+//     }))
+//     .await?;
+//
+// The guarantee holds for majority reads and writes, configure those on the client or the collections.
+// The methods take the same arguments as the model trait and apply the model's collation and `map_error`, and
+// check references and unique fields the same way.
+// A session belongs to one cluster: start it on the client of the models' logical database (`client_of`), it
+// fails with `Error::InvalidParams` for models of another one than the first it was used with.
+
+use futures::TryStreamExt;
+
+use crate::{id_type, shard, Error, ErrorContext, IdOf, RustMongoDBModelMethods};
+
+pub struct Session {
+    session: mongodb::ClientSession,
+    // `logical_database()` of the first model used
+    database: Option<&'static str>,
+}
+
+pub async fn with_causal_session<T, E, F>(client: &mongodb::Client, f: F) -> Result<T, E>
+where
+    E: From<Error>,
+    F: for<'a> FnOnce(&'a mut Session) -> futures::future::BoxFuture<'a, Result<T, E>>,
+{
+    let options = mongodb::options::SessionOptions::builder().causal_consistency(true).build();
+    let mut session = Session::start(client, options).await?;
+    f(&mut session).await
+}
+
+// Point-in-time reads: every read through the session sees the data as of its first read, so a report over
+// several collections adds up even while writes go on. Snapshot sessions can't write, and the server keeps
+// the snapshot for about five minutes (`minSnapshotHistoryWindowInSeconds`).
+pub async fn snapshot<T, E, F>(client: &mongodb::Client, f: F) -> Result<T, E>
+where
+    E: From<Error>,
+    F: for<'a> FnOnce(&'a mut Session) -> futures::future::BoxFuture<'a, Result<T, E>>,
+{
+    let mut session = Session::start_snapshot(client).await?;
+    f(&mut session).await
+}
+
+impl Session {
+    pub async fn start(client: &mongodb::Client, options: mongodb::options::SessionOptions) -> Result<Self, Error> {
+        let session = client.start_session(options).await?;
+        Ok(Self { session, database: None })
+    }
+
+    // Session with `readConcern: snapshot`, for callers holding on to it outside of `snapshot`
+    pub async fn start_snapshot(client: &mongodb::Client) -> Result<Self, Error> {
+        Self::start(client, mongodb::options::SessionOptions::builder().snapshot(true).build()).await
+    }
+
+    fn enter<M, E>(&mut self) -> Result<(), Error>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let database = *self.database.get_or_insert(M::logical_database());
+        match database == M::logical_database() {
+            true => Ok(()),
+            false => Err(Error::InvalidParams(format!(
+                "`{}` is in the `{}` database, the session's is `{}`",
+                M::collection().name(),
+                M::logical_database(),
+                database
+            ))),
+        }
+    }
+
+    // For driver calls the helpers don't cover
+    pub fn client_session(&mut self) -> &mut mongodb::ClientSession {
+        &mut self.session
+    }
+
+    pub async fn find<M, E>(&mut self, filter: bson::Document) -> Result<Vec<M>, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let collection = M::collection();
+        let context = ErrorContext::new(collection.name(), "find").filter(&filter);
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let options = mongodb::options::FindOptions::builder().collation(M::collation()).build();
+        let mut cursor = collection
+            .find_with_session(filter, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+        let items = cursor.stream(&mut self.session).try_collect().await.map_err(context.wrapper(M::map_error))?;
+        Ok(items)
+    }
+
+    pub async fn find_one<M, E>(&mut self, filter: bson::Document) -> Result<Option<M>, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let collection = M::collection();
+        let context = ErrorContext::new(collection.name(), "find_one").filter(&filter);
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let options = mongodb::options::FindOneOptions::builder().collation(M::collation()).build();
+        let item = collection
+            .find_one_with_session(filter, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+        Ok(item)
+    }
+
+    pub async fn find_one_strict<M, E>(&mut self, filter: bson::Document) -> Result<M, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let context = ErrorContext::new(M::collection().name(), "find_one_strict").filter(&filter);
+        let not_found = Error::not_found_filter::<M>(&filter);
+        self.find_one::<M, E>(filter).await?.ok_or_else(|| M::map_error(context.wrap(not_found)))
+    }
+
+    pub async fn find_by_id<M, E, I>(&mut self, id: &I) -> Result<Option<M>, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        I: IdOf<M> + ?Sized,
+    {
+        self.find_one::<M, E>(bson::doc! { "_id": id.raw_id() }).await
+    }
+
+    pub async fn find_by_id_strict<M, E, I>(&mut self, id: &I) -> Result<M, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        I: IdOf<M> + ?Sized,
+    {
+        let context = ErrorContext::new(M::collection().name(), "find_by_id_strict").id(id.raw_id().to_owned());
+        let not_found = Error::not_found_id::<M>(id.raw_id().to_owned());
+        self.find_by_id::<M, E, I>(id).await?.ok_or_else(|| M::map_error(context.wrap(not_found)))
+    }
+
+    pub async fn count<M, E>(&mut self, filter: bson::Document) -> Result<u64, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let collection = M::collection();
+        let context = ErrorContext::new(collection.name(), "count").filter(&filter);
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let options = mongodb::options::CountOptions::builder().collation(M::collation()).build();
+        let count = collection
+            .count_documents_with_session(filter, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+        Ok(count)
+    }
+
+    pub async fn aggregate<M, E, T>(&mut self, pipeline: Vec<bson::Document>) -> Result<Vec<T>, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        T: serde::de::DeserializeOwned,
+    {
+        let collection = M::collection();
+        let context = ErrorContext::new(collection.name(), "aggregate");
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let options = mongodb::options::AggregateOptions::builder().collation(M::collation()).build();
+        let mut cursor = collection
+            .aggregate_with_session(pipeline, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+        let documents: Vec<bson::Document> =
+            cursor.stream(&mut self.session).try_collect().await.map_err(context.wrapper(M::map_error))?;
+
+        let items = documents
+            .into_iter()
+            .map(bson::from_document::<T>)
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(context.wrapper(M::map_error))?;
+        Ok(items)
+    }
+
+    pub async fn create_one<M, E>(&mut self, data: &M) -> Result<M, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let collection = crate::writes::<M, E>().clone_with_type::<bson::Document>();
+        let mut context = ErrorContext::new(collection.name(), "create_one");
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+        crate::writable::<M, E>().map_err(context.wrapper(M::map_error))?;
+
+        let mut document = bson::to_document(data).map_err(context.wrapper(M::map_error))?;
+        if document.get("_id").is_none_or(id_type::is_unset) {
+            document.insert("_id", M::generate_id());
+        }
+        let id = document.get("_id").cloned().unwrap_or_default();
+        context = context.id(id.clone());
+        shard::check_document(M::shard_key(), &document).map_err(context.wrapper(M::map_error))?;
+        crate::check_references::<M, E>(&document).await.map_err(context.wrapper(M::map_error))?;
+        let exclude = bson::doc! { "_id": &id };
+        crate::check_unique::<M, E>(&document, exclude).await.map_err(context.wrapper(M::map_error))?;
+
+        collection
+            .insert_one_with_session(document, None, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+
+        self.find_one_strict::<M, E>(bson::doc! { "_id": id }).await
+    }
+
+    pub async fn update_one<M, E, D>(&mut self, filter: bson::Document, data: D) -> Result<M, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        D: serde::Serialize,
+    {
+        let collection = crate::writes::<M, E>();
+        let context = ErrorContext::new(collection.name(), "update_one").filter(&filter);
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+        crate::writable::<M, E>().map_err(context.wrapper(M::map_error))?;
+        shard::check_filter(M::shard_key(), &filter, true).map_err(context.wrapper(M::map_error))?;
+
+        let set = bson::to_bson(&data).map_err(context.wrapper(M::map_error))?;
+        if let bson::Bson::Document(set) = &set {
+            crate::check_references::<M, E>(set).await.map_err(context.wrapper(M::map_error))?;
+            crate::check_unique::<M, E>(set, filter.clone()).await.map_err(context.wrapper(M::map_error))?;
+        }
+        let update = bson::doc! { "$set": set };
+        crate::immutable::check(M::immutable_fields(), &update.clone().into()).map_err(context.wrapper(M::map_error))?;
+
+        // The updated document itself, `filter` may not select it anymore
+        let options = mongodb::options::FindOneAndUpdateOptions::builder()
+            .return_document(mongodb::options::ReturnDocument::After)
+            .collation(M::collation())
+            .build();
+        let item = collection
+            .find_one_and_update_with_session(filter.clone(), update, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+        item.ok_or_else(|| M::map_error(context.wrap(Error::not_found_filter::<M>(&filter))))
+    }
+
+    pub async fn update_by_id<M, E, I, D>(&mut self, id: &I, data: D) -> Result<M, E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        I: IdOf<M> + ?Sized,
+        D: serde::Serialize,
+    {
+        self.update_one::<M, E, D>(bson::doc! { "_id": id.raw_id() }, data).await
+    }
+
+    pub async fn delete_one<M, E>(&mut self, filter: bson::Document) -> Result<(), E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+    {
+        let collection = crate::writes::<M, E>();
+        let context = ErrorContext::new(collection.name(), "delete_one").filter(&filter);
+        self.enter::<M, E>().map_err(context.wrapper(M::map_error))?;
+        crate::writable::<M, E>().map_err(context.wrapper(M::map_error))?;
+        shard::check_filter(M::shard_key(), &filter, true).map_err(context.wrapper(M::map_error))?;
+
+        let options = mongodb::options::DeleteOptions::builder().collation(M::collation()).build();
+        let delete_result = collection
+            .delete_one_with_session(filter, options, &mut self.session)
+            .await
+            .map_err(context.wrapper(M::map_error))?;
+
+        if delete_result.deleted_count != 1 {
+            return Err(M::map_error(context.wrap(Error::DeleteFailed("No record deleted".to_string()))));
+        };
+
+        Ok(())
+    }
+
+    pub async fn delete_by_id<M, E, I>(&mut self, id: &I) -> Result<(), E>
+    where
+        M: RustMongoDBModelMethods<E>,
+        E: From<Error>,
+        I: IdOf<M> + ?Sized,
+    {
+        self.delete_one::<M, E>(bson::doc! { "_id": id.raw_id() }).await
+    }
+}
@@ -0,0 +1,122 @@
+// SHARD KEYS ======================================================================================================
+// Models of sharded collections declare their shard key, in order:
+//
+//     fn shard_key() -> &'static [&'static str] {
+//         &["tenant_id", "_id"]
+//     }
+//
+// Writes the server routes by shard key are checked for it before they're sent, and fail with
+// `Error::MissingShardKey` naming the field instead of the server's "Failed to target upsert by query" or
+// "Query for sharded findAndModify must contain the shard key":
+//
+// - `create_one` and `import_extjson*`: every inserted document sets each field of the key
+// - `update_one*`, `set_field`, `update_by_id`, `delete_one` and their `Session` counterparts: the filter pins
+//   each field of the key to one value, or pins `_id`
+// - `find_one_and_replace`, `increment_and_get`, `pop` (findAndModify) and `upsert_many`: the filter pins each
+//   field of the key, `_id` isn't enough. The ID-only ones only pass for a key of `_id` alone.
+//
+// A field is pinned by a top-level `{ field: value }` or `{ field: { $eq: value } }`, also inside a top-level
+// `$and`. Multi-document writes (`update_many*`, `delete_many`) may go to every shard and aren't checked.
+//
+// Queries starting from a loaded document stay on its shard through the instance helpers, `update`, `delete` and
+// `reload` use them too:
+//
+//     Order::update_one(order.scoped_filter()?, doc! { "status": "shipped" }).await?;
+//     let open = Invoice::find(order.scoped(doc! { "paid": false })?).await?;
+//
+// `scoped_filter()` is `{ _id, tenant_id }` with the document's values, `scoped(filter)` adds the shard key to
+// `filter`, for models sharing it. Both are `{ _id }` / `filter` as given without a shard key.
+
+use crate::Error;
+
+// `document` sets every field of `key`
+pub(crate) fn check_document(key: &[&str], document: &bson::Document) -> Result<(), Error> {
+    match key.iter().find(|x| crate::path_value(document, x).is_none()) {
+        Some(field) => Err(Error::MissingShardKey { field: field.to_string(), from: "document" }),
+        None => Ok(()),
+    }
+}
+
+// `filter` pins every field of `key`, or `_id` when `by_id` targets too
+pub(crate) fn check_filter(key: &[&str], filter: &bson::Document, by_id: bool) -> Result<(), Error> {
+    if key.is_empty() || (by_id && pins(filter, "_id")) {
+        return Ok(());
+    }
+    match key.iter().find(|x| !pins(filter, x)) {
+        Some(field) => Err(Error::MissingShardKey { field: field.to_string(), from: "filter" }),
+        None => Ok(()),
+    }
+}
+
+// `filter` with the values of `key` from `document`
+pub(crate) fn scoped(
+    key: &[&str],
+    mut filter: bson::Document,
+    document: &bson::Document,
+) -> Result<bson::Document, Error> {
+    for field in key {
+        let value = crate::path_value(document, field);
+        let value = value.ok_or_else(|| Error::MissingShardKey { field: field.to_string(), from: "document" })?;
+        filter.insert(*field, value.clone());
+    }
+    Ok(filter)
+}
+
+fn pins(filter: &bson::Document, field: &str) -> bool {
+    let value = match filter.get(field) {
+        Some(bson::Bson::Document(x)) if x.keys().any(|x| x.starts_with('$')) => x.get("$eq"),
+        x => x,
+    };
+    if value.is_some() {
+        return true;
+    }
+    let and = filter.get_array("$and").map(|x| x.as_slice()).unwrap_or_default();
+    and.iter().filter_map(|x| x.as_document()).any(|x| pins(x, field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn pins_by_value_eq_or_and() {
+        assert!(pins(&doc! { "tenant_id": 1 }, "tenant_id"));
+        assert!(pins(&doc! { "tenant_id": { "$eq": 1 } }, "tenant_id"));
+        assert!(pins(&doc! { "tenant_id": { "name": "a" } }, "tenant_id"));
+        assert!(pins(&doc! { "$and": [{ "status": "open" }, { "tenant_id": { "$eq": 1 } }] }, "tenant_id"));
+        assert!(pins(&doc! { "$and": [{ "$and": [{ "tenant_id": 1 }] }] }, "tenant_id"));
+
+        assert!(!pins(&doc! { "tenant_id": { "$in": [1, 2] } }, "tenant_id"));
+        assert!(!pins(&doc! { "tenant_id": { "$gt": 1 } }, "tenant_id"));
+        assert!(!pins(&doc! { "$or": [{ "tenant_id": 1 }] }, "tenant_id"));
+        assert!(!pins(&doc! { "tenant": { "id": 1 } }, "tenant_id"));
+        assert!(!pins(&doc! {}, "tenant_id"));
+    }
+
+    #[test]
+    fn check_filter_needs_every_key_field_or_id() {
+        let key = &["tenant_id", "region"];
+        let missing = |filter, by_id| match check_filter(key, &filter, by_id) {
+            Err(Error::MissingShardKey { field, from: "filter" }) => Some(field),
+            _ => None,
+        };
+        assert_eq!(missing(doc! { "tenant_id": 1, "region": "eu" }, false), None);
+        assert_eq!(missing(doc! { "tenant_id": 1 }, false), Some("region".to_string()));
+        assert_eq!(missing(doc! { "_id": 1 }, true), None);
+        assert_eq!(missing(doc! { "_id": 1 }, false), Some("tenant_id".to_string()));
+        assert!(check_filter(&[], &doc! {}, false).is_ok());
+    }
+
+    #[test]
+    fn scoped_adds_the_key_of_the_document() {
+        let document = doc! { "_id": 5, "tenant": { "id": 1 }, "name": "a" };
+        let filter = scoped(&["tenant.id"], doc! { "_id": 5 }, &document).unwrap();
+        assert_eq!(filter, doc! { "_id": 5, "tenant.id": 1 });
+        assert!(check_document(&["tenant.id"], &document).is_ok());
+        assert!(matches!(
+            scoped(&["region"], doc! {}, &document),
+            Err(Error::MissingShardKey { from: "document", .. })
+        ));
+    }
+}
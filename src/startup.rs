@@ -0,0 +1,194 @@
+// STARTUP CHECKS ==================================================================================================
+// `verify_startup` runs the checks a service wants before taking traffic, for every listed model at once, and
+// fails with one report of everything that's wrong:
+//
+//     rms::init(options, "shop")?;
+//     rms::verify_startup::<(User, Order, Invoice)>().await?;
+//
+//     let options = StartupOptions { ensure_indexes: false, sample: 200 };
+//     let report = rms::verify_startup_with::<(User, Order)>(options).await?;
+//
+// It pings the server, creates the declared indexes (`ensure_indexes()`, skipped while `read_only`) and compares
+// them with the server's, then deserializes up to `sample` random documents of each collection as the model.
+// Missing or mismatched indexes and documents that don't deserialize fail it with `Error::StartupFailed`, extra
+// indexes only show in the report:
+//
+//     startup checks failed:
+//       orders: index { "customer_id": 1, "created_at": -1 } missing
+//       users: 2 of 200 sampled documents don't deserialize, 64f1…: missing field `name`
+//
+// Tuples of up to 12 models whose error type is `Error` are accepted. Models with their own go through
+// `verify_model::<M, AppError>` one by one.
+
+use futures::TryStreamExt;
+
+use crate::{error, Error, IndexDrift, RustMongoDBModelMethods};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartupOptions {
+    // Creates the missing indexes before comparing
+    pub ensure_indexes: bool,
+    // Documents per collection to deserialize, 0 for none
+    pub sample: u32,
+}
+
+impl Default for StartupOptions {
+    fn default() -> Self {
+        Self { ensure_indexes: true, sample: 0 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StartupReport {
+    // Round trip of the `ping` command
+    pub latency_ms: f64,
+    pub models: Vec<ModelReport>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelReport {
+    pub model: &'static str,
+    pub collection: String,
+    pub indexes: IndexDrift,
+    pub sampled: u64,
+    // Shortened `_id` and deserialization error of the sampled documents that failed
+    pub undecodable: Vec<(String, String)>,
+}
+
+impl ModelReport {
+    pub fn is_ok(&self) -> bool {
+        self.indexes.missing.is_empty() && self.indexes.mismatched.is_empty() && self.undecodable.is_empty()
+    }
+}
+
+impl StartupReport {
+    pub fn is_ok(&self) -> bool {
+        self.models.iter().all(ModelReport::is_ok)
+    }
+}
+
+impl std::fmt::Display for StartupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_ok() {
+            true => write!(f, "startup checks passed")?,
+            false => write!(f, "startup checks failed:")?,
+        }
+        for model in &self.models {
+            for index in &model.indexes.missing {
+                write!(f, "\n  {}: index {} missing", model.collection, index.keys_document())?;
+            }
+            for index in &model.indexes.mismatched {
+                let options: Vec<&str> = index.differences.iter().map(|x| x.option.as_str()).collect();
+                write!(f, "\n  {}: index `{}` differs in {}", model.collection, index.name, options.join(", "))?;
+            }
+            for name in &model.indexes.extra {
+                write!(f, "\n  {}: index `{}` not declared (not an error)", model.collection, name)?;
+            }
+            if let Some((id, reason)) = model.undecodable.first() {
+                write!(
+                    f,
+                    "\n  {}: {} of {} sampled documents don't deserialize, {}: {}",
+                    model.collection,
+                    model.undecodable.len(),
+                    model.sampled,
+                    id,
+                    reason
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait Models {
+    fn verify(options: StartupOptions) -> futures::future::BoxFuture<'static, Result<Vec<ModelReport>, Error>>;
+}
+
+macro_rules! models {
+    ($($model:ident),+) => {
+        impl<$($model: RustMongoDBModelMethods),+> Models for ($($model,)+) {
+            fn verify(options: StartupOptions) -> futures::future::BoxFuture<'static, Result<Vec<ModelReport>, Error>> {
+                Box::pin(async move { Ok(vec![$(verify_model::<$model, Error>(options).await?),+]) })
+            }
+        }
+    };
+}
+
+models!(A);
+models!(A, B);
+models!(A, B, C);
+models!(A, B, C, D);
+models!(A, B, C, D, F);
+models!(A, B, C, D, F, G);
+models!(A, B, C, D, F, G, H);
+models!(A, B, C, D, F, G, H, I);
+models!(A, B, C, D, F, G, H, I, J);
+models!(A, B, C, D, F, G, H, I, J, K);
+models!(A, B, C, D, F, G, H, I, J, K, L);
+models!(A, B, C, D, F, G, H, I, J, K, L, N);
+
+pub async fn verify_startup<T: Models>() -> Result<StartupReport, Error> {
+    verify_startup_with::<T>(StartupOptions::default()).await
+}
+
+// The report when every check passed, `Error::StartupFailed` with it otherwise
+pub async fn verify_startup_with<T: Models>(options: StartupOptions) -> Result<StartupReport, Error> {
+    let latency_ms = ping().await?;
+    finish(StartupReport { latency_ms, models: T::verify(options).await? })
+}
+
+// Round trip in milliseconds
+pub(crate) async fn ping() -> Result<f64, Error> {
+    let admin = crate::connection::connection()?.client.database("admin");
+    let context = crate::ErrorContext::new(admin.name(), "verify_startup");
+    let started = std::time::Instant::now();
+    admin.run_command(bson::doc! { "ping": 1 }, None).await.map_err(|x| context.wrap(x))?;
+    Ok(started.elapsed().as_secs_f64() * 1000.0)
+}
+
+pub(crate) fn finish(report: StartupReport) -> Result<StartupReport, Error> {
+    match report.is_ok() {
+        true => Ok(report),
+        false => Err(Error::StartupFailed(report)),
+    }
+}
+
+// The checks of `verify_startup` for one model, whatever its error type
+pub async fn verify_model<M, E>(options: StartupOptions) -> Result<ModelReport, Error>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+    let collection = crate::writes::<M, E>().clone_with_type::<bson::Document>();
+    let context = crate::ErrorContext::new(collection.name(), "verify_startup");
+    let declared = crate::declared_indexes::<M, E>();
+
+    if options.ensure_indexes && !declared.is_empty() && !M::read_only() {
+        let models = declared.iter().map(crate::Index::to_model);
+        collection.create_indexes(models, None).await.map_err(|x| context.wrap(x))?;
+    }
+    let existing: Vec<mongodb::IndexModel> = match collection.list_indexes(None).await {
+        Ok(cursor) => cursor.try_collect().await.map_err(|x| context.wrap(x))?,
+        // No collection yet, so none of its indexes
+        Err(x) if matches!(x.kind.as_ref(), mongodb::error::ErrorKind::Command(x) if x.code == 26) => Vec::new(),
+        Err(x) => return Err(context.wrap(x)),
+    };
+    let indexes = IndexDrift::compare(&declared, &existing);
+
+    let mut sampled = 0;
+    let mut undecodable = Vec::new();
+    if options.sample > 0 {
+        let pipeline = vec![bson::doc! { "$sample": { "size": i64::from(options.sample) } }];
+        let mut cursor = collection.aggregate(pipeline, None).await.map_err(|x| context.wrap(x))?;
+        while let Some(document) = cursor.try_next().await.map_err(|x| context.wrap(x))? {
+            sampled += 1;
+            let id = document.get("_id").map(error::shorten_id).unwrap_or_default();
+            if let Err(x) = bson::from_document::<M>(document) {
+                undecodable.push((id, x.to_string()));
+            }
+        }
+    }
+
+    let collection = collection.name().to_string();
+    Ok(ModelReport { model: error::model_name::<M>(), collection, indexes, sampled, undecodable })
+}
@@ -0,0 +1,467 @@
+// BLOCKING API ====================================================================================================
+// The `sync` feature runs the async methods on an internal multi-threaded tokio runtime, for CLI tools and
+// scripts without an async main. The client must be created on that same runtime, since the driver spawns
+// its background tasks wherever it's constructed:
+//
+//     use rust_mongodb_model_methods::sync::{self, RustMongoDBModelMethodsSync};
+//
+//     let client = sync::block_on(mongodb::Client::with_uri_str("mongodb://localhost"))?;
+//     let users = User::find(doc! { "active": true })?;
+//
+// Import this trait instead of the async one: with both in scope every call is ambiguous. The model's impl
+// can name the async trait by path (`impl rust_mongodb_model_methods::RustMongoDBModelMethods for User`).
+// Don't call these from inside an async context: blocking a runtime thread panics.
+
+use crate::{
+    array, map, projection, Duplicate, Error, FacetedResults, Field, FilterExpr, IdOf, IdType, IndexDrift, ListParams,
+    Page, PageOptions, Push, RustMongoDBModelMethods, SchemaViolation, UpdateSummary, UpsertStatus,
+};
+
+static RUNTIME: std::sync::OnceLock<tokio::runtime::Runtime> = std::sync::OnceLock::new();
+
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("rust_mongodb_model_methods")
+            .build()
+            .expect("failed to start the blocking API runtime")
+    })
+}
+
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    runtime().block_on(future)
+}
+
+// Blocking versions of the methods taking arguments and returning a result, implemented for every model, with the
+// same names and arguments. Streams (`tail`, `watch`), methods taking progress callbacks or a cancellation token,
+// and those of optional features apart from `decimal` (search, geo, backups, ...) have none: run them with
+// `block_on(User::watch(pipeline))` and friends.
+pub trait RustMongoDBModelMethodsSync<E = Error>: RustMongoDBModelMethods<E>
+where
+    E: From<Error>,
+{
+    fn find(filter: bson::Document) -> Result<Vec<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find(filter))
+    }
+
+    fn find_with_options(filter: bson::Document, options: mongodb::options::FindOptions) -> Result<Vec<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_with_options(filter, options))
+    }
+
+    fn find_one(filter: bson::Document) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_one(filter))
+    }
+
+    fn find_one_with_options(
+        filter: bson::Document,
+        options: mongodb::options::FindOneOptions,
+    ) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_one_with_options(filter, options))
+    }
+
+    fn find_first(filter: bson::Document, sort: bson::Document) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_first(filter, sort))
+    }
+
+    fn find_latest(field: &str) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_latest(field))
+    }
+
+    fn find_one_strict(filter: bson::Document) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_one_strict(filter))
+    }
+
+    fn find_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_by_id(id))
+    }
+
+    fn find_by_id_strict<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_by_id_strict(id))
+    }
+
+    fn find_by_id_until<I: IdOf<Self> + Sync + ?Sized>(id: &I, timeout: std::time::Duration) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_by_id_until(id, timeout))
+    }
+
+    fn find_list(params: &ListParams) -> Result<Page<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_list(params))
+    }
+
+    fn find_by_ids<I: IdOf<Self> + Sync>(ids: &[I]) -> Result<Vec<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_by_ids(ids))
+    }
+
+    fn find_map_by_id(filter: bson::Document) -> Result<std::collections::HashMap<IdType, Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_map_by_id(filter))
+    }
+
+    fn find_map_by<K>(field: &str, filter: bson::Document) -> Result<std::collections::HashMap<K, Self>, E>
+    where
+        K: serde::de::DeserializeOwned + Eq + std::hash::Hash + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_map_by(field, filter))
+    }
+
+    fn find_as<P>(filter: bson::Document, projection: Option<bson::Document>) -> Result<Vec<P>, E>
+    where
+        P: serde::de::DeserializeOwned + Send + Sync + Unpin,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_as(filter, projection))
+    }
+
+    fn find_projected<P: projection::Projection<Of = Self>>(filter: bson::Document) -> Result<Vec<P>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_projected(filter))
+    }
+
+    fn find_redacted(filter: bson::Document) -> Result<Vec<bson::Document>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_redacted(filter))
+    }
+
+    fn pluck<T: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Vec<T>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pluck(field, filter))
+    }
+
+    fn find_page_with_total(filter: bson::Document, options: PageOptions) -> Result<Page<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_page_with_total(filter, options))
+    }
+
+    fn faceted_search(
+        filter: bson::Document,
+        facets: &[&str],
+        options: PageOptions,
+    ) -> Result<FacetedResults<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::faceted_search(filter, facets, options))
+    }
+
+    fn aggregate<T: serde::de::DeserializeOwned + Send>(pipeline: Vec<bson::Document>) -> Result<Vec<T>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::aggregate(pipeline))
+    }
+
+    fn aggregate_with_options<T: serde::de::DeserializeOwned + Send>(
+        pipeline: Vec<bson::Document>,
+        options: mongodb::options::AggregateOptions,
+    ) -> Result<Vec<T>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::aggregate_with_options(pipeline, options))
+    }
+
+    fn sample(n: u64, filter: bson::Document) -> Result<Vec<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::sample(n, filter))
+    }
+
+    fn count_by<K: serde::de::DeserializeOwned>(field: &str) -> Result<Vec<(K, u64)>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::count_by(field))
+    }
+
+    fn sum_by<K, V>(group_field: &str, value_field: &str) -> Result<Vec<(K, V)>, E>
+    where
+        K: serde::de::DeserializeOwned,
+        V: serde::de::DeserializeOwned,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::sum_by(group_field, value_field))
+    }
+
+    fn find_duplicates(fields: &[&str], filter: bson::Document) -> Result<Vec<Duplicate>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_duplicates(fields, filter))
+    }
+
+    fn max_of<V: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Option<V>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::max_of(field, filter))
+    }
+
+    fn min_of<V: serde::de::DeserializeOwned>(field: &str, filter: bson::Document) -> Result<Option<V>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::min_of(field, filter))
+    }
+
+    fn validate_collection() -> Result<Vec<SchemaViolation>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::validate_collection())
+    }
+
+    fn ensure_indexes() -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::ensure_indexes())
+    }
+
+    fn check_indexes() -> Result<IndexDrift, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::check_indexes())
+    }
+
+    fn check_indexes_strict() -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::check_indexes_strict())
+    }
+
+    fn count(filter: bson::Document) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::count(filter))
+    }
+
+    fn count_where(filter: FilterExpr) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::count_where(filter))
+    }
+
+    fn exists(filter: bson::Document) -> Result<bool, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::exists(filter))
+    }
+
+    fn exists_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<bool, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::exists_by_id(id))
+    }
+
+    fn estimated_count() -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::estimated_count())
+    }
+
+    fn create_one(data: &Self) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::create_one(data))
+    }
+
+    fn update_one<D: serde::Serialize + Send>(filter: bson::Document, data: D) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_one(filter, data))
+    }
+
+    fn update_one_modified<D>(filter: bson::Document, data: D) -> Result<(Self, bool), E>
+    where
+        D: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_one_modified(filter, data))
+    }
+
+    fn update_one_with(
+        filter: bson::Document,
+        update: mongodb::options::UpdateModifications,
+    ) -> Result<(Self, bool), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_one_with(filter, update))
+    }
+
+    fn set_field<I, T>(id: &I, field: Field<Self, T>, value: T) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        T: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::set_field(id, field, value))
+    }
+
+    fn unset_field<I, T>(id: &I, field: Field<Self, T>) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        T: Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::unset_field(id, field))
+    }
+
+    fn drop_field(field: &str, filter: bson::Document, dry_run: bool) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::drop_field(field, filter, dry_run))
+    }
+
+    fn update_one_pipeline(filter: bson::Document, pipeline: Vec<bson::Document>) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_one_pipeline(filter, pipeline))
+    }
+
+    fn update_many_with(
+        filter: bson::Document,
+        update: mongodb::options::UpdateModifications,
+    ) -> Result<UpdateSummary, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_many_with(filter, update))
+    }
+
+    fn update_many_pipeline(filter: bson::Document, pipeline: Vec<bson::Document>) -> Result<UpdateSummary, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_many_pipeline(filter, pipeline))
+    }
+
+    fn update_by_id<I, D>(id: &I, data: D) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        D: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_by_id(id, data))
+    }
+
+    fn find_one_and_replace(filter: bson::Document, data: &Self, upsert: bool) -> Result<Option<Self>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::find_one_and_replace(filter, data, upsert))
+    }
+
+    fn update_by_ids<I, D>(ids: &[I], data: D) -> Result<UpdateSummary, E>
+    where
+        I: IdOf<Self> + Sync,
+        D: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update_by_ids(ids, data))
+    }
+
+    fn upsert_many<D: serde::Serialize + Sync>(pairs: &[(bson::Document, D)]) -> Result<Vec<UpsertStatus>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::upsert_many(pairs))
+    }
+
+    fn increment_and_get<I, N>(id: &I, field: &str, by: N) -> Result<N, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        N: Into<bson::Bson> + serde::de::DeserializeOwned + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::increment_and_get(id, field, by))
+    }
+
+    #[cfg(feature = "decimal")]
+    fn increment_decimal_by_id<I: IdOf<Self> + Sync + ?Sized>(
+        id: &I,
+        field: &str,
+        amount: &rust_decimal::Decimal,
+    ) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::increment_decimal_by_id(id, field, amount))
+    }
+
+    fn push<I, A>(id: &I, field: Field<Self, A>, push: Push<A::Item>) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::push(id, field, push))
+    }
+
+    fn pull<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pull(id, field, value))
+    }
+
+    fn pull_where<I, A>(id: &I, field: Field<Self, A>, condition: bson::Document) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pull_where(id, field, condition))
+    }
+
+    fn add_to_set<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::add_to_set(id, field, value))
+    }
+
+    fn remove_from_set<I, A>(id: &I, field: Field<Self, A>, value: A::Item) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::remove_from_set(id, field, value))
+    }
+
+    fn pop_first<I, A>(id: &I, field: Field<Self, A>) -> Result<Option<A::Item>, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::de::DeserializeOwned + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pop_first(id, field))
+    }
+
+    fn pop_last<I, A>(id: &I, field: Field<Self, A>) -> Result<Option<A::Item>, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+        A::Item: serde::de::DeserializeOwned + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pop_last(id, field))
+    }
+
+    fn pop<T: serde::de::DeserializeOwned>(id: &IdType, field: &str, pop: i32) -> Result<Option<T>, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::pop(id, field, pop))
+    }
+
+    fn truncate_array<I, A>(id: &I, field: Field<Self, A>, n: u32) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        A: array::Array + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::truncate_array(id, field, n))
+    }
+
+    fn set_map_entry<I, M>(id: &I, field: Field<Self, M>, key: &str, value: M::Value) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        M: map::Map + Send,
+        M::Value: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::set_map_entry(id, field, key, value))
+    }
+
+    fn remove_map_entry<I, M>(id: &I, field: Field<Self, M>, key: &str) -> Result<Self, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        M: map::Map + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::remove_map_entry(id, field, key))
+    }
+
+    fn schedule_update<I, D>(id: &I, at: bson::DateTime, changes: D) -> Result<bson::oid::ObjectId, E>
+    where
+        I: IdOf<Self> + Sync + ?Sized,
+        D: serde::Serialize + Send,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::schedule_update(id, at, changes))
+    }
+
+    fn cancel_scheduled(operation: bson::oid::ObjectId) -> Result<bool, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::cancel_scheduled(operation))
+    }
+
+    fn run_due_operations() -> Result<u64, E>
+    where
+        E: std::fmt::Display,
+    {
+        block_on(<Self as RustMongoDBModelMethods<E>>::run_due_operations())
+    }
+
+    fn delete_one(filter: bson::Document) -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::delete_one(filter))
+    }
+
+    fn delete_by_id<I: IdOf<Self> + Sync + ?Sized>(id: &I) -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::delete_by_id(id))
+    }
+
+    fn export_extjson<W: std::io::Write + Send>(filter: bson::Document, writer: &mut W) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::export_extjson(filter, writer))
+    }
+
+    fn import_extjson<R: std::io::BufRead + Send>(reader: R) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::import_extjson(reader))
+    }
+
+    // Instance Methods
+    fn create(&self) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::create(self))
+    }
+
+    fn update<D: serde::Serialize + Send>(&self, data: D) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::update(self, data))
+    }
+
+    fn delete(&self) -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::delete(self))
+    }
+
+    fn reload(&self) -> Result<Self, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::reload(self))
+    }
+
+    fn validate_unique(&self, fields: &[&str]) -> Result<(), E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::validate_unique(self, fields))
+    }
+
+    fn apply_mirrors(&self) -> Result<u64, E> {
+        block_on(<Self as RustMongoDBModelMethods<E>>::apply_mirrors(self))
+    }
+}
+
+impl<T, E> RustMongoDBModelMethodsSync<E> for T
+where
+    T: RustMongoDBModelMethods<E>,
+    E: From<Error>,
+{
+}
@@ -0,0 +1,129 @@
+// TYPED IDS =======================================================================================================
+// `Id<M>` is an `IdType` that remembers which model it belongs to, so an `Id<User>` can't be passed to
+// `Order::find_by_id`. It serializes exactly like the raw ID, so it can be used as the `_id` field itself:
+//
+//     #[derive(Serialize, Deserialize)]
+//     struct Order {
+//         #[serde(rename = "_id")]
+//         id: Id<Order>,
+//         customer_id: Id<Customer>,
+//     }
+//
+// The `*_by_id` methods accept anything implementing `IdOf<Self>`: the model's own `Id<M>` or a raw `IdType`.
+
+use crate::IdType;
+
+pub struct Id<M> {
+    raw: IdType,
+    _model: std::marker::PhantomData<fn() -> M>,
+}
+
+impl<M> Id<M> {
+    pub fn new(raw: IdType) -> Self {
+        Self { raw, _model: std::marker::PhantomData }
+    }
+
+    pub fn raw(&self) -> &IdType {
+        &self.raw
+    }
+
+    pub fn into_raw(self) -> IdType {
+        self.raw
+    }
+}
+
+pub trait IdOf<M> {
+    fn raw_id(&self) -> &IdType;
+}
+
+impl<M> IdOf<M> for IdType {
+    fn raw_id(&self) -> &IdType {
+        self
+    }
+}
+
+impl<M> IdOf<M> for Id<M> {
+    fn raw_id(&self) -> &IdType {
+        &self.raw
+    }
+}
+
+// Hand-written so `M` itself doesn't need to implement these traits
+impl<M> Clone for Id<M> {
+    // ObjectId and Uuid are Copy, String isn't
+    #[allow(clippy::clone_on_copy)]
+    fn clone(&self) -> Self {
+        Self::new(self.raw.clone())
+    }
+}
+
+impl<M> PartialEq for Id<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl<M> Eq for Id<M> {}
+
+impl<M> std::hash::Hash for Id<M> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.raw.hash(state)
+    }
+}
+
+impl<M> std::fmt::Debug for Id<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let model = std::any::type_name::<M>().rsplit("::").next().unwrap_or_default();
+        write!(f, "Id<{}>({})", model, self.raw)
+    }
+}
+
+impl<M> std::fmt::Display for Id<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError(pub String);
+
+impl std::fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid id: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+impl<M> std::str::FromStr for Id<M> {
+    type Err = ParseIdError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let raw = crate::id_type::parse(value).map_err(ParseIdError)?;
+        Ok(Self::new(raw))
+    }
+}
+
+impl<M> From<IdType> for Id<M> {
+    fn from(raw: IdType) -> Self {
+        Self::new(raw)
+    }
+}
+
+impl<M> From<Id<M>> for bson::Bson {
+    fn from(id: Id<M>) -> Self {
+        id.raw.into()
+    }
+}
+
+impl<M> serde::Serialize for Id<M> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de, M> serde::Deserialize<'de> for Id<M> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IdType::deserialize(deserializer).map(Self::new)
+    }
+}
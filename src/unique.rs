@@ -0,0 +1,280 @@
+// UNIQUENESS ======================================================================================================
+// Fields no two documents may share, checked before writes so handlers get one entry per taken field instead of
+// the server's duplicate key error:
+//
+//     fn unique_fields() -> &'static [&'static str] {
+//         &["email", "username"]
+//     }
+//
+//     if let Err(x) = user.validate_unique(&["email", "username"]).await { ... }
+//
+// `create_one`, `update_one*`, `set_field`, `update_by_ids`, `find_one_and_replace` and their `Session`
+// counterparts check the unique fields they write, against every document but the ones they write to, and fail
+// with `Error::Validation`, its `ValidationErrors` serializes to
+// `{ "errors": [{ "field": "email", "code": "taken", "message": "email is already taken" }] }` for responses.
+// `upsert_many` reports the item as `UpsertStatus::Failed` instead. `import_extjson`, `restore_from` and
+// `BufferedWriter` don't check, only the index applies to them. It's one lookup per field, not a constraint: two
+// writes can still race, keep the unique index too.
+//
+// Combinations unique together, e.g. an email per tenant, get their compound unique index from `ensure_indexes()`
+// as well (`tenant_id_1_email_1`, unless `indexes()` declares one on the same keys):
+//
+//     fn unique_together() -> &'static [&'static [&'static str]] {
+//         &[&["tenant_id", "email"]]
+//     }
+//
+// They're checked when a write sets all their fields. Writes setting only some, and writes losing the race, fail
+// on the index instead: `create_one`, the update methods and `find_one_and_replace` turn its duplicate key error
+// into the same `Error::Validation`, as they do for the unique index of a `unique_fields` field.
+//
+// `null` counts as not set, an optional field left `None` is never taken. The server's unique index disagrees
+// unless it's sparse or partial, declare it so in `indexes()` for optional fields.
+
+use futures::TryStreamExt;
+
+use crate::{breaker, CircuitBreaker, Error, Index};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct FieldError {
+    pub field: String,
+    // Machine-readable, `"taken"`
+    pub code: String,
+    pub message: String,
+}
+
+impl ValidationErrors {
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // The last of `fields` is the one reported, "email is already taken for this tenant_id"
+    pub fn taken(&mut self, fields: &[&str]) {
+        let Some((field, scope)) = fields.split_last() else {
+            return;
+        };
+        let message = match scope.is_empty() {
+            true => format!("{} is already taken", field),
+            false => format!("{} is already taken for this {}", field, scope.join(" and ")),
+        };
+        self.errors.push(FieldError { field: field.to_string(), code: "taken".to_string(), message });
+    }
+}
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.errors.iter().map(|x| x.message.as_str()).collect();
+        write!(f, "{}", messages.join(", "))
+    }
+}
+
+// Unique fields, then combinations
+pub(crate) fn groups<'a>(fields: &'a [&'a str], together: &'a [&'a [&'a str]]) -> Vec<&'a [&'a str]> {
+    fields.iter().map(std::slice::from_ref).chain(together.iter().copied()).collect()
+}
+
+// The filter for the values `document` sets `group` to, `None` unless it sets every field. `null` is no value: an
+// unset optional field isn't taken by the documents without it.
+pub(crate) fn values(group: &[&str], document: &bson::Document) -> Option<bson::Document> {
+    let mut values = bson::Document::new();
+    for field in group {
+        match crate::path_value(document, field)? {
+            bson::Bson::Null => return None,
+            x => values.insert(field.to_string(), x.clone()),
+        };
+    }
+    Some(values).filter(|x| !x.is_empty())
+}
+
+// The groups whose fields `document` all sets to values another document outside `exclude` already has
+pub(crate) async fn conflicts(
+    collection: &mongodb::Collection<bson::Document>,
+    groups: &[&[&str]],
+    document: &bson::Document,
+    exclude: &bson::Document,
+    collation: Option<mongodb::options::Collation>,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<ValidationErrors, Error> {
+    let mut errors = ValidationErrors::default();
+    for group in groups {
+        let Some(mut filter) = values(group, document) else {
+            continue;
+        };
+        filter.insert("$nor", vec![exclude.clone()]);
+        // Existence right after another write, so on the primary
+        let options = mongodb::options::FindOneOptions::builder()
+            .projection(bson::doc! { "_id": 1 })
+            .collation(collation.clone())
+            .selection_criteria(crate::read_back().selection_criteria)
+            .build();
+        if breaker::guard(breaker, collection.find_one(filter, options)).await?.is_some() {
+            errors.taken(group);
+        }
+    }
+    Ok(errors)
+}
+
+// `$unionWith` branches per `aggregate` of `conflicts_many`, well under the server's 1000 stages
+const BRANCHES: usize = 100;
+
+// `conflicts` of each `(document, exclude)` of `items`, one `aggregate` per 100 lookups instead of a `find_one` each
+pub(crate) async fn conflicts_many(
+    collection: &mongodb::Collection<bson::Document>,
+    groups: &[&[&str]],
+    items: &[(&bson::Document, &bson::Document)],
+    collation: Option<mongodb::options::Collation>,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<Vec<ValidationErrors>, Error> {
+    // The item and group of each branch
+    let mut lookups = Vec::new();
+    let mut branches = Vec::new();
+    for (item, (document, exclude)) in items.iter().enumerate() {
+        for (index, group) in groups.iter().enumerate() {
+            let Some(mut filter) = values(group, document) else {
+                continue;
+            };
+            filter.insert("$nor", vec![(*exclude).clone()]);
+            branches.push(vec![
+                bson::doc! { "$match": filter },
+                bson::doc! { "$limit": 1 },
+                bson::doc! { "$project": { "_id": 0, "branch": { "$literal": lookups.len() as i64 } } },
+            ]);
+            lookups.push((item, index));
+        }
+    }
+
+    let mut errors: Vec<ValidationErrors> = items.iter().map(|_| ValidationErrors::default()).collect();
+    // Existence right after another write, so on the primary
+    let options = mongodb::options::AggregateOptions::builder()
+        .collation(collation)
+        .selection_criteria(crate::read_back().selection_criteria)
+        .build();
+    for chunk in branches.chunks(BRANCHES) {
+        let Some((first, rest)) = chunk.split_first() else {
+            continue;
+        };
+        let mut pipeline = first.clone();
+        for branch in rest {
+            pipeline.push(bson::doc! { "$unionWith": { "coll": collection.name(), "pipeline": branch.clone() } });
+        }
+        let cursor = breaker::guard(breaker, collection.aggregate(pipeline, options.clone())).await?;
+        for found in cursor.try_collect::<Vec<bson::Document>>().await? {
+            let lookup = found.get("branch").and_then(crate::as_u64).and_then(|x| lookups.get(x as usize));
+            if let Some(&(item, group)) = lookup {
+                errors[item].taken(groups[group]);
+            }
+        }
+    }
+    Ok(errors)
+}
+
+// Compound unique indexes of the combinations `declared` has no index on the same keys for
+pub(crate) fn indexes(together: &[&[&str]], declared: &[Index]) -> Vec<Index> {
+    let mut indexes = Vec::new();
+    for fields in together {
+        let Some((first, rest)) = fields.split_first() else {
+            continue;
+        };
+        let index = rest.iter().fold(Index::asc(first), |x, field| x.then_asc(field)).unique();
+        if !declared.iter().any(|x| x.keys_document() == index.keys_document()) {
+            indexes.push(index);
+        }
+    }
+    indexes
+}
+
+// `Error::Validation` for a duplicate key error on the unique index of one of `groups`
+pub(crate) fn duplicate_key(error: Error, groups: &[&[&str]], indexes: &[Index]) -> Error {
+    let message = match &error {
+        Error::DBError(x) => match x.kind.as_ref() {
+            mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(x)) if x.code == 11000 => {
+                x.message.as_str()
+            }
+            mongodb::error::ErrorKind::Command(x) if x.code == 11000 => x.message.as_str(),
+            _ => return error,
+        },
+        _ => return error,
+    };
+    // "E11000 duplicate key error collection: shop.users index: tenant_id_1_email_1 dup key: { ... }"
+    let Some(name) = message.split(" index: ").nth(1).and_then(|x| x.split(' ').next()) else {
+        return error;
+    };
+
+    let fields_of = |index: &Index| -> Vec<String> { index.keys.iter().map(|(x, _)| x.clone()).collect() };
+    let named = indexes.iter().find(|x| x.options.name.as_deref() == Some(name)).map(fields_of);
+    let group = groups.iter().find(|group| match &named {
+        Some(fields) => fields.iter().eq(group.iter()),
+        None => group.iter().map(|x| format!("{}_1", x)).collect::<Vec<_>>().join("_") == name,
+    });
+    match group {
+        Some(group) => {
+            let mut errors = ValidationErrors::default();
+            errors.taken(group);
+            Error::Validation(errors)
+        }
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+    use mongodb::error::{ErrorKind, WriteFailure};
+
+    fn duplicate(index: &str) -> Error {
+        let message = format!("E11000 duplicate key error collection: shop.users index: {} dup key: {{ }}", index);
+        let error = doc! { "code": 11000, "codeName": "DuplicateKey", "errmsg": message };
+        Error::DBError(ErrorKind::Command(bson::from_document(error).unwrap()).into())
+    }
+
+    fn taken(error: Error) -> Option<String> {
+        match error {
+            Error::Validation(x) => x.errors.first().map(|x| x.message.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn duplicate_key_names_the_group_of_the_default_index_name() {
+        let groups: &[&[&str]] = &[&["email"], &["tenant_id", "email"]];
+        let expected = Some("email is already taken for this tenant_id".to_string());
+        assert_eq!(taken(duplicate_key(duplicate("tenant_id_1_email_1"), groups, &[])), expected);
+        assert_eq!(taken(duplicate_key(duplicate("email_1"), groups, &[])), Some("email is already taken".to_string()));
+        assert_eq!(taken(duplicate_key(duplicate("email_-1"), groups, &[])), None);
+        assert_eq!(taken(duplicate_key(duplicate("_id_"), groups, &[])), None);
+    }
+
+    #[test]
+    fn duplicate_key_reads_the_fields_of_a_named_index() {
+        let groups: &[&[&str]] = &[&["tenant_id", "email"]];
+        let indexes = [Index::asc("tenant_id").then_asc("email").unique().name("tenant_email")];
+        let expected = Some("email is already taken for this tenant_id".to_string());
+        assert_eq!(taken(duplicate_key(duplicate("tenant_email"), groups, &indexes)), expected);
+        let reordered = [Index::asc("email").then_asc("tenant_id").unique().name("tenant_id_1_email_1")];
+        assert_eq!(taken(duplicate_key(duplicate("tenant_id_1_email_1"), groups, &reordered)), None);
+    }
+
+    #[test]
+    fn duplicate_key_keeps_other_errors() {
+        let write =
+            doc! { "code": 11000, "errmsg": "E11000 duplicate key error collection: shop.users index: email_1" };
+        let write = ErrorKind::Write(WriteFailure::WriteError(bson::from_document(write).unwrap()));
+        assert!(taken(duplicate_key(Error::DBError(write.into()), &[&["email"]], &[])).is_some());
+
+        let other = doc! { "code": 2, "errmsg": "E11000 duplicate key error collection: shop.users index: email_1" };
+        let other = Error::DBError(ErrorKind::Command(bson::from_document(other).unwrap()).into());
+        assert!(matches!(duplicate_key(other, &[&["email"]], &[]), Error::DBError(_)));
+        let unparsed = doc! { "code": 11000, "errmsg": "E11000 duplicate key error" };
+        let unparsed = Error::DBError(ErrorKind::Command(bson::from_document(unparsed).unwrap()).into());
+        assert!(matches!(duplicate_key(unparsed, &[&["email"]], &[]), Error::DBError(_)));
+        let params = Error::InvalidParams("per_page".to_string());
+        assert!(matches!(duplicate_key(params, &[&["email"]], &[]), Error::InvalidParams(_)));
+    }
+}
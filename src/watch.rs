@@ -0,0 +1,157 @@
+// CHANGE STREAMS ==================================================================================================
+// `watch` follows the collection's change stream, `watch_resumable` also persists the resume token after every
+// event so a restarted consumer picks up where the previous one stopped:
+//
+//     let store = CollectionTokenStore::new(db.collection("resume_tokens"));
+//     let mut events = User::watch_resumable("search-indexer", vec![], store);
+//     while let Some(event) = events.next().await {
+//         index(event?).await;
+//     }
+//
+// An event's token is saved when the next one is requested, so an event is only skipped after a restart once
+// the consumer has finished with it (at-least-once delivery). The driver retries resumable errors once by
+// itself; when that's not enough the stream is reopened after a second from the last token, yielding the error.
+
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+use mongodb::change_stream::event::{ChangeStreamEvent, ResumeToken};
+use mongodb::change_stream::ChangeStream;
+
+use crate::{Error, ErrorContext, RustMongoDBModelMethods};
+
+#[async_trait::async_trait]
+pub trait ResumeTokenStore: Send + Sync {
+    async fn load(&self, name: &str) -> Result<Option<ResumeToken>, Error>;
+    async fn save(&self, name: &str, token: &ResumeToken) -> Result<(), Error>;
+}
+
+// One `{ _id: name, token, updated_at }` document per consumer
+pub struct CollectionTokenStore {
+    collection: mongodb::Collection<bson::Document>,
+}
+
+impl CollectionTokenStore {
+    pub fn new(collection: mongodb::Collection<bson::Document>) -> Self {
+        Self { collection }
+    }
+}
+
+#[async_trait::async_trait]
+impl ResumeTokenStore for CollectionTokenStore {
+    async fn load(&self, name: &str) -> Result<Option<ResumeToken>, Error> {
+        let document = self.collection.find_one(bson::doc! { "_id": name }, None).await?;
+        match document.and_then(|mut x| x.remove("token")) {
+            Some(token) => Ok(Some(bson::from_bson(token)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, name: &str, token: &ResumeToken) -> Result<(), Error> {
+        let update = bson::doc! { "$set": { "token": bson::to_bson(token)?, "updated_at": bson::DateTime::now() } };
+        let options = mongodb::options::UpdateOptions::builder().upsert(true).build();
+        self.collection.update_one(bson::doc! { "_id": name }, update, options).await?;
+        Ok(())
+    }
+}
+
+struct Watch<M: serde::de::DeserializeOwned> {
+    pipeline: Vec<bson::Document>,
+    context: ErrorContext,
+    store: Option<(String, Arc<dyn ResumeTokenStore>)>,
+    stream: Option<ChangeStream<ChangeStreamEvent<M>>>,
+    token: Option<ResumeToken>,
+    unsaved: bool,
+    loaded: bool,
+    reconnect: bool,
+}
+
+pub(crate) fn stream<M, E>(
+    pipeline: Vec<bson::Document>,
+    store: Option<(String, Arc<dyn ResumeTokenStore>)>,
+) -> futures::stream::BoxStream<'static, Result<ChangeStreamEvent<M>, E>>
+where
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error> + Send + 'static,
+{
+    let context = ErrorContext::new(M::collection().name(), "watch");
+    let loaded = store.is_none();
+    let state = Watch { pipeline, context, store, stream: None, token: None, unsaved: false, loaded, reconnect: false };
+
+    futures::stream::unfold(state, |mut state| async move {
+        loop {
+            // The consumer asked for the next event, so it's done with the previous one
+            if state.unsaved {
+                if let (Some((name, store)), Some(token)) = (&state.store, &state.token) {
+                    if let Err(x) = store.save(name, token).await {
+                        return Some((Err(M::map_error(state.context.wrap(x))), state));
+                    }
+                }
+                state.unsaved = false;
+            }
+
+            if state.stream.is_none() {
+                if state.reconnect {
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+                state.reconnect = true;
+
+                if !state.loaded {
+                    if let Some((name, store)) = &state.store {
+                        match store.load(name).await {
+                            Ok(token) => state.token = token,
+                            Err(x) => return Some((Err(M::map_error(state.context.wrap(x))), state)),
+                        }
+                    }
+                    state.loaded = true;
+                }
+
+                // `start_after` instead of `resume_after` also gets past an invalidate event
+                let options = mongodb::options::ChangeStreamOptions::builder()
+                    .full_document(Some(mongodb::options::FullDocumentType::UpdateLookup))
+                    .start_after(state.token.clone())
+                    .build();
+                match M::collection().watch(state.pipeline.clone(), options).await {
+                    Ok(stream) => state.stream = Some(stream),
+                    Err(x) => return Some((Err(M::map_error(state.context.wrap(x))), state)),
+                }
+            }
+
+            let next = match state.stream.as_mut() {
+                Some(stream) => stream.try_next().await,
+                None => continue,
+            };
+            match next {
+                Ok(Some(event)) => {
+                    state.token = Some(event.id.clone());
+                    state.unsaved = true;
+                    return Some((Ok(event), state));
+                }
+                // Closed after an invalidate event (collection dropped or renamed)
+                Ok(None) => state.stream = None,
+                Err(x) => {
+                    state.stream = None;
+                    return Some((Err(M::map_error(state.context.wrap(x))), state));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+// Full documents of insert, update and replace events, ending with the document's deletion
+pub(crate) fn versions<M, E>(
+    events: futures::stream::BoxStream<'static, Result<ChangeStreamEvent<M>, E>>,
+) -> futures::stream::BoxStream<'static, Result<M, E>>
+where
+    M: serde::de::DeserializeOwned + Send + 'static,
+    E: Send + 'static,
+{
+    use mongodb::change_stream::event::OperationType;
+
+    events
+        .take_while(|x| std::future::ready(!matches!(x, Ok(event) if event.operation_type == OperationType::Delete)))
+        // An update's lookup comes back empty when the document was deleted in the meantime
+        .filter_map(|x| std::future::ready(x.map(|x| x.full_document).transpose()))
+        .boxed()
+}
@@ -0,0 +1,84 @@
+// AXUM ============================================================================================================
+// `Loaded<M>` reads the `:id` path parameter, loads the document with `find_by_id_strict` and rejects the
+// request with the model's error response, 404 for `Error::NotFound`:
+//
+//     async fn show(user: Loaded<User>) -> Json<User> {
+//         Json(user.into_inner())
+//     }
+//
+//     Router::new().route("/users/:id", get(show))
+//
+// Models with a custom error type use `Loaded<User, MyError>`, which requires `MyError: IntoResponse`.
+
+use axum::extract::{FromRequestParts, Path};
+use axum::http::{request::Parts, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use crate::{Error, ErrorContext, Id, RustMongoDBModelMethods};
+
+pub struct Loaded<M, E = Error> {
+    pub item: M,
+    _error: std::marker::PhantomData<fn() -> E>,
+}
+
+impl<M, E> Loaded<M, E> {
+    pub fn into_inner(self) -> M {
+        self.item
+    }
+}
+
+impl<M, E> std::ops::Deref for Loaded<M, E> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.item
+    }
+}
+
+#[async_trait::async_trait]
+impl<S, M, E> FromRequestParts<S> for Loaded<M, E>
+where
+    S: Send + Sync,
+    M: RustMongoDBModelMethods<E>,
+    E: From<Error> + IntoResponse,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<std::collections::HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let Some(raw) = params.get("id") else {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Route has no :id parameter").into_response());
+        };
+        let id: Id<M> = raw.parse().map_err(|x: crate::typed_id::ParseIdError| {
+            let context = ErrorContext::new(M::collection().name(), "find_by_id_strict");
+            M::map_error(context.wrap(Error::InvalidId(x.0))).into_response()
+        })?;
+
+        let item = M::find_by_id_strict(&id).await.map_err(IntoResponse::into_response)?;
+        Ok(Self { item, _error: std::marker::PhantomData })
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let (status, message) = match self.root() {
+            // "User 64ab… not found"
+            x @ Error::NotFound { .. } => (StatusCode::NOT_FOUND, x.to_string()),
+            Error::InvalidId(reason) => (StatusCode::BAD_REQUEST, format!("Invalid id: {}", reason)),
+            Error::InvalidParams(reason) => (StatusCode::BAD_REQUEST, reason.clone()),
+            x @ Error::BrokenReference { .. } => (StatusCode::UNPROCESSABLE_ENTITY, x.to_string()),
+            x @ Error::ImmutableField(_) => (StatusCode::UNPROCESSABLE_ENTITY, x.to_string()),
+            Error::Validation(x) => (StatusCode::UNPROCESSABLE_ENTITY, x.to_string()),
+            x @ Error::MissingShardKey { .. } => (StatusCode::BAD_REQUEST, x.to_string()),
+            Error::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "Timed out".to_string()),
+            Error::CircuitOpen => (StatusCode::SERVICE_UNAVAILABLE, "Service unavailable".to_string()),
+            Error::ReadOnly => (StatusCode::SERVICE_UNAVAILABLE, "Read-only mode".to_string()),
+            // Server-side details stay in the logs, not in the response body
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()),
+        };
+        (status, message).into_response()
+    }
+}
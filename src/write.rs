@@ -0,0 +1,340 @@
+// BULK WRITES =====================================================================================================
+// `upsert_many` sends `(filter, data)` pairs as one unordered batch of upserts and reports what happened to each,
+// in the order they were given:
+//
+//     let pairs: Vec<_> = rows.iter().map(|x| (doc! { "sku": &x.sku }, x)).collect();
+//     for ((filter, _), status) in pairs.iter().zip(Product::upsert_many(&pairs).await?) {
+//         if let UpsertStatus::Failed { message, .. } = status {
+//             println!("{filter}: {message}");
+//         }
+//     }
+//
+// Unordered means a failing item doesn't stop the others. `data` is `$set` like in `update_one`, its `_id` only
+// applies to created documents.
+//
+// `update_by_ids` applies one `$set` to every listed document and says which IDs matched none:
+//
+//     let summary = Order::update_by_ids(&ids, doc! { "status": "shipped" }).await?;
+//     if !summary.not_found.is_empty() { ... }
+//
+// `backfill` fills in a newly added field from the rest of each document, batch by batch, resumable from the last
+// `_id` it reported:
+//
+//     let options = BackfillOptions::default().pause(Duration::from_millis(200)).resume_after(saved.take());
+//     let display_name = |x: &Document| Some(format!("{} {}", x.get_str("first").ok()?, x.get_str("last").ok()?));
+//     User::backfill("display_name", doc! {}, options, display_name, |x| save(&x.last_id)).await?;
+//
+// Durability is declared once, not per call. The client's write concern and `retryWrites` (URI, or
+// `MONGODB_WRITE_CONCERN*` and `MONGODB_RETRY_WRITES` of `Config`) apply to every model, a model with other needs
+// overrides `write_concern()`:
+//
+//     fn write_concern() -> Option<WriteConcern> {
+//         let timeout = Duration::from_secs(5);
+//         Some(WriteConcern::builder().w(Acknowledgment::Majority).journal(true).w_timeout(timeout).build())
+//     }
+//
+// Every write method of the model and its `Session` counterparts use it, bulk commands included. Retrying is a
+// client setting, models needing other retry behaviour than the rest go on a `Router` route of their own. It
+// covers the driver's write helpers, not the raw commands this crate sends with `run_command`: `backfill`,
+// `refresh_derived`, `reencrypt` and `archive_older_than` resend a command once after a network error or step-down
+// themselves, their statements are safe to apply twice. `upsert_many` and `BufferedWriter` aren't retried, a resent upsert would
+// report a created document as `Updated` and a resent `$inc` or insert would apply twice.
+
+use crate::{breaker, id_type, CircuitBreaker, Error, IdType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpsertStatus {
+    Created(IdType),
+    // Matched an existing document, whether that changed it or not
+    Updated,
+    // `code` is the server's, 0 for an item not sent for a broken reference or a taken unique value
+    Failed { code: i32, message: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateSummary {
+    pub matched: u64,
+    pub modified: u64,
+    // Looked up only when fewer documents matched than IDs were given
+    pub not_found: Vec<IdType>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillOptions {
+    pub batch_size: u32,
+    // Wait between batches, the rate limit
+    pub pause: std::time::Duration,
+    // `last_id` of an interrupted run, documents up to it are skipped
+    pub resume_after: Option<bson::Bson>,
+}
+
+impl Default for BackfillOptions {
+    fn default() -> Self {
+        Self { batch_size: 500, pause: std::time::Duration::ZERO, resume_after: None }
+    }
+}
+
+impl BackfillOptions {
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn pause(mut self, pause: std::time::Duration) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    pub fn resume_after(mut self, id: Option<bson::Bson>) -> Self {
+        self.resume_after = id;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackfillProgress {
+    pub updated: u64,
+    // Documents the value function returned `None` for
+    pub skipped: u64,
+    // Last `_id` of the batches done so far, for `resume_after`
+    pub last_id: bson::Bson,
+}
+
+// `collection` with `write_concern` instead of its own, reads keep their settings
+pub(crate) fn with_write_concern<T>(
+    collection: mongodb::Collection<T>,
+    write_concern: Option<mongodb::options::WriteConcern>,
+) -> mongodb::Collection<T> {
+    let Some(write_concern) = write_concern else {
+        return collection;
+    };
+    let options = mongodb::options::CollectionOptions::builder()
+        .selection_criteria(collection.selection_criteria().cloned())
+        .read_concern(collection.read_concern().cloned())
+        .write_concern(write_concern)
+        .build();
+    let database = collection.client().database(&collection.namespace().db);
+    database.collection_with_options(collection.name(), options)
+}
+
+// `maxWriteBatchSize` and `maxBsonObjectSize` of every server since 3.6
+pub(crate) const MAX_WRITE_BATCH: usize = 100_000;
+const MAX_BSON_OBJECT_SIZE: usize = 16 * 1024 * 1024;
+// Room left in a command for its name, `ordered`, `writeConcern` and the like
+const MAX_STATEMENTS_SIZE: usize = MAX_BSON_OBJECT_SIZE - 16 * 1024;
+
+// How many of `statements`, from the first, fit in one write command. At least one: a statement too large on its
+// own goes alone, for the server to reject.
+pub(crate) fn fitting<'a>(statements: impl IntoIterator<Item = &'a bson::Document>) -> Result<usize, Error> {
+    let mut size = 0;
+    let mut count = 0;
+    for statement in statements.into_iter().take(MAX_WRITE_BATCH) {
+        // The element's type byte and array index key come on top of the document
+        size += bson::to_vec(statement)?.len() + 8;
+        if count > 0 && size > MAX_STATEMENTS_SIZE {
+            break;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+// `statements` cut into commands of at most `max_statements` that stay under `maxBsonObjectSize`, in order
+pub(crate) fn batches(
+    mut statements: Vec<bson::Document>,
+    max_statements: usize,
+) -> Result<Vec<Vec<bson::Document>>, Error> {
+    let mut batches = Vec::new();
+    while !statements.is_empty() {
+        let count = fitting(statements.iter().take(max_statements.max(1)))?;
+        let rest = statements.split_off(count);
+        batches.push(std::mem::replace(&mut statements, rest));
+    }
+    Ok(batches)
+}
+
+// Raw unordered `update` command, the driver has no bulk write API
+pub(crate) fn update_command(
+    collection: &str,
+    updates: Vec<bson::Document>,
+    write_concern: Option<&mongodb::options::WriteConcern>,
+) -> Result<bson::Document, Error> {
+    let mut command = bson::doc! { "update": collection, "updates": updates, "ordered": false };
+    if let Some(write_concern) = write_concern {
+        command.insert("writeConcern", bson::to_bson(write_concern)?);
+    }
+    Ok(command)
+}
+
+// Sends `updates` as unordered `update` commands of `batches`, returns how many documents they modified. A command
+// failing on a network error or step-down is sent once more, so `updates` have to be safe to apply twice.
+pub(crate) async fn update_all(
+    collection: &mongodb::Collection<bson::Document>,
+    updates: Vec<bson::Document>,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<u64, Error> {
+    let database = collection.client().database(&collection.namespace().db);
+    let mut modified = 0;
+    for batch in batches(updates, MAX_WRITE_BATCH)? {
+        let command = update_command(collection.name(), batch, collection.write_concern())?;
+        modified += modified_count(&run_retrying(&database, command, breaker).await?)?;
+    }
+    Ok(modified)
+}
+
+// `update_all` for the statements of unordered `delete` commands, returns how many documents they deleted
+pub(crate) async fn delete_all(
+    collection: &mongodb::Collection<bson::Document>,
+    deletes: Vec<bson::Document>,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<u64, Error> {
+    let database = collection.client().database(&collection.namespace().db);
+    let mut deleted = 0;
+    for batch in batches(deletes, MAX_WRITE_BATCH)? {
+        let mut command = bson::doc! { "delete": collection.name(), "deletes": batch, "ordered": false };
+        if let Some(write_concern) = collection.write_concern() {
+            command.insert("writeConcern", bson::to_bson(write_concern)?);
+        }
+        let response = run_retrying(&database, command, breaker).await?;
+        deleted += written(&response, "n", Error::DeleteFailed)?;
+    }
+    Ok(deleted)
+}
+
+async fn run_retrying(
+    database: &mongodb::Database,
+    command: bson::Document,
+    breaker: Option<&CircuitBreaker>,
+) -> Result<bson::Document, Error> {
+    match breaker::guard(breaker, database.run_command(command.clone(), None)).await {
+        Err(Error::DBError(x)) if retryable(&x) => breaker::guard(breaker, database.run_command(command, None)).await,
+        x => x,
+    }
+}
+
+// Errors the driver retries its own writes after: network errors, a cleared pool and the step-down codes. Commands
+// sent with `run_command` carry no transaction number, the server doesn't label them.
+fn retryable(error: &mongodb::error::Error) -> bool {
+    use mongodb::error::ErrorKind;
+
+    match error.kind.as_ref() {
+        ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } => true,
+        ErrorKind::Command(x) => [11600, 11602, 10107, 13435, 13436, 189, 91, 7, 6, 89, 9001, 262].contains(&x.code),
+        _ => error.contains_label(mongodb::error::RETRYABLE_WRITE_ERROR),
+    }
+}
+
+// `nModified` of an `update` command response, failing on the first rejected statement
+pub(crate) fn modified_count(response: &bson::Document) -> Result<u64, Error> {
+    written(response, "nModified", Error::UpdateFailed)
+}
+
+// The `count` field of a write command response, failing with `failed` on the first rejected statement
+fn written(response: &bson::Document, count: &str, failed: fn(String) -> Error) -> Result<u64, Error> {
+    let errors = response.get_array("writeErrors").map(|x| x.as_slice()).unwrap_or_default();
+    if let Some(error) = errors.first().and_then(|x| x.as_document()) {
+        let message = error.get_str("errmsg").unwrap_or_default();
+        return Err(failed(format!("{} statements failed, first: {}", errors.len(), message)));
+    }
+    if let Ok(error) = response.get_document("writeConcernError") {
+        let message = error.get_str("errmsg").unwrap_or_default();
+        return Err(failed(format!("Write concern not satisfied: {}", message)));
+    }
+    Ok(response.get(count).and_then(crate::as_u64).unwrap_or_default())
+}
+
+// One statement of an `update` command. The `_id` goes to `$setOnInsert` unless the filter pins it already, so
+// do the `immutable` fields.
+pub(crate) fn upsert_statement(
+    filter: bson::Document,
+    mut set: bson::Document,
+    immutable: &[&str],
+    generated_id: impl FnOnce() -> IdType,
+    collation: Option<&mongodb::options::Collation>,
+) -> Result<bson::Document, Error> {
+    let id = set.remove("_id").filter(|x| !id_type::is_unset(x));
+    let mut on_insert = bson::Document::new();
+    if !matches!(filter.get("_id"), Some(x) if !matches!(x, bson::Bson::Document(_))) {
+        on_insert.insert("_id", id.unwrap_or_else(|| generated_id().into()));
+    }
+    let kept: Vec<String> =
+        set.keys().filter(|x| immutable.iter().any(|field| crate::immutable::overlaps(field, x))).cloned().collect();
+    for key in kept {
+        if let Some(value) = set.remove(&key) {
+            on_insert.insert(key, value);
+        }
+    }
+
+    let mut update = bson::Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !on_insert.is_empty() {
+        update.insert("$setOnInsert", on_insert);
+    }
+
+    let mut statement = bson::doc! { "q": filter, "u": update, "upsert": true };
+    if let Some(collation) = collation {
+        statement.insert("collation", bson::to_document(collation)?);
+    }
+    Ok(statement)
+}
+
+// Statuses of the `len` statements of one `update` command response
+pub(crate) fn upsert_statuses(response: &bson::Document, len: usize) -> Result<Vec<UpsertStatus>, Error> {
+    let mut statuses = vec![UpsertStatus::Updated; len];
+    let entries = |key: &str| {
+        let entries = response.get_array(key).map(|x| x.as_slice()).unwrap_or_default();
+        entries.iter().filter_map(|x| x.as_document()).filter_map(|x| Some((index(x)?, x)))
+    };
+
+    for (index, upserted) in entries("upserted") {
+        let id = upserted.get("_id").and_then(id_type::from_bson);
+        let id = id.ok_or_else(|| Error::UpdateFailed(format!("Upserted ID of unexpected type: {:?}", upserted)))?;
+        if let Some(status) = statuses.get_mut(index) {
+            *status = UpsertStatus::Created(id);
+        }
+    }
+    for (index, error) in entries("writeErrors") {
+        let code = error.get_i32("code").unwrap_or_default();
+        let message = error.get_str("errmsg").unwrap_or_default().to_string();
+        if let Some(status) = statuses.get_mut(index) {
+            *status = UpsertStatus::Failed { code, message };
+        }
+    }
+    // The writes went through but may not be durable as asked
+    if let Ok(error) = response.get_document("writeConcernError") {
+        let message = error.get_str("errmsg").unwrap_or_default();
+        return Err(Error::UpdateFailed(format!("Write concern not satisfied: {}", message)));
+    }
+
+    Ok(statuses)
+}
+
+fn index(entry: &bson::Document) -> Option<usize> {
+    match entry.get("index")? {
+        bson::Bson::Int32(x) => usize::try_from(*x).ok(),
+        bson::Bson::Int64(x) => usize::try_from(*x).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_split_by_count_and_size() {
+        let small: Vec<bson::Document> = (0..25).map(|x| bson::doc! { "q": { "_id": x } }).collect();
+        let lengths: Vec<usize> = batches(small, 10).unwrap().iter().map(Vec::len).collect();
+        assert_eq!(lengths, [10, 10, 5]);
+
+        // 6MB each, two fit under 16MB with their command
+        let large = bson::doc! { "u": { "$set": { "blob": "x".repeat(6 * 1024 * 1024) } } };
+        let lengths: Vec<usize> = batches(vec![large; 5], 1000).unwrap().iter().map(Vec::len).collect();
+        assert_eq!(lengths, [2, 2, 1]);
+
+        let oversized = bson::doc! { "u": { "$set": { "blob": "x".repeat(MAX_BSON_OBJECT_SIZE) } } };
+        assert_eq!(fitting([&oversized, &oversized]).unwrap(), 1);
+    }
+}